@@ -1,40 +1,106 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
 
 use crate::{
-    ast::{ASTNode, Op, Statement},
+    ast::{ASTNode, Op, Statement, UnaryOp},
     lex::{Position, Token},
+    module::{self, ModuleSource},
     r#type::DataType,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TypeCheckError {
     MISSING_ARGUMENT,
     TYPE_NOT_ASSIGNABLE(Position, Position, DataType, DataType),
     UNKNOWN_VARIABLE(Token),
     UNKNOWN_DATA_TYPE(Rc<String>, Position),
+    // a generic which couldn't be worked out from the arguments of a call or constructor
+    UNRESOLVED_GENERIC(Rc<String>, Position),
+    // the type, the interface, the method and the signature it should have, then the signature it
+    // does have if it has the method at all
+    INTERFACE_NOT_SATISFIED(
+        Position,
+        DataType,
+        Rc<String>,
+        Rc<String>,
+        Box<DataType>,
+        Option<Box<DataType>>,
+    ),
+    // the module path couldn't be found
+    UNKNOWN_MODULE(Position, Rc<String>),
+    // the name and the module which doesn't export it
+    UNKNOWN_EXPORT(Token, Rc<String>),
+    // a builtin called with the wrong number of arguments, its name, how many it takes and how
+    // many it was given
+    WRONG_ARGUMENT_COUNT(Position, Rc<String>, usize, usize),
+    // a constructor given a field the type doesn't have, the type and the field
+    UNKNOWN_FIELD(Position, Rc<String>, Rc<String>),
+    // a constructor which doesn't set one of the type's fields, the type and the field
+    UNSET_FIELD(Position, Rc<String>, Rc<String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeCheckWarning {
+    // a call to something marked @deprecated, with the message it was given if any
+    DEPRECATED(Token, Option<Rc<String>>),
 }
 
+// what's marked @deprecated, by the type it's a method of (if it is one) and its name
+type Deprecations = HashMap<(Option<Rc<String>>, Rc<String>), Option<Rc<String>>>;
+
 struct Scope {
     vars: HashMap<Rc<String>, DataType>,
+    // variables declared with a type keep it when they're given a new value
+    annotated: HashSet<Rc<String>>,
+}
+
+// the fields of a type declaration, and its generics in the order they first appear
+#[derive(Clone)]
+struct TypeDecl {
+    generics: Vec<Rc<String>>,
+    fields: Vec<(Rc<String>, DataType)>,
 }
 
 pub struct Analyser {
     scope_index: usize,
     scopes: Vec<Scope>,
+    types: HashMap<Rc<String>, TypeDecl>,
+    // the methods each interface requires, without self
+    interfaces: HashMap<Rc<String>, Vec<(Rc<String>, DataType)>>,
+    // the methods defined for each type, without self
+    methods: HashMap<Rc<String>, HashMap<Rc<String>, DataType>>,
+    // where the modules this one imports are found
+    search_path: Vec<PathBuf>,
+    deprecated: Deprecations,
+    // the builtins, which take exactly as many arguments as their type has parameters
+    natives: HashSet<Rc<String>>,
+    pub warnings: Vec<TypeCheckWarning>,
 }
 
 impl Analyser {
-    pub fn new() -> Self {
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
         return Analyser {
             scope_index: 0,
             scopes: vec![Scope {
                 vars: HashMap::new(),
+                annotated: HashSet::new(),
             }],
+            types: HashMap::new(),
+            interfaces: HashMap::new(),
+            methods: HashMap::new(),
+            search_path,
+            deprecated: HashMap::new(),
+            natives: HashSet::new(),
+            warnings: vec![],
         };
     }
 
     pub fn analyse(&mut self, ast: &ASTNode) -> Result<(), TypeCheckError> {
         self.init_builtins();
+        self.collect_declarations(ast);
         let res = self.visit(ast);
         if res.is_err() {
             return Err(res.err().unwrap());
@@ -45,14 +111,19 @@ impl Analyser {
     fn init_builtins(&mut self) {
         self.scopes[self.scope_index].vars.insert(
             Rc::new("print".to_string()),
-            DataType::FN(vec![DataType::STRING], Box::new(DataType::VOID)),
+            DataType::FN(vec![DataType::ANY], Box::new(DataType::VOID)),
         );
         self.scopes[self.scope_index].vars.insert(
             Rc::new("len".to_string()),
-            DataType::FN(
-                vec![DataType::SLICE(Box::new(DataType::U32))],
-                Box::new(DataType::U32),
-            ),
+            DataType::FN(vec![DataType::ANY], Box::new(DataType::I64)),
+        );
+        self.scopes[self.scope_index].vars.insert(
+            Rc::new("append".to_string()),
+            DataType::FN(vec![DataType::ANY, DataType::ANY], Box::new(DataType::ANY)),
+        );
+        self.scopes[self.scope_index].vars.insert(
+            Rc::new("annotations".to_string()),
+            DataType::FN(vec![DataType::ANY], Box::new(DataType::ANY)),
         );
 
         // so we don't actually know what the type is here, so how do we do this...
@@ -66,32 +137,310 @@ impl Analyser {
         self.scopes[self.scope_index]
             .vars
             .insert(Rc::new("__platform__".to_string()), DataType::STRING);
+        self.natives = self.scopes[self.scope_index]
+            .vars
+            .iter()
+            .filter(|(_, t)| matches!(t, DataType::FN(_, _)))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        // what a for loop needs, __iter returns whether it's done and sets self.value otherwise
+        self.interfaces.insert(
+            Rc::new("Iterator".to_string()),
+            vec![(
+                Rc::new("__iter".to_string()),
+                DataType::FN(vec![], Box::new(DataType::BOOL)),
+            )],
+        );
+    }
+
+    // methods and interfaces can be used before they're declared, so they're found up front
+    fn collect_declarations(&mut self, ast: &ASTNode) {
+        let Statement::PROGRAM(items) = &ast.statement else {
+            return;
+        };
+        self.deprecated.extend(deprecations(ast));
+        for item in items {
+            match &item.declaration().statement {
+                Statement::NAMED_FUNCTION(name, params, return_type, _) => {
+                    if let Some(receiver) = method_receiver(params) {
+                        self.methods
+                            .entry(receiver)
+                            .or_default()
+                            .insert(name.as_identifier(), method_signature(params, return_type));
+                    }
+                }
+                Statement::NAMED_INTERFACE_DECL(name, methods) => {
+                    self.interfaces.insert(
+                        name.as_identifier(),
+                        methods
+                            .iter()
+                            .map(|(method, params, return_type)| {
+                                (
+                                    method.as_identifier(),
+                                    method_signature(params, return_type),
+                                )
+                            })
+                            .collect(),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // the first method the type is missing or has with the wrong signature, if it doesn't satisfy
+    // the interface. seen stops an interface which refers to itself from going round forever
+    fn unsatisfied_method(
+        &self,
+        actual: &DataType,
+        interface: &Rc<String>,
+        seen: &mut HashSet<(Rc<String>, Rc<String>)>,
+    ) -> Option<(Rc<String>, DataType, Option<DataType>)> {
+        let required = self.interfaces.get(interface)?;
+        let type_name = match actual {
+            DataType::ANY => return None,
+            DataType::NAMED_REFERENCE(name) | DataType::INSTANCE(name, _) => name.clone(),
+            _ => Rc::new(actual.print()),
+        };
+        if type_name == *interface || !seen.insert((type_name.clone(), interface.clone())) {
+            return None;
+        }
+
+        // another interface has the methods it requires
+        let methods: HashMap<Rc<String>, DataType> = match self.interfaces.get(&type_name) {
+            Some(methods) => methods.iter().cloned().collect(),
+            None => self.methods.get(&type_name).cloned().unwrap_or_default(),
+        };
+        for (method, signature) in required {
+            match methods.get(method) {
+                None => return Some((method.clone(), signature.clone(), None)),
+                Some(found) if !self.conforms(signature, found, seen) => {
+                    return Some((method.clone(), signature.clone(), Some(found.clone())))
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // like assignable_from, but a type can be used as an interface it satisfies
+    fn conforms(
+        &self,
+        expected: &DataType,
+        actual: &DataType,
+        seen: &mut HashSet<(Rc<String>, Rc<String>)>,
+    ) -> bool {
+        if *expected == DataType::ANY || *actual == DataType::ANY {
+            return true;
+        }
+        if let DataType::NAMED_REFERENCE(interface) = expected {
+            if self.interfaces.contains_key(interface) {
+                return self.unsatisfied_method(actual, interface, seen).is_none();
+            }
+        }
+        match (expected, actual) {
+            (DataType::SLICE(a), DataType::SLICE(b)) => self.conforms(a, b, seen),
+            // parameters can go either way so a method can take its own type where the
+            // interface takes the interface
+            (DataType::FN(a_params, a_return), DataType::FN(b_params, b_return)) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .iter()
+                        .zip(b_params)
+                        .all(|(a, b)| self.conforms(a, b, seen) || self.conforms(b, a, seen))
+                    && self.conforms(a_return, b_return, seen)
+            }
+            _ => expected.clone().assignable_from(actual.clone()),
+        }
+    }
+
+    fn check_assignable(
+        &self,
+        lhs_position: &Position,
+        rhs_position: &Position,
+        expected: &DataType,
+        actual: DataType,
+    ) -> Result<(), TypeCheckError> {
+        if let DataType::NAMED_REFERENCE(interface) = expected {
+            if self.interfaces.contains_key(interface) {
+                let unsatisfied = self.unsatisfied_method(&actual, interface, &mut HashSet::new());
+                if let Some((method, signature, found)) = unsatisfied {
+                    return Err(TypeCheckError::INTERFACE_NOT_SATISFIED(
+                        rhs_position.clone(),
+                        actual,
+                        interface.clone(),
+                        method,
+                        Box::new(signature),
+                        found.map(Box::new),
+                    ));
+                }
+                return Ok(());
+            }
+        }
+        if !self.conforms(expected, &actual, &mut HashSet::new()) {
+            return Err(TypeCheckError::TYPE_NOT_ASSIGNABLE(
+                lhs_position.clone(),
+                rhs_position.clone(),
+                expected.clone(),
+                actual,
+            ));
+        }
+        Ok(())
+    }
+
+    // fill in the generics of the parameter from the argument, then check the argument fits. the
+    // first argument a generic is worked out from wins, so f(a: $T, b: $T) called with an i64 and
+    // a string fails on the string
+    fn check_argument(
+        &self,
+        param_position: &Position,
+        arg_position: &Position,
+        param: &DataType,
+        arg: DataType,
+        bindings: &mut HashMap<Rc<String>, DataType>,
+    ) -> Result<(), TypeCheckError> {
+        bind_generics(param, &arg, bindings);
+        let expected = param.substitute(bindings);
+        self.check_assignable(param_position, arg_position, &expected, arg)
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Scope {
+            vars: HashMap::new(),
+            annotated: HashSet::new(),
+        });
+        self.scope_index += 1;
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.scope_index -= 1;
+    }
+
+    fn check_deprecated(&mut self, receiver: Option<Rc<String>>, name: &Token) {
+        if let Some(message) = self.deprecated.get(&(receiver, name.as_identifier())) {
+            self.warnings
+                .push(TypeCheckWarning::DEPRECATED(name.clone(), message.clone()));
+        }
+    }
+
+    fn lookup(&self, name: &Rc<String>) -> Option<DataType> {
+        for scope in self.scopes[..=self.scope_index].iter().rev() {
+            if let Some(t) = scope.vars.get(name) {
+                return Some(t.clone());
+            }
+        }
+        None
+    }
+
+    // how many arguments a call to name takes, if it's a builtin which hasn't been shadowed
+    fn native_arity(&self, name: &Rc<String>) -> Option<usize> {
+        if !self.natives.contains(name) {
+            return None;
+        }
+        let scope = self.scopes[..=self.scope_index]
+            .iter()
+            .rposition(|scope| scope.vars.contains_key(name));
+        if scope != Some(0) {
+            return None;
+        }
+        match &self.scopes[0].vars[name] {
+            DataType::FN(params, _) => Some(params.len()),
+            _ => None,
+        }
     }
 
     fn visit(&mut self, statement: &ASTNode) -> Result<DataType, TypeCheckError> {
         match &statement.statement {
             Statement::PROGRAM(p) => self.visit_program(p),
             Statement::BIN_OP(left, right, op) => self.visit_bin_op(left, right, op),
-            Statement::IMPORT(module) => Ok(DataType::U32),
+            Statement::UNARY_OP(UnaryOp::NOT, _) => Ok(DataType::BOOL),
+            Statement::UNARY_OP(UnaryOp::NEG, expr) => self.visit(expr),
             Statement::NAMED_FUNCTION(t, params, return_type, body) => {
                 self.visit_named_fn(t, params, return_type, body)
             }
-            Statement::TEST(name, body) => Ok(DataType::U32),
-            Statement::IF(cond, body, else_body) => self.visit_if(cond, body, else_body),
-            Statement::FOR(var, iter_obj, body) => Ok(DataType::U32),
-            Statement::RANGE(first, second) => Ok(DataType::U32),
+            Statement::LAMBDA(params, return_type, body) => {
+                self.visit_lambda(params, return_type, body)
+            }
             Statement::DEFINE(t, typ, val) => self.visit_define(t, typ, val),
             Statement::ASSIGN(lhs, rhs) => self.visit_assign(lhs, rhs),
             Statement::CALL(calee, args) => self.visit_call(calee, args),
             Statement::LITERAL_NUM(n) => self.visit_literal_num(n),
             Statement::LITERAL_BOOL(_) => Ok(DataType::BOOL),
             Statement::STRING(s) => self.visit_string(s),
+            Statement::INTERPOLATED_STRING(parts) => self.visit_interpolated_string(parts),
             Statement::SLICE(s) => self.visit_slice(s),
             Statement::VARIABLE(t) => self.visit_variable(t),
             Statement::NAMED_TYPE_DECL(t, decls) => self.visit_named_type_decl(&t, &decls),
-            Statement::STRUCT_ACCESS(strct, member) => Ok(DataType::U32),
-            _ => panic!("Missing visit for {:?}", statement),
+            Statement::NAMED_ENUM_DECL(t, _) => self.visit_named_enum_decl(&t),
+            // already collected up front
+            Statement::NAMED_INTERFACE_DECL(_, _) => Ok(DataType::U32),
+            Statement::FOR(_, iter_obj, body) => self.visit_for(iter_obj, body),
+            Statement::STRUCT_ACCESS(strct, member) => self.visit_struct_access(strct, member),
+            Statement::FROM_IMPORT(path, names) => self.visit_from_import(path, names),
+            Statement::DOC(_, declaration) => self.visit(declaration),
+            Statement::BLOCK(_) => self.visit_block(statement),
+            // nothing is known about the rest yet, but what's inside them is still checked
+            _ => self.visit_children(statement),
+        }
+    }
+
+    // the names are only known at runtime, but whether the module exports them can be checked now
+    fn visit_from_import(
+        &mut self,
+        path: &Vec<Token>,
+        names: &Vec<(Token, Option<Token>)>,
+    ) -> Result<DataType, TypeCheckError> {
+        let module_path = Rc::new(
+            path.iter()
+                .map(|p| p.as_identifier().to_string())
+                .collect::<Vec<String>>()
+                .join("."),
+        );
+        let Some(source) = module::find(&module_path, &self.search_path) else {
+            let pos = path[0].pos.join(path[path.len() - 1].pos.clone());
+            return Err(TypeCheckError::UNKNOWN_MODULE(pos, module_path));
+        };
+        let Some(exports) = module::exports(&source) else {
+            return Ok(DataType::ANY);
+        };
+        for (name, _) in names {
+            if !exports.contains(&name.as_identifier()) {
+                return Err(TypeCheckError::UNKNOWN_EXPORT(name.clone(), module_path));
+            }
+        }
+        // so calls to what's deprecated in the module are warned about here too
+        if let ModuleSource::FILE(path) = &source {
+            let deprecated = module::parse(path).map(|ast| deprecations(&ast));
+            for ((receiver, name), message) in deprecated.unwrap_or_default() {
+                let alias = names.iter().find_map(|(imported, alias)| {
+                    (receiver.is_none() && imported.as_identifier() == name)
+                        .then(|| alias.as_ref().unwrap_or(imported).as_identifier())
+                });
+                self.deprecated
+                    .insert((receiver, alias.unwrap_or(name)), message);
+            }
+        }
+        Ok(DataType::ANY)
+    }
+
+    fn visit_children(&mut self, statement: &ASTNode) -> Result<DataType, TypeCheckError> {
+        for child in statement.children() {
+            let res = self.visit(child);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
         }
+        Ok(DataType::ANY)
+    }
+
+    // what's declared in a block is only visible inside it
+    fn visit_block(&mut self, block: &ASTNode) -> Result<DataType, TypeCheckError> {
+        self.push_scope();
+        let res = self.visit_children(block);
+        self.pop_scope();
+        res
     }
 
     fn visit_program(&mut self, program: &Vec<ASTNode>) -> Result<DataType, TypeCheckError> {
@@ -127,16 +476,32 @@ impl Analyser {
         //     return Err(())
         // }
 
-        return Ok(lhs_type);
-    }
+        match op {
+            Op::EQ
+            | Op::NEQ
+            | Op::GT
+            | Op::GE
+            | Op::LT
+            | Op::LE
+            | Op::LOGICAL_OR
+            | Op::LOGICAL_AND
+            | Op::IN => return Ok(DataType::BOOL),
+            _ => {}
+        }
 
-    fn visit_if(
-        &mut self,
-        cond: &Box<ASTNode>,
-        body: &Box<ASTNode>,
-        else_body: &Option<Box<ASTNode>>,
-    ) -> Result<DataType, TypeCheckError> {
-        Ok(DataType::U32)
+        // a type with its own operator gives back whatever the method does
+        if let DataType::NAMED_REFERENCE(name) | DataType::INSTANCE(name, _) = &lhs_type {
+            let method = operator_method(op).and_then(|method| {
+                self.methods
+                    .get(name)
+                    .and_then(|methods| methods.get(&Rc::new(method.to_string())))
+            });
+            if let Some(DataType::FN(_, return_type)) = method {
+                return Ok(*return_type.clone());
+            }
+        }
+
+        return Ok(lhs_type);
     }
 
     fn resolve_data_type_to_concrete_type(
@@ -145,21 +510,25 @@ impl Analyser {
         t: DataType,
     ) -> Result<DataType, TypeCheckError> {
         match t {
-            DataType::NAMED_REFERENCE(named_reference) => {
-                if !self.scopes[self.scope_index]
-                    .vars
-                    .contains_key(&named_reference)
+            DataType::NAMED_REFERENCE(ref named_reference)
+            | DataType::INSTANCE(ref named_reference, _) => {
+                if self.lookup(named_reference).is_none()
+                    && !self.types.contains_key(named_reference)
+                    && !self.interfaces.contains_key(named_reference)
                 {
                     return Err(TypeCheckError::UNKNOWN_DATA_TYPE(
                         named_reference.clone(),
                         position.clone(),
                     ));
                 }
-                Ok(self.scopes[self.scope_index]
-                    .vars
-                    .get(&named_reference)
-                    .unwrap()
-                    .clone())
+                Ok(t)
+            }
+            DataType::SLICE(item) => {
+                let res = self.resolve_data_type_to_concrete_type(position, *item);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                Ok(DataType::SLICE(Box::new(res.unwrap())))
             }
             _ => Ok(t),
         }
@@ -173,83 +542,48 @@ impl Analyser {
     ) -> Result<DataType, TypeCheckError> {
         let identifier = token.as_identifier();
 
-        if self.scopes[self.scope_index].vars.contains_key(&identifier) {
-            let rhs_value = val.as_ref().unwrap();
-            let rhs_type = self.visit(&rhs_value);
-            let lhs_type = self.scopes[self.scope_index].vars.get(&identifier).unwrap();
-
-            if rhs_type.is_err() {
-                return Err(rhs_type.err().unwrap());
+        let declared_type: Option<DataType> = if let Some(t) = typ {
+            let resolved_type_res =
+                self.resolve_data_type_to_concrete_type(token.pos.clone(), t.clone());
+            if resolved_type_res.is_err() {
+                return Err(resolved_type_res.err().unwrap());
             }
-
-            let rhs_unrapped = rhs_type.unwrap();
-            let res = lhs_type.clone().assignable_from(rhs_unrapped.clone());
-            if !res {
-                return Err(TypeCheckError::TYPE_NOT_ASSIGNABLE(
-                    token.pos.clone(),
-                    rhs_value.position.clone(),
-                    lhs_type.clone(),
-                    rhs_unrapped.clone(),
-                ));
-            }
-            return Ok(lhs_type.clone());
+            Some(resolved_type_res.unwrap())
+        } else if self.scopes[self.scope_index]
+            .annotated
+            .contains(&identifier)
+        {
+            self.scopes[self.scope_index].vars.get(&identifier).cloned()
         } else {
-            if let Some(t) = typ {
-                if let Some(v) = val {
-                    // ensure types are same
-
-                    let value_type = self.visit(v);
-
-                    if value_type.is_err() {
-                        return Err(value_type.err().unwrap());
-                    }
-
-                    let resolved_type_res =
-                        self.resolve_data_type_to_concrete_type(token.pos.clone(), t.clone());
-
-                    if resolved_type_res.is_err() {
-                        return Err(resolved_type_res.err().unwrap());
-                    }
+            None
+        };
 
-                    let resolved_type = resolved_type_res.unwrap();
-
-                    if resolved_type
-                        .clone()
-                        .assignable_from(value_type.as_ref().unwrap().clone())
-                    {
-                        self.scopes[self.scope_index]
-                            .vars
-                            .insert(identifier, t.clone());
-                        return Ok(resolved_type.clone());
-                    } else {
-                        return Err(TypeCheckError::TYPE_NOT_ASSIGNABLE(
-                            token.pos.clone(),
-                            v.position.clone(),
-                            t.clone(),
-                            value_type.unwrap(),
-                        ));
-                    }
-                } else {
-                    // todo i think this is an err?
-                    return self.resolve_data_type_to_concrete_type(token.pos.clone(), t.clone());
-                }
-            } else {
-                let rhs_value = val.as_ref().unwrap();
-                let value_type = self.visit(&rhs_value);
+        let mut value_type = DataType::ANY;
+        if let Some(v) = val {
+            let value_type_res = self.visit(v);
+            if value_type_res.is_err() {
+                return Err(value_type_res.err().unwrap());
+            }
+            value_type = value_type_res.unwrap();
 
-                if value_type.is_err() {
-                    return Err(value_type.err().unwrap());
+            if let Some(t) = &declared_type {
+                let res = self.check_assignable(&token.pos, &v.position, t, value_type.clone());
+                if res.is_err() {
+                    return Err(res.err().unwrap());
                 }
-
-                let v_type = value_type.unwrap();
-
-                self.scopes[self.scope_index]
-                    .vars
-                    .insert(identifier, v_type.clone());
-
-                return Ok(v_type);
             }
         }
+
+        if typ.is_some() {
+            self.scopes[self.scope_index]
+                .annotated
+                .insert(identifier.clone());
+        }
+        let var_type = declared_type.unwrap_or(value_type);
+        self.scopes[self.scope_index]
+            .vars
+            .insert(identifier, var_type.clone());
+        return Ok(var_type);
     }
 
     fn visit_assign(
@@ -257,29 +591,37 @@ impl Analyser {
         lhs: &Box<ASTNode>,
         rhs: &Box<ASTNode>,
     ) -> Result<DataType, TypeCheckError> {
-        // todo this is never parsed, may remove!
-        // let lhs_type = self.visit(&lhs);
-        // let rhs_type = self.visit(&rhs);
+        let lhs_type = self.visit(&lhs);
+        let rhs_type = self.visit(&rhs);
 
-        // if lhs_type.is_err() {
-        //     return Err(lhs_type.err().unwrap());
-        // }
-        // if rhs_type.is_err() {
-        //     return Err(rhs_type.err().unwrap());
-        // }
+        if lhs_type.is_err() {
+            return Err(lhs_type.err().unwrap());
+        }
+        if rhs_type.is_err() {
+            return Err(rhs_type.err().unwrap());
+        }
 
-        // let lhs_unrapped = lhs_type.unwrap();
-        // let rhs_unrapped = rhs_type.unwrap();
-        // let res = lhs_unrapped.clone().assignable_from(rhs_unrapped.clone());
-        // if !res {
-        //     return Err(TypeCheckError::TYPE_NOT_ASSIGNABLE(
-        //         lhs.position.clone(),
-        //         rhs.position.clone(),
-        //         lhs_unrapped.clone(),
-        //         rhs_unrapped.clone(),
-        //     ));
-        // }
-        Ok(DataType::U32)
+        let lhs_unrapped = lhs_type.unwrap();
+        let rhs_unrapped = rhs_type.unwrap();
+        // a variable declared without a type takes on the type of whatever it's given
+        if let Statement::VARIABLE(t) = &lhs.statement {
+            let name = t.as_identifier();
+            let scope = self.scopes[..=self.scope_index]
+                .iter_mut()
+                .rev()
+                .find(|scope| scope.vars.contains_key(&name));
+            if let Some(scope) = scope {
+                if !scope.annotated.contains(&name) {
+                    scope.vars.insert(name, rhs_unrapped.clone());
+                    return Ok(rhs_unrapped);
+                }
+            }
+        }
+        let res = self.check_assignable(&lhs.position, &rhs.position, &lhs_unrapped, rhs_unrapped);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        Ok(lhs_unrapped)
     }
 
     fn visit_call(
@@ -287,6 +629,27 @@ impl Analyser {
         callee: &Box<ASTNode>,
         args: &Vec<ASTNode>,
     ) -> Result<DataType, TypeCheckError> {
+        if let Statement::VARIABLE(v) = &callee.statement {
+            self.check_deprecated(None, v);
+            if let Some(decl) = self.types.get(&v.as_identifier()).cloned() {
+                return self.visit_constructor(callee, v.as_identifier(), &decl, args);
+            }
+            if let Some(arity) = self.native_arity(&v.as_identifier()) {
+                // a spread argument's length is only known at runtime
+                let spread = args
+                    .iter()
+                    .any(|arg| matches!(arg.statement, Statement::SPREAD(_)));
+                if !spread && args.len() != arity {
+                    return Err(TypeCheckError::WRONG_ARGUMENT_COUNT(
+                        callee.position.clone(),
+                        v.as_identifier(),
+                        arity,
+                        args.len(),
+                    ));
+                }
+            }
+        }
+
         let callee_type_res: Result<DataType, TypeCheckError> = self.visit(&callee);
 
         if callee_type_res.is_err() {
@@ -296,59 +659,349 @@ impl Analyser {
         let callee_type = callee_type_res.unwrap();
 
         match callee_type {
-            // todo check params match
             DataType::FN(params, return_type) => {
+                let mut bindings: HashMap<Rc<String>, DataType> = HashMap::new();
+                // keyword and spread arguments are only matched up with parameters at runtime,
+                // so anything they would have told us about the generics is unknown
+                let mut inferrable = true;
                 let mut i = 0;
-                for param in params {
-                    if i >= args.len() {
-                        return Err(TypeCheckError::MISSING_ARGUMENT);
-                    }
-                    let arg_typ_res = self.visit(&args[i]);
+                for arg in args {
+                    let arg_value = match &arg.statement {
+                        Statement::ASSIGN(_, value) | Statement::SPREAD(value) => {
+                            inferrable = false;
+                            value
+                        }
+                        _ => arg,
+                    };
+                    let arg_typ_res = self.visit(arg_value);
                     if arg_typ_res.is_err() {
                         return Err(arg_typ_res.err().unwrap());
                     }
-                    let arg_type = arg_typ_res.unwrap();
-                    if !param.clone().assignable_from(arg_type.clone()) {
-                        return Err(TypeCheckError::TYPE_NOT_ASSIGNABLE(
-                            callee.position.clone(),
-                            args[i].position.clone(),
-                            param.clone(),
-                            arg_type.clone(),
-                        ));
+                    if !inferrable || i >= params.len() {
+                        continue;
+                    }
+                    let res = self.check_argument(
+                        &callee.position,
+                        &arg.position,
+                        &params[i],
+                        arg_typ_res.unwrap(),
+                        &mut bindings,
+                    );
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
                     }
                     i += 1;
                 }
-                return Ok(*return_type);
+                return resolve_generics(&return_type, &bindings, &callee.position, inferrable);
+            }
+            _ => {
+                for arg in args {
+                    let res = self.visit(arg);
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
+                    }
+                }
+                Ok(DataType::ANY)
             }
-            _ => panic!("found weird callee type {:?}", callee_type),
         }
     }
 
+    // types are constructed with keyword arguments, which is what the generics are worked out from
+    fn visit_constructor(
+        &mut self,
+        callee: &Box<ASTNode>,
+        name: Rc<String>,
+        decl: &TypeDecl,
+        args: &Vec<ASTNode>,
+    ) -> Result<DataType, TypeCheckError> {
+        let mut bindings: HashMap<Rc<String>, DataType> = HashMap::new();
+        let mut inferrable = true;
+        for arg in args {
+            let Statement::ASSIGN(field, value) = &arg.statement else {
+                inferrable = false;
+                let res = self.visit(arg);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                continue;
+            };
+            let value_type = self.visit(value);
+            if value_type.is_err() {
+                return Err(value_type.err().unwrap());
+            }
+            let field_type = match &field.statement {
+                Statement::VARIABLE(f) => {
+                    let field_type = decl
+                        .fields
+                        .iter()
+                        .find(|(name, _)| *name == f.as_identifier())
+                        .map(|(_, t)| t.clone());
+                    if field_type.is_none() {
+                        return Err(TypeCheckError::UNKNOWN_FIELD(
+                            field.position.clone(),
+                            name.clone(),
+                            f.as_identifier(),
+                        ));
+                    }
+                    field_type
+                }
+                _ => None,
+            };
+            if let Some(field_type) = field_type {
+                let res = self.check_argument(
+                    &field.position,
+                    &value.position,
+                    &field_type,
+                    value_type.unwrap(),
+                    &mut bindings,
+                );
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+            }
+        }
+
+        // with positional or spread arguments there's already a runtime error
+        if inferrable {
+            for (field, _) in &decl.fields {
+                let set = args.iter().any(|arg| match &arg.statement {
+                    Statement::ASSIGN(f, _) => match &f.statement {
+                        Statement::VARIABLE(f) => f.as_identifier() == *field,
+                        _ => false,
+                    },
+                    _ => false,
+                });
+                if !set {
+                    return Err(TypeCheckError::UNSET_FIELD(
+                        callee.position.clone(),
+                        name.clone(),
+                        field.clone(),
+                    ));
+                }
+            }
+        }
+
+        if decl.generics.is_empty() {
+            return Ok(DataType::NAMED_REFERENCE(name));
+        }
+        let instance = DataType::INSTANCE(
+            name,
+            decl.generics
+                .iter()
+                .map(|g| DataType::GENERIC(g.clone()))
+                .collect(),
+        );
+        return resolve_generics(&instance, &bindings, &callee.position, inferrable);
+    }
+
+    fn visit_struct_access(
+        &mut self,
+        strct: &Box<ASTNode>,
+        member: &Token,
+    ) -> Result<DataType, TypeCheckError> {
+        let strct_type = self.visit(strct);
+        if strct_type.is_err() {
+            return Err(strct_type.err().unwrap());
+        }
+        let (name, params) = match strct_type.unwrap() {
+            DataType::NAMED_REFERENCE(name) => (name, vec![]),
+            DataType::INSTANCE(name, params) => (name, params),
+            _ => return Ok(DataType::ANY),
+        };
+        self.check_deprecated(Some(name.clone()), member);
+        let method = match self.interfaces.get(&name) {
+            Some(methods) => methods
+                .iter()
+                .find(|(method, _)| *method == member.as_identifier())
+                .map(|(_, t)| t.clone()),
+            None => self
+                .methods
+                .get(&name)
+                .and_then(|methods| methods.get(&member.as_identifier()).cloned()),
+        };
+        let Some(decl) = self.types.get(&name) else {
+            return Ok(method.unwrap_or(DataType::ANY));
+        };
+        let Some((_, field_type)) = decl
+            .fields
+            .iter()
+            .find(|(field, _)| *field == member.as_identifier())
+        else {
+            // fields set at runtime aren't known
+            return Ok(method.unwrap_or(DataType::ANY));
+        };
+
+        // a generic type used without its parameters could have anything in it
+        let mut bindings: HashMap<Rc<String>, DataType> = HashMap::new();
+        for (i, generic) in decl.generics.iter().enumerate() {
+            bindings.insert(
+                generic.clone(),
+                params.get(i).cloned().unwrap_or(DataType::ANY),
+            );
+        }
+        Ok(field_type.substitute(&bindings))
+    }
+
+    // anything with a type of its own has to be an iterator to be looped over
+    fn visit_for(
+        &mut self,
+        iter_obj: &Box<ASTNode>,
+        body: &Box<ASTNode>,
+    ) -> Result<DataType, TypeCheckError> {
+        let iter_type = self.visit(iter_obj);
+        if iter_type.is_err() {
+            return Err(iter_type.err().unwrap());
+        }
+        let iter_type = iter_type.unwrap();
+        // slices and maps are looped over by the runtime, anything else has to be an Iterator
+        let must_be_iterator = match &iter_type {
+            DataType::NAMED_REFERENCE(name) | DataType::INSTANCE(name, _) => {
+                self.types.contains_key(name)
+            }
+            DataType::ANY
+            | DataType::SLICE(_)
+            | DataType::DYNAMIC_OBJECT(_)
+            | DataType::GENERIC(_) => false,
+            _ => true,
+        };
+        if must_be_iterator {
+            let res = self.check_assignable(
+                &iter_obj.position,
+                &iter_obj.position,
+                &DataType::NAMED_REFERENCE(Rc::new("Iterator".to_string())),
+                iter_type,
+            );
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+        }
+        self.visit(body)
+    }
+
     fn visit_literal_num(&mut self, n: &Token) -> Result<DataType, TypeCheckError> {
-        Ok(DataType::U32)
+        let (_, typ) = DataType::from_number_literal(&n.as_number());
+        Ok(typ)
     }
 
     fn visit_string(&mut self, s: &Token) -> Result<DataType, TypeCheckError> {
         Ok(DataType::STRING)
     }
 
+    fn visit_interpolated_string(
+        &mut self,
+        parts: &Vec<ASTNode>,
+    ) -> Result<DataType, TypeCheckError> {
+        for part in parts {
+            let res = self.visit(part);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+        }
+        Ok(DataType::STRING)
+    }
+
     fn visit_slice(&mut self, s: &Vec<ASTNode>) -> Result<DataType, TypeCheckError> {
-        // todo check whats in the slice
-        Ok(DataType::SLICE(Box::new(DataType::U32)))
+        let mut item_types: Vec<DataType> = vec![];
+        for item in s {
+            let res = self.visit(item);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+            item_types.push(res.unwrap());
+        }
+        // a slice of mixed types could have anything in it
+        match item_types.first() {
+            Some(first) if item_types.iter().all(|t| t == first) => {
+                Ok(DataType::SLICE(Box::new(first.clone())))
+            }
+            _ => Ok(DataType::SLICE(Box::new(DataType::ANY))),
+        }
     }
 
     fn visit_variable(&mut self, t: &Token) -> Result<DataType, TypeCheckError> {
-        if self.scopes[self.scope_index]
-            .vars
-            .contains_key(&t.as_identifier())
-        {
-            return Ok(self.scopes[self.scope_index]
-                .vars
-                .get(&t.as_identifier())
-                .unwrap()
-                .clone());
+        // natives, imports and names from an earlier compilation aren't known until runtime
+        Ok(self.lookup(&t.as_identifier()).unwrap_or(DataType::ANY))
+    }
+
+    // the types of the parameters a call can be checked against. default values are evaluated
+    // where the function is defined so they're checked here too
+    fn visit_params(&mut self, params: &Vec<ASTNode>) -> Result<Vec<DataType>, TypeCheckError> {
+        let mut param_types: Vec<DataType> = vec![];
+        for param in params {
+            match &param.statement {
+                Statement::DEFINE(t, typ, default) => {
+                    let mut param_type = DataType::ANY;
+                    if let Some(typ) = typ {
+                        let res =
+                            self.resolve_data_type_to_concrete_type(t.pos.clone(), typ.clone());
+                        if res.is_err() {
+                            return Err(res.err().unwrap());
+                        }
+                        param_type = res.unwrap();
+                    }
+                    if let Some(default) = default {
+                        let default_type = self.visit(default);
+                        if default_type.is_err() {
+                            return Err(default_type.err().unwrap());
+                        }
+                        let res = self.check_assignable(
+                            &t.pos,
+                            &default.position,
+                            &param_type,
+                            default_type.unwrap(),
+                        );
+                        if res.is_err() {
+                            return Err(res.err().unwrap());
+                        }
+                    }
+                    param_types.push(param_type);
+                }
+                Statement::DESTRUCTURE(_, _) => param_types.push(DataType::ANY),
+                // the extra arguments a rest parameter collects aren't checked
+                _ => {}
+            }
         }
-        return Err(TypeCheckError::UNKNOWN_VARIABLE(t.clone()));
+        Ok(param_types)
+    }
+
+    fn visit_fn_body(
+        &mut self,
+        params: &Vec<ASTNode>,
+        param_types: &Vec<DataType>,
+        body: &Box<ASTNode>,
+    ) -> Result<DataType, TypeCheckError> {
+        self.push_scope();
+        let mut i = 0;
+        for param in params {
+            match &param.statement {
+                Statement::DEFINE(t, _, _) => {
+                    self.scopes[self.scope_index]
+                        .vars
+                        .insert(t.as_identifier(), param_types[i].clone());
+                    self.scopes[self.scope_index]
+                        .annotated
+                        .insert(t.as_identifier());
+                    i += 1;
+                }
+                Statement::SPREAD(rest) => {
+                    if let Statement::DEFINE(t, typ, _) = &rest.statement {
+                        self.scopes[self.scope_index].vars.insert(
+                            t.as_identifier(),
+                            typ.clone()
+                                .unwrap_or(DataType::SLICE(Box::new(DataType::ANY))),
+                        );
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        // the body shares the parameters' scope
+        let res = match &body.statement {
+            Statement::BLOCK(_) => self.visit_children(body),
+            _ => self.visit(body),
+        };
+        self.pop_scope();
+        res
     }
 
     fn visit_named_fn(
@@ -358,48 +1011,240 @@ impl Analyser {
         return_type: &Option<DataType>,
         body: &Box<ASTNode>,
     ) -> Result<DataType, TypeCheckError> {
-        let mut param_types: Vec<DataType> = vec![];
-        for param in params {
-            let res = self.visit(param);
-            if res.is_err() {
-                return Err(res.err().unwrap());
-            }
-            param_types.push(res.unwrap());
+        let param_types = self.visit_params(params);
+        if param_types.is_err() {
+            return Err(param_types.err().unwrap());
         }
+        let param_types = param_types.unwrap();
 
         let return_type_resolved: DataType = if return_type.is_some() {
             return_type.clone().unwrap()
         } else {
-            DataType::VOID
+            DataType::ANY
         };
 
-        let fn_type = DataType::FN(param_types, Box::new(return_type_resolved));
+        let fn_type = DataType::FN(param_types.clone(), Box::new(return_type_resolved));
+        // inserted before the body so it can call itself
         self.scopes[self.scope_index]
             .vars
             .insert(t.as_identifier(), fn_type.clone());
 
+        let res = self.visit_fn_body(params, &param_types, body);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+
         Ok(fn_type)
     }
 
+    fn visit_lambda(
+        &mut self,
+        params: &Vec<ASTNode>,
+        return_type: &Option<DataType>,
+        body: &Box<ASTNode>,
+    ) -> Result<DataType, TypeCheckError> {
+        // lambda params don't need a type
+        let param_types = self.visit_params(params);
+        if param_types.is_err() {
+            return Err(param_types.err().unwrap());
+        }
+        let param_types = param_types.unwrap();
+
+        let res = self.visit_fn_body(params, &param_types, body);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+
+        let return_type_resolved: DataType = if return_type.is_some() {
+            return_type.clone().unwrap()
+        } else {
+            DataType::ANY
+        };
+
+        Ok(DataType::FN(param_types, Box::new(return_type_resolved)))
+    }
+
     fn visit_named_type_decl(
         &mut self,
         t: &Token,
         decls: &Vec<ASTNode>,
     ) -> Result<DataType, TypeCheckError> {
-        let mut v: Vec<DataType> = vec![];
+        // so fields can refer to the type itself
+        self.types.insert(
+            t.as_identifier(),
+            TypeDecl {
+                generics: vec![],
+                fields: vec![],
+            },
+        );
+
+        let mut generics: Vec<Rc<String>> = vec![];
+        let mut fields: Vec<(Rc<String>, DataType)> = vec![];
         for decl in decls {
-            let decl_type = self.visit(decl);
-            if decl_type.is_err() {
-                return Err(decl_type.err().unwrap());
+            if let Statement::DEFINE(field, Some(typ), _) = &decl.statement {
+                let decl_type =
+                    self.resolve_data_type_to_concrete_type(field.pos.clone(), typ.clone());
+                if decl_type.is_err() {
+                    return Err(decl_type.err().unwrap());
+                }
+                let decl_type = decl_type.unwrap();
+                decl_type.generics(&mut generics);
+                fields.push((field.as_identifier(), decl_type));
             }
-            v.push(decl_type.unwrap());
         }
 
-        // todo insert type into scope
+        self.scopes[self.scope_index].vars.insert(
+            t.as_identifier(),
+            DataType::DYNAMIC_OBJECT(fields.iter().map(|(_, t)| t.clone()).collect()),
+        );
+        self.types
+            .insert(t.as_identifier(), TypeDecl { generics, fields });
+
+        Ok(DataType::U32)
+    }
+
+    fn visit_named_enum_decl(&mut self, t: &Token) -> Result<DataType, TypeCheckError> {
+        // the variants are only known at runtime but the enum can be used as a type
         self.scopes[self.scope_index]
             .vars
-            .insert(t.as_identifier(), DataType::DYNAMIC_OBJECT(v));
-
+            .insert(t.as_identifier(), DataType::ANY);
         Ok(DataType::U32)
     }
 }
+
+// the method a user type defines to overload an arithmetic operator
+fn operator_method(op: &Op) -> Option<&'static str> {
+    match op {
+        Op::ADD => Some("__add"),
+        Op::SUB => Some("__sub"),
+        Op::MUL => Some("__mul"),
+        Op::DIV => Some("__div"),
+        Op::MOD => Some("__mod"),
+        Op::POW => Some("__pow"),
+        _ => None,
+    }
+}
+
+// the functions and types marked @deprecated at the top level of a module
+pub fn deprecations(ast: &ASTNode) -> Deprecations {
+    let mut deprecated: Deprecations = HashMap::new();
+    let Statement::PROGRAM(items) = &ast.statement else {
+        return deprecated;
+    };
+    for item in items {
+        let Some(message) = deprecation(item) else {
+            continue;
+        };
+        let declaration = item.declaration();
+        let receiver = match &declaration.statement {
+            Statement::NAMED_FUNCTION(_, params, _, _) => method_receiver(params),
+            _ => None,
+        };
+        if let Some(name) = declaration.declared_name() {
+            deprecated.insert((receiver, name), message);
+        }
+    }
+    deprecated
+}
+
+// the message of the @deprecated annotation on a declaration, which is none if it has no message
+fn deprecation(node: &ASTNode) -> Option<Option<Rc<String>>> {
+    match &node.statement {
+        Statement::EXPORT(e) | Statement::DOC(_, e) => deprecation(e),
+        Statement::ANNOTATION(annotation, args, e) => {
+            if annotation.as_identifier().as_str() != "deprecated" {
+                return deprecation(e);
+            }
+            Some(args.first().and_then(|arg| match &arg.statement {
+                Statement::STRING(message) => Some(message.as_string()),
+                _ => None,
+            }))
+        }
+        _ => None,
+    }
+}
+
+// the type a function is a method of, if its first parameter is a typed self
+pub fn method_receiver(params: &Vec<ASTNode>) -> Option<Rc<String>> {
+    if let Some(Statement::DEFINE(t, Some(typ), _)) = params.first().map(|p| &p.statement) {
+        if t.as_identifier().as_str() == "self" {
+            if let DataType::NAMED_REFERENCE(name) | DataType::INSTANCE(name, _) = typ {
+                return Some(name.clone());
+            }
+        }
+    }
+    None
+}
+
+// the type of a method as it's called on an object, so without self
+fn method_signature(params: &Vec<ASTNode>, return_type: &Option<DataType>) -> DataType {
+    let mut param_types: Vec<DataType> = vec![];
+    for (i, param) in params.iter().enumerate() {
+        match &param.statement {
+            Statement::DEFINE(t, _, _) if i == 0 && t.as_identifier().as_str() == "self" => {}
+            Statement::DEFINE(_, typ, _) => param_types.push(typ.clone().unwrap_or(DataType::ANY)),
+            Statement::DESTRUCTURE(_, _) => param_types.push(DataType::ANY),
+            _ => {}
+        }
+    }
+    DataType::FN(
+        param_types,
+        Box::new(return_type.clone().unwrap_or(DataType::ANY)),
+    )
+}
+
+fn bind_generics(param: &DataType, arg: &DataType, bindings: &mut HashMap<Rc<String>, DataType>) {
+    match (param, arg) {
+        (DataType::GENERIC(name), _) => {
+            if !bindings.contains_key(name) {
+                bindings.insert(name.clone(), arg.clone());
+            }
+        }
+        // we know nothing about the argument so its generics could be anything
+        (_, DataType::ANY) => {
+            let mut names: Vec<Rc<String>> = vec![];
+            param.generics(&mut names);
+            for name in names {
+                bindings.entry(name).or_insert(DataType::ANY);
+            }
+        }
+        (DataType::SLICE(p), DataType::SLICE(a)) => bind_generics(p, a, bindings),
+        (DataType::INSTANCE(p_name, p_params), DataType::INSTANCE(a_name, a_params))
+            if p_name == a_name =>
+        {
+            for (p, a) in p_params.iter().zip(a_params) {
+                bind_generics(p, a, bindings);
+            }
+        }
+        (DataType::FN(p_params, p_return), DataType::FN(a_params, a_return)) => {
+            for (p, a) in p_params.iter().zip(a_params) {
+                bind_generics(p, a, bindings);
+            }
+            bind_generics(p_return, a_return, bindings);
+        }
+        _ => {}
+    }
+}
+
+// the type a call or constructor produces. any of its generics which weren't worked out are an
+// error, unless some of the arguments could only be matched up at runtime
+fn resolve_generics(
+    t: &DataType,
+    bindings: &HashMap<Rc<String>, DataType>,
+    position: &Position,
+    inferrable: bool,
+) -> Result<DataType, TypeCheckError> {
+    let mut names: Vec<Rc<String>> = vec![];
+    t.generics(&mut names);
+    let mut bindings = bindings.clone();
+    for name in names {
+        if bindings.contains_key(&name) {
+            continue;
+        }
+        if inferrable {
+            return Err(TypeCheckError::UNRESOLVED_GENERIC(name, position.clone()));
+        }
+        bindings.insert(name, DataType::ANY);
+    }
+    Ok(t.substitute(&bindings))
+}
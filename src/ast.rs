@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::{
     lex::{Position, Token},
     r#type::DataType,
@@ -6,10 +8,16 @@ use crate::{
 #[derive(Debug, PartialEq)]
 pub enum Op {
     BITWISE_OR,
+    BITWISE_AND,
+    BITWISE_XOR,
+    SHIFT_LEFT,
+    SHIFT_RIGHT,
     ADD,
     SUB,
     MUL,
     DIV,
+    MOD,
+    POW,
     EQ,
     NEQ,
     GT,
@@ -17,6 +25,46 @@ pub enum Op {
     LT,
     LE,
     LOGICAL_OR,
+    LOGICAL_AND,
+    IN,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnaryOp {
+    NEG,
+    NOT,
+}
+
+// what a value is taken apart into when destructuring or matching
+#[derive(Debug)]
+pub enum Pattern {
+    // a plain name i.e. `a`
+    BIND(Token),
+    // `_`, matches anything without binding it
+    WILDCARD(Position),
+    // `1`, `"a"`, `true` or `:ok`, only allowed in a match case
+    LITERAL(Box<ASTNode>),
+    // `0..10`, the end is exclusive like a for loop, only allowed in a match case
+    RANGE(Box<ASTNode>, Box<ASTNode>),
+    // `a, b` or `(a, b)`
+    TUPLE(Vec<Pattern>, Position),
+    // `Vec(x=px, y=py)`, the type can be a path to an enum variant i.e. `Colour.Red`
+    STRUCT(Box<ASTNode>, Vec<(Token, Pattern)>, Position),
+}
+
+impl Pattern {
+    // every name the pattern binds, in order
+    pub fn names(&self) -> Vec<&Token> {
+        match self {
+            Pattern::BIND(t) => vec![t],
+            Pattern::WILDCARD(_) | Pattern::LITERAL(_) | Pattern::RANGE(_, _) => vec![],
+            Pattern::TUPLE(items, _) => items.iter().flat_map(|item| item.names()).collect(),
+            Pattern::STRUCT(_, fields, _) => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.names())
+                .collect(),
+        }
+    }
 }
 
 // #[derive(Debug)]
@@ -31,33 +79,62 @@ pub enum Statement {
     ASSERT(Box<ASTNode>, Option<Token>),
     RANGE(Box<ASTNode>, Box<ASTNode>),
     NAMED_ARG(Token, Box<ASTNode>),
+    // `...xs` as a call argument, or wrapping the define of a `...rest` parameter
+    SPREAD(Box<ASTNode>),
     TRY(Box<ASTNode>),
     TUPLE(Vec<ASTNode>),
     SLICE(Vec<ASTNode>),
+    // key, value pairs in the order they were written
+    DICT(Vec<(ASTNode, ASTNode)>),
     CALL(Box<ASTNode>, Vec<ASTNode>),
     ATOM(Token),
     VARIABLE(Token),
     BIN_OP(Box<ASTNode>, Box<ASTNode>, Op),
+    UNARY_OP(UnaryOp, Box<ASTNode>),
     LITERAL_BOOL(bool),
     LITERAL_NUM(Token),
     STRING(Token),
+    // the literal and expression parts of "x={x}", in order
+    INTERPOLATED_STRING(Vec<ASTNode>),
     RETURN(Option<Box<ASTNode>>),
     PROGRAM(Vec<ASTNode>),
     BLOCK(Vec<ASTNode>),
     MATCH(Box<ASTNode>, Vec<ASTNode>),
-    MATCH_CASE(Token, Box<ASTNode>),
+    // pattern, guard, body
+    MATCH_CASE(Pattern, Option<Box<ASTNode>>, Box<ASTNode>),
     // todo should these tokens be references?
     DEFINE(Token, Option<DataType>, Option<Box<ASTNode>>),
+    // like a define, there is no value when its a parameter
+    DESTRUCTURE(Pattern, Option<Box<ASTNode>>),
+    // gives the variables already declared in the pattern new values
+    DESTRUCTURE_ASSIGN(Pattern, Box<ASTNode>),
     ASSIGN(Box<ASTNode>, Box<ASTNode>),
     NAMED_FUNCTION(Token, Vec<ASTNode>, Option<DataType>, Box<ASTNode>),
+    LAMBDA(Vec<ASTNode>, Option<DataType>, Box<ASTNode>),
     NAMED_TYPE_DECL(Token, Vec<ASTNode>),
+    // each variant's name and the decls of its fields, which may be empty
+    NAMED_ENUM_DECL(Token, Vec<(Token, Vec<ASTNode>)>),
+    // the name, params and return type of each method a type needs to satisfy the interface
+    NAMED_INTERFACE_DECL(Token, Vec<(Token, Vec<ASTNode>, Option<DataType>)>),
     TEST(Box<ASTNode>, Box<ASTNode>),
     IF(Box<ASTNode>, Box<ASTNode>, Option<Box<ASTNode>>),
-    FOR(Token, Box<ASTNode>, Box<ASTNode>),
+    FOR(Pattern, Box<ASTNode>, Box<ASTNode>),
+    WHILE(Box<ASTNode>, Box<ASTNode>),
+    LOOP(Box<ASTNode>),
+    BREAK,
+    CONTINUE,
     INDEX(Box<ASTNode>, Box<ASTNode>),
-    ANNOTATION(Token, Vec<Token>, Box<ASTNode>),
+    // the annotation's name, its constant arguments and what it annotates
+    ANNOTATION(Token, Vec<ASTNode>, Box<ASTNode>),
     STRUCT_ACCESS(Box<ASTNode>, Token),
-    IMPORT(Vec<Token>),
+    // the module path and the name it's bound to with `as`, if any
+    IMPORT(Vec<Token>, Option<Token>),
+    // `from a.b import x, y as z`, each name with its alias
+    FROM_IMPORT(Vec<Token>, Vec<(Token, Option<Token>)>),
+    // a top level declaration which other modules can import
+    EXPORT(Box<ASTNode>),
+    // a declaration with the `///` comments written before it
+    DOC(Rc<String>, Box<ASTNode>),
 }
 
 #[derive(Debug)]
@@ -65,3 +142,113 @@ pub struct ASTNode {
     pub statement: Statement,
     pub position: Position,
 }
+
+impl ASTNode {
+    // the nodes directly beneath this one, used by passes that need to walk the whole tree
+    pub fn children(&self) -> Vec<&ASTNode> {
+        match &self.statement {
+            Statement::ASSERT(e, _) => vec![e],
+            Statement::RANGE(a, b) => vec![a, b],
+            Statement::NAMED_ARG(_, v) => vec![v],
+            Statement::SPREAD(e) => vec![e],
+            Statement::TRY(e) => vec![e],
+            Statement::TUPLE(items) => items.iter().collect(),
+            Statement::SLICE(items) => items.iter().collect(),
+            Statement::DICT(entries) => entries
+                .iter()
+                .flat_map(|(key, value)| vec![key, value])
+                .collect(),
+            Statement::CALL(callee, args) => {
+                let mut children: Vec<&ASTNode> = vec![callee];
+                children.extend(args.iter());
+                children
+            }
+            Statement::BIN_OP(a, b, _) => vec![a, b],
+            Statement::UNARY_OP(_, e) => vec![e],
+            Statement::INTERPOLATED_STRING(parts) => parts.iter().collect(),
+            Statement::RETURN(v) => v.iter().map(|v| v.as_ref()).collect(),
+            Statement::PROGRAM(items) => items.iter().collect(),
+            Statement::BLOCK(items) => items.iter().collect(),
+            Statement::MATCH(e, cases) => {
+                let mut children: Vec<&ASTNode> = vec![e];
+                children.extend(cases.iter());
+                children
+            }
+            Statement::MATCH_CASE(_, guard, body) => {
+                let mut children: Vec<&ASTNode> = guard.iter().map(|g| g.as_ref()).collect();
+                children.push(body);
+                children
+            }
+            Statement::DEFINE(_, _, v) => v.iter().map(|v| v.as_ref()).collect(),
+            Statement::DESTRUCTURE(_, v) => v.iter().map(|v| v.as_ref()).collect(),
+            Statement::DESTRUCTURE_ASSIGN(_, v) => vec![v],
+            Statement::ASSIGN(a, b) => vec![a, b],
+            Statement::NAMED_FUNCTION(_, params, _, body) => {
+                let mut children: Vec<&ASTNode> = params.iter().collect();
+                children.push(body);
+                children
+            }
+            Statement::LAMBDA(params, _, body) => {
+                let mut children: Vec<&ASTNode> = params.iter().collect();
+                children.push(body);
+                children
+            }
+            Statement::NAMED_TYPE_DECL(_, decls) => decls.iter().collect(),
+            Statement::NAMED_ENUM_DECL(_, variants) => variants
+                .iter()
+                .flat_map(|(_, decls)| decls.iter())
+                .collect(),
+            Statement::NAMED_INTERFACE_DECL(_, methods) => methods
+                .iter()
+                .flat_map(|(_, params, _)| params.iter())
+                .collect(),
+            Statement::TEST(name, body) => vec![name, body],
+            Statement::IF(cond, body, else_body) => {
+                let mut children: Vec<&ASTNode> = vec![cond, body];
+                children.extend(else_body.iter().map(|e| e.as_ref()));
+                children
+            }
+            Statement::FOR(_, iter, body) => vec![iter, body],
+            Statement::WHILE(cond, body) => vec![cond, body],
+            Statement::LOOP(body) => vec![body],
+            Statement::INDEX(obj, index) => vec![obj, index],
+            // the arguments are constants, so there's nothing in them to walk
+            Statement::ANNOTATION(_, _, e) => vec![e],
+            Statement::STRUCT_ACCESS(e, _) => vec![e],
+            Statement::EXPORT(e) => vec![e],
+            Statement::DOC(_, e) => vec![e],
+            Statement::ATOM(_)
+            | Statement::VARIABLE(_)
+            | Statement::LITERAL_BOOL(_)
+            | Statement::LITERAL_NUM(_)
+            | Statement::STRING(_)
+            | Statement::BREAK
+            | Statement::CONTINUE
+            | Statement::IMPORT(_, _)
+            | Statement::FROM_IMPORT(_, _) => vec![],
+        }
+    }
+
+    // the name this node declares if it's something that can be exported
+    pub fn declared_name(&self) -> Option<Rc<String>> {
+        match &self.statement {
+            Statement::DEFINE(t, _, _)
+            | Statement::NAMED_FUNCTION(t, _, _, _)
+            | Statement::NAMED_TYPE_DECL(t, _)
+            | Statement::NAMED_ENUM_DECL(t, _)
+            | Statement::NAMED_INTERFACE_DECL(t, _) => Some(t.as_identifier()),
+            Statement::ANNOTATION(_, _, e) | Statement::DOC(_, e) => e.declared_name(),
+            _ => None,
+        }
+    }
+
+    // what's underneath any export, doc comment and annotations
+    pub fn declaration(&self) -> &ASTNode {
+        match &self.statement {
+            Statement::EXPORT(e) | Statement::DOC(_, e) | Statement::ANNOTATION(_, _, e) => {
+                e.declaration()
+            }
+            _ => self,
+        }
+    }
+}
@@ -7,9 +7,9 @@ use std::{
 };
 
 use crate::{
-    ast::{ASTNode, Op, Statement},
+    ast::{ASTNode, Op, Pattern, Statement, UnaryOp},
     config::Config,
-    execution::{DynamicObject, FnObject, GCRef, GCRefData, Object, StringObject},
+    execution::{DynamicObject, FnObject, GCRef, GCRefData, Object, StringObject, VariantTag},
     lex::{Position, Token, Type},
     r#type::DataType,
 };
@@ -82,15 +82,52 @@ pub enum OpInstruction {
     GREATER_EQUAL,
     LESS_THAN,
     LESS_EQUAL,
+    // LOGICAL_OR <r1> <r2> <desination>
+    // the result is always a bool, the rhs is skipped with a jump when the lhs decides it
     LOGICAL_OR,
+    // LOGICAL_AND <r1> <r2> <desination>
+    LOGICAL_AND,
     BITWISE_OR,
+    // BITWISE_AND <r1> <r2> <desination>
+    BITWISE_AND,
+    // BITWISE_XOR <r1> <r2> <desination>
+    BITWISE_XOR,
+    // SHIFT_LEFT <r1> <r2> <desination>
+    SHIFT_LEFT,
+    // SHIFT_RIGHT <r1> <r2> <desination>
+    SHIFT_RIGHT,
+    // LOAD_CLOSURE <frame> <slot> <destination>
     LOAD_CLOSURE,
+    // STORE_CLOSURE <frame> <slot> <value>
+    // sets a variable declared in an enclosing function, i.e. a global
+    STORE_CLOSURE,
+    // NEW_CELL <register>
+    // moves the value into a heap cell so it can be shared with closures
+    NEW_CELL,
+    // LOAD_CELL <cell> <destination>
+    LOAD_CELL,
+    // STORE_CELL <cell> <value>
+    STORE_CELL,
+    // LOAD_UPVALUE <upvalue index> <destination>
+    LOAD_UPVALUE,
+    // STORE_UPVALUE <upvalue index> <value>
+    STORE_UPVALUE,
     // MUL <r1> <r2> <desination>
     MUL,
     // DIV <r1> <r2> <desination>
     DIV,
+    // MOD <r1> <r2> <desination>
+    MOD,
+    // POW <r1> <r2> <desination>
+    POW,
     // ADD <r1> <r2> <desination>
     ADD,
+    // SUB <r1> <r2> <desination>
+    SUB,
+    // NEG <r1> <destination>
+    NEG,
+    // NOT <r1> <destination>
+    NOT,
     // ADDI <r1> <r2> <desination>
     ADDI,
     // ADDI <i1> <i2> <desination>
@@ -100,6 +137,8 @@ pub enum OpInstruction {
     // CALL <location of fn> <args starting register> <num args>
     CALL,
     // CALL_KW <location of fn> <location of tuple containing arg names> <args starting register>
+    // the tuple starts with the number of positional args, which come before the named ones, then
+    // whether each positional arg is spread i.e. f(...xs), then the names
     CALL_KW,
 
     // NATIVE_CALL <name of fn string> <args starting register> <num args> <destination is implicitly the register after>
@@ -117,12 +156,25 @@ pub enum OpInstruction {
     BUILD_SLICE,
     // BUILD_TUPLE <staring reg> <num args> <destination>
     BUILD_TUPLE,
-    // BUILD_FN <code obj> <destination>
+    // BUILD_MAP <starting reg> <num entries> <destination>
+    // the registers alternate key, value
+    BUILD_MAP,
+    // BUILD_STRING <starting reg> <num parts> <destination>
+    // each part is printed and concatenated into a single new string
+    BUILD_STRING,
+    // BUILD_FN <code obj> <first default value reg> <num default values>
     // the purpose of this is so function specifications can be evaluated at runtime, i.e. is it static, is it a method etc.
-    // it also processes default arguments etc
+    // it also stores the default values for the trailing parameters
     BUILD_FN,
     // INDEX <item> <index> <destination>
     INDEX,
+    // INDEX_SET <item> <index> <value>
+    INDEX_SET,
+    // INDEX_RANGE <item> <start, end> <destination>
+    // the end is in the register after the start
+    INDEX_RANGE,
+    // IN <item> <container> <destination>
+    IN,
     // STRUCT_ACCESS <obj> <member> <dest>
     STRUCT_ACCESS,
     // STRUCT_SET <obj> <member> <value>
@@ -131,7 +183,34 @@ pub enum OpInstruction {
     // IMPORT <module path> <dest>
     IMPORT,
     // FOR_ITER <iter obj> <where to jump if done> <iter result reg>
+    // while iterating the iterator's `value` is put in the result reg
     FOR_ITER,
+    // GET_ITER <iterable> <destination>
+    // iterators are passed through, maps are turned into an iterator over their keys
+    GET_ITER,
+    // CHECK_ARITY <tuple or slice> <expected length> <column of the pattern>
+    // errors if a destructured value doesn't have exactly as many items as the pattern
+    CHECK_ARITY,
+    // HAS_LENGTH <value> <length> <dest>
+    // like CHECK_ARITY but puts false in dest instead of erroring, for matching
+    HAS_LENGTH,
+    // IS_INSTANCE <value> <type> <dest>
+    // for enum variants this compares tags, so the type can also be a variant without fields
+    IS_INSTANCE,
+    // CHECK_INSTANCE <value> <type> <column of the pattern>
+    // like IS_INSTANCE but errors instead, for destructuring
+    CHECK_INSTANCE,
+    // DESTRUCTURE_FIELD <object> <field name> <dest>
+    // reads one of the object's own fields, erroring with its name if it doesn't have it
+    DESTRUCTURE_FIELD,
+    // SAME_TYPE <lhs> <rhs> <dest>
+    SAME_TYPE,
+    // MATCH_FAIL <value> <column of the match>
+    // raised when no case of a match matches the value
+    MATCH_FAIL,
+    // ANNOTATE <fn or type> <annotation name> <args tuple>
+    // records an annotation the compiler doesn't handle itself so annotations(x) can find it
+    ANNOTATE,
 
     // this is just a hack to make variables work
     // MOV <from> <to>
@@ -166,6 +245,20 @@ impl Instruction {
                 format!("{}", self.arg_1),
                 format!("r{}", self.arg_2),
             ),
+            OpInstruction::BUILD_MAP => format!(
+                "{:>75}{:>5}{:>5}{:>5}\n",
+                format!("{:?}", self.op_instruction),
+                format!("r{}", self.arg_0),
+                format!("{}", self.arg_1),
+                format!("r{}", self.arg_2),
+            ),
+            OpInstruction::BUILD_STRING => format!(
+                "{:>75}{:>5}{:>5}{:>5}\n",
+                format!("{:?}", self.op_instruction),
+                format!("r{}", self.arg_0),
+                format!("{}", self.arg_1),
+                format!("r{}", self.arg_2),
+            ),
             OpInstruction::IF_JMP_FALSE => format!(
                 "{:>75}{:>5}{:>5}\n",
                 format!("{:?}", self.op_instruction),
@@ -179,10 +272,11 @@ impl Instruction {
                 format!("{}", self.arg_1),
             ),
             OpInstruction::TRY => format!(
-                "{:>75}{:>5}{:>5}\n",
+                "{:>75}{:>5}{:>5}{:>5}\n",
                 format!("{:?}", self.op_instruction),
                 format!("r{}", self.arg_0),
                 format!("r{}", self.arg_1),
+                format!("r{}", self.arg_2),
             ),
             OpInstruction::ADDI => format!(
                 "{:>75}{:>5}{:>5}{:>5}\n",
@@ -206,11 +300,29 @@ impl Instruction {
                 format!("r{}", self.arg_2)
             ),
             OpInstruction::BUILD_FN => format!(
+                "{:>75}{:>5}{:>5}{:>5}\n",
+                format!("{:?}", self.op_instruction),
+                format!("r{}", self.arg_0),
+                format!("r{}", self.arg_1),
+                format!("{}", self.arg_2),
+            ),
+            OpInstruction::NEW_CELL => format!(
                 "{:>75}{:>5}\n",
                 format!("{:?}", self.op_instruction),
                 format!("r{}", self.arg_0),
             ),
-            OpInstruction::MOV => format!(
+            OpInstruction::LOAD_UPVALUE | OpInstruction::STORE_UPVALUE => format!(
+                "{:>75}{:>5}{:>5}\n",
+                format!("{:?}", self.op_instruction),
+                format!("{}", self.arg_0),
+                format!("r{}", self.arg_1)
+            ),
+            OpInstruction::MOV
+            | OpInstruction::NEG
+            | OpInstruction::NOT
+            | OpInstruction::GET_ITER
+            | OpInstruction::LOAD_CELL
+            | OpInstruction::STORE_CELL => format!(
                 "{:>75}{:>5}{:>5}\n",
                 format!("{:?}", self.op_instruction),
                 format!("r{}", self.arg_0),
@@ -263,7 +375,19 @@ impl Instruction {
                 format!("r{}", self.arg_1),
                 format!("r{}", self.arg_2)
             ),
-            OpInstruction::LOGICAL_OR => format!(
+            OpInstruction::LOGICAL_OR
+            | OpInstruction::LOGICAL_AND
+            | OpInstruction::INDEX_SET
+            | OpInstruction::IN
+            | OpInstruction::BITWISE_AND
+            | OpInstruction::BITWISE_XOR
+            | OpInstruction::SHIFT_LEFT
+            | OpInstruction::SHIFT_RIGHT
+            | OpInstruction::SUB
+            | OpInstruction::MUL
+            | OpInstruction::DIV
+            | OpInstruction::MOD
+            | OpInstruction::POW => format!(
                 "{:>75}{:>5}{:>5}{:>5}\n",
                 format!("{:?}", self.op_instruction),
                 format!("r{}", self.arg_0),
@@ -338,12 +462,32 @@ pub struct Chunk {
     pub instructions: std::vec::Vec<Instruction>,
     // todo only enable this in debug mode
     pub debug_line_info: std::vec::Vec<usize>,
+    // where in the line an instruction is, for the ones whose errors point at part of it
+    pub debug_position_info: HashMap<usize, Position>,
     // maybe constant pools should be global...?
     pub constant_pool: std::vec::Vec<Object>,
     pub gc_ref_data: std::vec::Vec<GCRefData>,
     // todo make this a hashmap of Rc<String>
     pub variable_map: HashMap<Rc<String>, u8>,
     pub string_interns: HashMap<String, u8>,
+    // locals which are captured by a nested function, their registers hold a cell rather than the value
+    pub cells: HashSet<Rc<String>>,
+    // where each captured variable comes from in the enclosing function when this function is built
+    pub upvalues: Vec<(Rc<String>, UpvalueSource)>,
+}
+
+#[derive(Clone, DeepSizeOf, Debug)]
+pub enum UpvalueSource {
+    // a cell in one of the enclosing function's registers
+    LOCAL(u8),
+    // an upvalue the enclosing function captured itself
+    UPVALUE(u8),
+}
+
+enum VariableLocation {
+    REGISTER(u8),
+    CELL(u8),
+    UPVALUE(u8),
 }
 
 impl Chunk {
@@ -401,6 +545,12 @@ impl Chunk {
             Object::I64(i64) => {
                 s.push_str(&format!("i64: {}", i64));
             }
+            Object::F64(f64) => {
+                s.push_str(&format!("f64: {}", f64));
+            }
+            Object::ATOM(a) => {
+                s.push_str(&format!("atom: {}", a));
+            }
             _ => todo!(),
         }
         return s;
@@ -456,6 +606,9 @@ impl Chunk {
                 for instr in all_instructions.unwrap() {
                     s.push_str(&instr.to_string().as_str());
                 }
+            } else {
+                // keep every source line on its own line, even multi-line strings with no code
+                s.push('\n');
             }
             i += 1;
         }
@@ -682,6 +835,11 @@ impl SlotManager {
     }
 
     pub fn take_slot(&mut self, slot: u8) {
+        // a variable's register is already ours, marking it temporary would let it be freed and
+        // handed out again while the variable is still alive
+        if self.allocated_perm.contains(&slot) {
+            return;
+        }
         self.allocated.insert(slot);
         for i in 0..self.free_slots.len() {
             if self.free_slots[i] == slot {
@@ -712,6 +870,9 @@ impl SlotManager {
 pub struct CodegenContext {
     pub current_chunk_pointer: usize,
     pub chunks: Vec<Chunk>,
+    // the tags of the prelude's Result variants, which is the first Result declared as the prelude
+    // is always compiled first, so `try` and `assert` can tell it apart from other enums
+    pub prelude_result: HashMap<String, Rc<VariantTag>>,
 }
 
 #[derive(Clone)]
@@ -719,28 +880,77 @@ pub struct CodegenResult {
     pub codegen_context: CodegenContext,
 }
 
+#[derive(Clone, Debug)]
+pub enum CodegenError {
+    // a variable was used without ever being declared
+    UNKNOWN_VARIABLE(Token),
+    // a variable was given a value with = before let declared it
+    UNDECLARED_VARIABLE(Token),
+    // a builtin annotation was used wrongly, with why
+    INVALID_ANNOTATION(Token, String),
+    // annotation arguments are kept as metadata so they can only be literals
+    NON_CONSTANT_ANNOTATION_ARGUMENT(Position),
+    // something marked @test_only was used outside of a test build
+    TEST_ONLY(Token),
+    // a jump to past the last instruction an operand can point at
+    JUMP_TOO_FAR(Position),
+}
+
+// where continue jumps to, and the breaks that need pointing at the end once we know where it is
+struct LoopContext {
+    continue_ip: usize,
+    break_jumps: Vec<usize>,
+}
+
+// what a block has declared, and the function's variables as they were before it so they can be
+// put back when it ends
+struct BlockScope {
+    declared: HashSet<Rc<String>>,
+    outer_variables: HashMap<Rc<String>, u8>,
+}
+
 pub struct BytecodeGenerator {
     config: Config,
     codegen_context: CodegenContext,
+    loops: Vec<LoopContext>,
+    // the blocks of the current function we're inside, innermost last
+    scopes: Vec<BlockScope>,
+    errors: Vec<CodegenError>,
+    // whether declarations marked @test_only are kept
+    test_build: bool,
+    // the @test_only declarations which were left out, so using them can say why they're missing
+    test_only: HashSet<Rc<String>>,
 }
 
 impl BytecodeGenerator {
-    pub fn new<'a>(config: Config, codegen_context: CodegenContext) -> BytecodeGenerator {
+    pub fn new<'a>(
+        config: Config,
+        codegen_context: CodegenContext,
+        test_build: bool,
+    ) -> BytecodeGenerator {
         return BytecodeGenerator {
             config,
             codegen_context,
+            loops: vec![],
+            scopes: vec![],
+            errors: vec![],
+            test_build,
+            test_only: HashSet::new(),
         };
     }
 
-    pub fn generate(&mut self, ast: &ASTNode) -> CodegenResult {
+    pub fn generate(&mut self, ast: &ASTNode) -> Result<CodegenResult, Vec<CodegenError>> {
         let annotation_context = AnnotationContext {
             annotations: vec![],
         };
         self.visit(annotation_context, ast);
 
-        return CodegenResult {
+        if self.errors.len() > 0 {
+            return Err(self.errors.clone());
+        }
+        return Ok(CodegenResult {
             codegen_context: self.codegen_context.clone(),
-        };
+        });
     }
 
     pub fn init_builtins(&mut self) {
@@ -781,6 +991,10 @@ impl BytecodeGenerator {
         self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
             .variable_map
             .insert(Rc::new("gila_socket".to_string()), gila_socket_reg);
+        let annotations_reg = alloc_perm_slot!(self);
+        self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+            .variable_map
+            .insert(Rc::new("annotations".to_string()), annotations_reg);
     }
 
     fn push_instruction(&mut self, instruction: Instruction, line: usize) {
@@ -792,6 +1006,14 @@ impl BytecodeGenerator {
             .push(line);
     }
 
+    // records where in the line the last instruction is
+    fn mark_position(&mut self, pos: &Position) {
+        let chunk = &mut self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+        chunk
+            .debug_position_info
+            .insert(chunk.instructions.len() - 1, pos.clone());
+    }
+
     fn push_gc_ref_data(&mut self, gc_ref_data: GCRefData) -> u8 {
         self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
             .gc_ref_data
@@ -819,11 +1041,14 @@ impl BytecodeGenerator {
         self.codegen_context.chunks.push(Chunk {
             slot_manager: SlotManager::new(),
             debug_line_info: vec![],
+            debug_position_info: HashMap::new(),
             constant_pool: vec![],
             gc_ref_data: vec![],
             instructions: vec![],
             variable_map: HashMap::new(),
             string_interns: HashMap::new(),
+            cells: HashSet::new(),
+            upvalues: vec![],
         });
         self.codegen_context.current_chunk_pointer += 1;
     }
@@ -837,11 +1062,11 @@ impl BytecodeGenerator {
 
     fn visit(&mut self, annotation_context: AnnotationContext, ast: &ASTNode) -> u8 {
         match &ast.statement {
-            Statement::PROGRAM(p) => self.gen_program(annotation_context, &p),
+            Statement::PROGRAM(p) => self.gen_program(annotation_context, ast, &p),
             Statement::BLOCK(b) => self.gen_block(annotation_context, &b),
             Statement::TUPLE(t) => self.gen_tuple(annotation_context, &ast.position.clone(), &t),
-            Statement::MATCH(to_match, patterns) => {
-                self.gen_match(annotation_context, &to_match, &patterns)
+            Statement::MATCH(to_match, cases) => {
+                self.gen_match(annotation_context, ast.position.clone(), &to_match, &cases)
             }
             Statement::TEST(name, body) => {
                 self.gen_test(annotation_context, ast.position.clone(), &name, &body)
@@ -860,6 +1085,12 @@ impl BytecodeGenerator {
                 &iter_obj,
                 &body,
             ),
+            Statement::WHILE(cond, body) => {
+                self.gen_while(annotation_context, ast.position.clone(), &cond, &body)
+            }
+            Statement::LOOP(body) => self.gen_loop(annotation_context, ast.position.clone(), &body),
+            Statement::BREAK => self.gen_break(ast.position.clone()),
+            Statement::CONTINUE => self.gen_continue(ast.position.clone()),
             Statement::RANGE(first, second) => {
                 self.generate_range(annotation_context, &ast.position.clone(), &first, &second)
             }
@@ -869,6 +1100,14 @@ impl BytecodeGenerator {
             Statement::DEFINE(var, typ, value) => {
                 self.gen_define(annotation_context, ast.position.clone(), var, value)
             }
+            Statement::DESTRUCTURE(pattern, value) => {
+                self.gen_destructure(annotation_context, ast.position.clone(), pattern, value)
+            }
+            Statement::DESTRUCTURE_ASSIGN(pattern, value) => {
+                let location = self.visit(annotation_context.clone(), &value);
+                self.bind_pattern(annotation_context, &ast.position, pattern, location, false);
+                location
+            }
             Statement::ASSIGN(lhs, rhs) => {
                 self.gen_assign(annotation_context, ast.position.clone(), lhs, rhs)
             }
@@ -880,21 +1119,42 @@ impl BytecodeGenerator {
             }
             Statement::ATOM(a) => self.gen_atom(annotation_context, ast.position.clone(), a),
             Statement::STRING(s) => self.gen_string(annotation_context, ast.position.clone(), s),
+            Statement::INTERPOLATED_STRING(parts) => {
+                self.gen_interpolated_string(annotation_context, ast.position.clone(), parts)
+            }
             Statement::CALL(b, args) => {
                 self.gen_call(annotation_context, ast.position.clone(), b, args)
             }
             Statement::BIN_OP(e1, e2, op) => {
                 self.gen_bin_op(annotation_context, ast.position.clone(), &e1, &e2, &op)
             }
+            Statement::UNARY_OP(op, e) => {
+                self.gen_unary_op(annotation_context, ast.position.clone(), &op, &e)
+            }
             Statement::NAMED_FUNCTION(t, params, return_type, statement) => {
                 self.gen_named_function(annotation_context, &t, &params, &return_type, &statement)
             }
+            Statement::LAMBDA(params, return_type, body) => self.gen_lambda(
+                annotation_context,
+                ast.position.clone(),
+                &params,
+                &return_type,
+                &body,
+            ),
             Statement::NAMED_TYPE_DECL(t, decls) => {
                 self.gen_named_type(annotation_context, &t, &decls)
             }
+            Statement::NAMED_ENUM_DECL(t, variants) => {
+                self.gen_named_enum(annotation_context, &t, &variants)
+            }
+            // interfaces are only checked at compile time
+            Statement::NAMED_INTERFACE_DECL(_, _) => alloc_slot!(self),
             Statement::SLICE(items) => {
                 self.gen_slice(annotation_context, ast.position.clone(), &items)
             }
+            Statement::DICT(entries) => {
+                self.gen_dict(annotation_context, ast.position.clone(), &entries)
+            }
             Statement::INDEX(obj, index) => self.gen_index(annotation_context, &obj, &index),
             Statement::ANNOTATION(annotation, args, expr) => {
                 self.gen_annotation(annotation_context, &annotation, &args, &expr)
@@ -903,14 +1163,29 @@ impl BytecodeGenerator {
             Statement::STRUCT_ACCESS(expr, field) => {
                 self.gen_struct_access(annotation_context, &expr, &field)
             }
-            Statement::IMPORT(path) => self.gen_import(annotation_context, path),
+            Statement::IMPORT(path, alias) => {
+                self.gen_import(annotation_context, &ast.position, path, alias)
+            }
+            Statement::FROM_IMPORT(path, names) => {
+                self.gen_from_import(annotation_context, &ast.position, path, names)
+            }
+            Statement::EXPORT(declaration) => self.visit(annotation_context, &declaration),
+            Statement::DOC(_, declaration) => self.visit(annotation_context, &declaration),
             Statement::TRY(rhs) => self.gen_try(annotation_context, rhs),
             Statement::ASSERT(expr, msg) => self.gen_assert(annotation_context, expr, msg),
             _ => panic!(),
         }
     }
 
-    fn gen_program(&mut self, annotation_context: AnnotationContext, p: &Vec<ASTNode>) -> u8 {
+    fn gen_program(
+        &mut self,
+        annotation_context: AnnotationContext,
+        program: &ASTNode,
+        p: &Vec<ASTNode>,
+    ) -> u8 {
+        // the globals functions refer to are shared with them through cells, a module's functions
+        // outlive the frame it ran in so they can't find its globals there
+        self.setup_cells(&program.position, &vec![], program);
         for instruction in p {
             let result_slot = self.visit(annotation_context.clone(), instruction);
             free_slot!(self, result_slot);
@@ -919,6 +1194,13 @@ impl BytecodeGenerator {
     }
 
     fn gen_block(&mut self, annotation_context: AnnotationContext, b: &Vec<ASTNode>) -> u8 {
+        self.push_scope();
+        let result = self.gen_statements(annotation_context, b);
+        self.pop_scope();
+        result
+    }
+
+    fn gen_statements(&mut self, annotation_context: AnnotationContext, b: &Vec<ASTNode>) -> u8 {
         for instruction in b {
             let result_slot = self.visit(annotation_context.clone(), instruction);
             // free_slot!(self, result_slot);
@@ -926,6 +1208,22 @@ impl BytecodeGenerator {
         alloc_slot!(self)
     }
 
+    fn push_scope(&mut self) {
+        let variables =
+            &self.codegen_context.chunks[self.codegen_context.current_chunk_pointer].variable_map;
+        self.scopes.push(BlockScope {
+            declared: HashSet::new(),
+            outer_variables: variables.clone(),
+        });
+    }
+
+    // whatever the block declared goes out of scope, and what it shadowed is visible again
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        self.codegen_context.chunks[self.codegen_context.current_chunk_pointer].variable_map =
+            scope.outer_variables;
+    }
+
     fn gen_tuple(
         &mut self,
         annotation_context: AnnotationContext,
@@ -981,21 +1279,266 @@ impl BytecodeGenerator {
     fn gen_match(
         &mut self,
         annotation_context: AnnotationContext,
+        position: Position,
         to_match: &Box<ASTNode>,
-        patterns: &Vec<ASTNode>,
+        cases: &Vec<ASTNode>,
     ) -> u8 {
         let to_match_register = self.visit(annotation_context.clone(), &to_match);
+        // the value may be in a freed register, and the cases allocate, so keep it somewhere safe
+        let value = alloc_slot!(self);
+        if value != to_match_register {
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::MOV,
+                    arg_0: to_match_register,
+                    arg_1: value,
+                    arg_2: 0,
+                },
+                position.line as usize,
+            );
+        }
+        let result = alloc_slot!(self);
 
-        for match_case in patterns {
-            match &match_case.statement {
-                Statement::MATCH_CASE(_, expr) => {
-                    self.visit(annotation_context.clone(), &expr);
-                }
+        // each case tests its pattern and guard in turn, any failing check jumps to the next case
+        let mut end_jumps: Vec<usize> = vec![];
+        for match_case in cases {
+            let (pattern, guard, body) = match &match_case.statement {
+                Statement::MATCH_CASE(pattern, guard, body) => (pattern, guard, body),
                 _ => panic!(),
+            };
+            let mut fail_jumps: Vec<usize> = vec![];
+            // what the pattern binds is only visible in its case
+            self.push_scope();
+            self.test_pattern(
+                annotation_context.clone(),
+                &match_case.position,
+                pattern,
+                value,
+                &mut fail_jumps,
+            );
+            if let Some(guard) = guard {
+                let guard_register = self.visit(annotation_context.clone(), &guard);
+                self.jump_if_false(guard_register, &guard.position, &mut fail_jumps);
+                free_slot!(self, guard_register);
+            }
+
+            let body_register = self.visit(annotation_context.clone(), &body);
+            self.pop_scope();
+            if body_register != result {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: body_register,
+                        arg_1: result,
+                        arg_2: 0,
+                    },
+                    body.position.line as usize,
+                );
+            }
+            end_jumps.push(current_ip!(self));
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::JMP,
+                    arg_0: 0,
+                    arg_1: 0,
+                    arg_2: 0,
+                },
+                match_case.position.line as usize,
+            );
+
+            let next_case_ip = current_ip!(self);
+            for jump in fail_jumps {
+                let target = self.jump_target(next_case_ip, &match_case.position);
+                set_arg_value_at_loc!(self, jump, arg_1, target);
             }
         }
 
-        0
+        // falling out of the last case means nothing matched
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::MATCH_FAIL,
+                arg_0: value,
+                arg_1: std::cmp::min(position.index, u8::MAX as u32) as u8,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
+
+        let end_ip = current_ip!(self);
+        let target = self.jump_target(end_ip, &position);
+        for jump in end_jumps {
+            set_arg_value_at_loc!(self, jump, arg_0, target);
+        }
+        free_slot!(self, value);
+        result
+    }
+
+    // like bind_pattern but every check that can fail adds a jump to `fail_jumps` instead of
+    // raising an error, names are bound as they are reached
+    fn test_pattern(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        pattern: &Pattern,
+        value: u8,
+        fail_jumps: &mut Vec<usize>,
+    ) {
+        match pattern {
+            Pattern::WILDCARD(_) => {}
+            Pattern::BIND(t) => {
+                self.define_variable(annotation_context, pos, t, value);
+            }
+            Pattern::LITERAL(literal) => {
+                let literal_register = self.visit(annotation_context, &literal);
+                let cond = alloc_slot!(self);
+                // the literal goes on the left so any value can be compared against it
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::EQUAL,
+                        arg_0: literal_register,
+                        arg_1: value,
+                        arg_2: cond,
+                    },
+                    literal.position.line as usize,
+                );
+                self.jump_if_false(cond, &literal.position, fail_jumps);
+                free_slot!(self, literal_register);
+                free_slot!(self, cond);
+            }
+            Pattern::RANGE(start, end) => {
+                // comparing numbers against anything else is an error, so check that first
+                let start_register = self.visit(annotation_context.clone(), &start);
+                let cond = alloc_slot!(self);
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::SAME_TYPE,
+                        arg_0: value,
+                        arg_1: start_register,
+                        arg_2: cond,
+                    },
+                    start.position.line as usize,
+                );
+                self.jump_if_false(cond, &start.position, fail_jumps);
+                free_slot!(self, start_register);
+                free_slot!(self, cond);
+                for (bound, op_instruction) in [
+                    (start, OpInstruction::GREATER_EQUAL),
+                    (end, OpInstruction::LESS_THAN),
+                ] {
+                    let bound_register = self.visit(annotation_context.clone(), &bound);
+                    let cond = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction,
+                            arg_0: value,
+                            arg_1: bound_register,
+                            arg_2: cond,
+                        },
+                        bound.position.line as usize,
+                    );
+                    self.jump_if_false(cond, &bound.position, fail_jumps);
+                    free_slot!(self, bound_register);
+                    free_slot!(self, cond);
+                }
+            }
+            Pattern::TUPLE(items, tuple_pos) => {
+                let cond = alloc_slot!(self);
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::HAS_LENGTH,
+                        arg_0: value,
+                        arg_1: items.len() as u8,
+                        arg_2: cond,
+                    },
+                    tuple_pos.line as usize,
+                );
+                self.jump_if_false(cond, tuple_pos, fail_jumps);
+                free_slot!(self, cond);
+                for (i, item) in items.iter().enumerate() {
+                    let index_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::ADDI,
+                            arg_0: 0,
+                            arg_1: i as u8,
+                            arg_2: index_reg,
+                        },
+                        tuple_pos.line as usize,
+                    );
+                    let item_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::INDEX,
+                            arg_0: value,
+                            arg_1: index_reg,
+                            arg_2: item_reg,
+                        },
+                        tuple_pos.line as usize,
+                    );
+                    self.test_pattern(
+                        annotation_context.clone(),
+                        tuple_pos,
+                        item,
+                        item_reg,
+                        fail_jumps,
+                    );
+                    free_slot!(self, index_reg);
+                    free_slot!(self, item_reg);
+                }
+            }
+            Pattern::STRUCT(typ, fields, struct_pos) => {
+                let type_reg = self.visit(annotation_context.clone(), &typ);
+                let cond = alloc_slot!(self);
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::IS_INSTANCE,
+                        arg_0: value,
+                        arg_1: type_reg,
+                        arg_2: cond,
+                    },
+                    struct_pos.line as usize,
+                );
+                self.jump_if_false(cond, struct_pos, fail_jumps);
+                free_slot!(self, type_reg);
+                free_slot!(self, cond);
+                for (field, field_pattern) in fields {
+                    let string_reg =
+                        self.create_constant_string(field.as_identifier().to_string(), struct_pos);
+                    let field_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::STRUCT_ACCESS,
+                            arg_0: value,
+                            arg_1: string_reg,
+                            arg_2: field_reg,
+                        },
+                        struct_pos.line as usize,
+                    );
+                    self.test_pattern(
+                        annotation_context.clone(),
+                        struct_pos,
+                        field_pattern,
+                        field_reg,
+                        fail_jumps,
+                    );
+                    free_slot!(self, string_reg);
+                    free_slot!(self, field_reg);
+                }
+            }
+        }
+    }
+
+    fn jump_if_false(&mut self, cond: u8, pos: &Position, jumps: &mut Vec<usize>) {
+        jumps.push(current_ip!(self));
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::IF_JMP_FALSE,
+                arg_0: cond,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            pos.line as usize,
+        );
     }
 
     fn gen_test(
@@ -1005,6 +1548,11 @@ impl BytecodeGenerator {
         name: &ASTNode,
         body: &ASTNode,
     ) -> u8 {
+        // only a test build runs tests, and they can use what's marked @test_only which is left
+        // out of any other build
+        if !self.test_build {
+            return alloc_slot!(self);
+        }
         let func_name = match &name.statement {
             Statement::STRING(s) => format!("test_{}", s.as_string().to_string()),
             _ => panic!("expected string but got {:?}.", name),
@@ -1012,7 +1560,7 @@ impl BytecodeGenerator {
         self.create_function(
             annotation_context,
             &position,
-            Rc::new(func_name),
+            Some(Rc::new(func_name)),
             body,
             &vec![],
             &None,
@@ -1058,10 +1606,12 @@ impl BytecodeGenerator {
         if else_body.is_some() {
             self.visit(annotation_context, &else_body.as_ref().unwrap());
         }
-        set_arg_value_at_loc!(self, saved_if_ip, arg_1, ip_after_body.try_into().unwrap());
+        let target = self.jump_target(ip_after_body, &position);
+        set_arg_value_at_loc!(self, saved_if_ip, arg_1, target);
         // now insert the jump after the body
         let ip_at_end = current_ip!(self);
-        set_arg_value_at_loc!(self, jump_ip, arg_0, ip_at_end.try_into().unwrap());
+        let target = self.jump_target(ip_at_end, &position);
+        set_arg_value_at_loc!(self, jump_ip, arg_0, target);
 
         alloc_slot!(self)
     }
@@ -1079,8 +1629,8 @@ impl BytecodeGenerator {
         first: &Box<ASTNode>,
         second: &Box<ASTNode>,
     ) -> u8 {
-        // setup the ("counter", "limit") tuple
-        let mut kwarg_strings: Vec<Object> = vec![];
+        // setup the (0, "counter", "limit") tuple
+        let mut kwarg_strings: Vec<Object> = vec![Object::I64(0)];
         let gc_ref_data_idx = self.push_gc_ref_data(GCRefData::STRING(StringObject {
             s: Rc::new("counter".to_owned()),
         }));
@@ -1150,14 +1700,12 @@ impl BytecodeGenerator {
             },
             position.line as usize,
         );
-        let range_iterator_type = &self.codegen_context.chunks
-            [self.codegen_context.current_chunk_pointer]
-            .variable_map
-            .get(&Rc::new("RangeIterator".to_string()));
+        let range_iterator_type =
+            self.load_variable(position, &Rc::new("RangeIterator".to_string()));
         self.push_instruction(
             Instruction {
                 op_instruction: OpInstruction::CALL_KW,
-                arg_0: *range_iterator_type.unwrap(),
+                arg_0: range_iterator_type.unwrap(),
                 arg_1: const_reg,
                 arg_2: first_arg_register,
             },
@@ -1171,11 +1719,23 @@ impl BytecodeGenerator {
         &mut self,
         annotation_context: AnnotationContext,
         position: Position,
-        var: &Token,
+        var: &Pattern,
         iter_obj: &Box<ASTNode>,
         body: &Box<ASTNode>,
     ) -> u8 {
-        let range_iterator_reg = self.visit(annotation_context.clone(), &iter_obj);
+        let iterable_reg = self.visit(annotation_context.clone(), &iter_obj);
+        // the loop gets its own register for the iterator, so a map held in a variable isn't
+        // replaced by the iterator over it
+        let range_iterator_reg = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::GET_ITER,
+                arg_0: iterable_reg,
+                arg_1: range_iterator_reg,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
 
         let for_iter_instruction_ptr = self.codegen_context.chunks
             [self.codegen_context.current_chunk_pointer]
@@ -1192,22 +1752,42 @@ impl BytecodeGenerator {
             },
             position.line as usize,
         );
+        // the loop variable is only visible in the loop
+        self.push_scope();
+        self.bind_pattern(
+            annotation_context.clone(),
+            &position,
+            var,
+            iter_result_reg,
+            true,
+        );
+        self.loops.push(LoopContext {
+            continue_ip: for_iter_instruction_ptr,
+            break_jumps: vec![],
+        });
         self.visit(annotation_context, &body);
+        self.pop_scope();
+        let target = self.jump_target(for_iter_instruction_ptr, &position);
         self.push_instruction(
             Instruction {
                 op_instruction: OpInstruction::JMP,
-                arg_0: for_iter_instruction_ptr as u8,
+                arg_0: target,
                 arg_1: 0,
                 arg_2: 0,
             },
             position.line as usize,
         );
+        // breaking out lands in the same place as the iterator finishing, so the iterator
+        // registers are only released once both ways out have been generated
         let current_ip = current_ip!(self);
+        let target = self.jump_target(current_ip, &position);
         self.codegen_context.chunks[self.codegen_context.current_chunk_pointer].instructions
             [for_iter_instruction_ptr]
-            .arg_1 = current_ip as u8;
+            .arg_1 = target;
+        self.patch_breaks(current_ip, &position);
 
         // todo also free the kwarg
+        free_slot!(self, iterable_reg);
         free_slot!(self, range_iterator_reg);
         free_slot!(self, iter_result_reg);
 
@@ -1222,68 +1802,206 @@ impl BytecodeGenerator {
         alloc_slot!(self)
     }
 
-    fn gen_literal_num(
+    fn gen_while(
         &mut self,
         annotation_context: AnnotationContext,
-        pos: Position,
-        t: &Token,
+        position: Position,
+        cond: &Box<ASTNode>,
+        body: &Box<ASTNode>,
     ) -> u8 {
-        // so currently we just add to a new register
-        if let Some(n) = self.parse_embedding_instruction_number(&t.typ) {
-            let reg = alloc_slot!(self);
-            self.push_instruction(
-                Instruction {
-                    op_instruction: OpInstruction::ADDI,
-                    arg_0: 0,
-                    arg_1: n,
-                    arg_2: reg,
-                },
-                pos.line as usize,
-            );
-            return reg;
-        } else {
-            let constant_idx = match self.parse_literal_num_string(t.as_number()) {
-                DataType::I64 => self.push_constant(Object::I64(self.parse_i64(t.as_number()))),
-                DataType::F64 => self.push_constant(Object::F64(self.parse_f64(t.as_number()))),
-                _ => panic!(),
-            };
+        let cond_ip = current_ip!(self);
+        let cond_reg = self.visit(annotation_context.clone(), &cond);
+        let if_jmp_ip = current_ip!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::IF_JMP_FALSE,
+                arg_0: cond_reg,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
 
-            let dest = alloc_slot!(self);
-            self.push_instruction(
-                Instruction {
-                    op_instruction: OpInstruction::LOAD_CONST,
-                    arg_0: constant_idx,
-                    arg_1: dest,
-                    arg_2: 0,
-                },
-                pos.line as usize,
-            );
-            return dest;
-        }
+        self.loops.push(LoopContext {
+            continue_ip: cond_ip,
+            break_jumps: vec![],
+        });
+        self.visit(annotation_context, &body);
+        let target = self.jump_target(cond_ip, &position);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::JMP,
+                arg_0: target,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
+        let current_ip = current_ip!(self);
+        let target = self.jump_target(current_ip, &position);
+        set_arg_value_at_loc!(self, if_jmp_ip, arg_1, target);
+        self.patch_breaks(current_ip, &position);
+
+        free_slot!(self, cond_reg);
+        alloc_slot!(self)
     }
 
-    fn gen_literal_bool(
+    fn gen_loop(
         &mut self,
         annotation_context: AnnotationContext,
-        pos: Position,
-        b: bool,
+        position: Position,
+        body: &Box<ASTNode>,
     ) -> u8 {
-        // we need to push the atom as a constant?
-
-        // todo maybe have a constant hashmap?
-        let const_index = self.push_constant(Object::BOOL(b));
-        let reg = alloc_slot!(self);
+        let start_ip = current_ip!(self);
+        self.loops.push(LoopContext {
+            continue_ip: start_ip,
+            break_jumps: vec![],
+        });
+        self.visit(annotation_context, &body);
+        let target = self.jump_target(start_ip, &position);
         self.push_instruction(
             Instruction {
-                op_instruction: OpInstruction::LOAD_CONST,
-                arg_0: const_index,
-                arg_1: reg,
+                op_instruction: OpInstruction::JMP,
+                arg_0: target,
+                arg_1: 0,
                 arg_2: 0,
             },
-            pos.line.try_into().unwrap(),
+            position.line as usize,
         );
-        return reg;
-    }
+        let current_ip = current_ip!(self);
+        self.patch_breaks(current_ip, &position);
+
+        alloc_slot!(self)
+    }
+
+    fn gen_break(&mut self, position: Position) -> u8 {
+        let jump_ip = current_ip!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::JMP,
+                arg_0: 0,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
+        // the parser makes sure we are always inside a loop here
+        self.loops.last_mut().unwrap().break_jumps.push(jump_ip);
+        alloc_slot!(self)
+    }
+
+    fn gen_continue(&mut self, position: Position) -> u8 {
+        let continue_ip = self.loops.last().unwrap().continue_ip;
+        let target = self.jump_target(continue_ip, &position);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::JMP,
+                arg_0: target,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
+        alloc_slot!(self)
+    }
+
+    // pops the innermost loop and points all of its breaks at the end of it
+    fn patch_breaks(&mut self, end_ip: usize, position: &Position) {
+        let loop_context = self.loops.pop().unwrap();
+        let target = self.jump_target(end_ip, position);
+        for break_jump in loop_context.break_jumps {
+            set_arg_value_at_loc!(self, break_jump, arg_0, target);
+        }
+    }
+
+    // jumps only have a byte to say where they go, so code which is too long is reported once
+    // rather than jumping to the wrong place
+    fn jump_target(&mut self, ip: usize, position: &Position) -> u8 {
+        let target = u8::try_from(ip);
+        if target.is_err() {
+            if !self
+                .errors
+                .iter()
+                .any(|e| matches!(e, CodegenError::JUMP_TOO_FAR(_)))
+            {
+                self.errors
+                    .push(CodegenError::JUMP_TOO_FAR(position.clone()));
+            }
+            return 0;
+        }
+        return target.unwrap();
+    }
+
+    fn gen_literal_num(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        t: &Token,
+    ) -> u8 {
+        // so currently we just add to a new register
+        if let Some(n) = self.parse_embedding_instruction_number(&t.typ) {
+            let reg = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::ADDI,
+                    arg_0: 0,
+                    arg_1: n,
+                    arg_2: reg,
+                },
+                pos.line as usize,
+            );
+            return reg;
+        } else {
+            let num = t.as_number();
+            let (digits, typ) = DataType::from_number_literal(&num);
+            // all the integer types are i64 at runtime, and floats are f64
+            let constant_idx = match typ {
+                DataType::U32 | DataType::I32 | DataType::I64 => {
+                    self.push_constant(Object::I64(self.parse_i64(digits)))
+                }
+                DataType::F32 => {
+                    self.push_constant(Object::F64(self.parse_f64(digits) as f32 as f64))
+                }
+                DataType::F64 => self.push_constant(Object::F64(self.parse_f64(digits))),
+                _ => panic!(),
+            };
+
+            let dest = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::LOAD_CONST,
+                    arg_0: constant_idx,
+                    arg_1: dest,
+                    arg_2: 0,
+                },
+                pos.line as usize,
+            );
+            return dest;
+        }
+    }
+
+    fn gen_literal_bool(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        b: bool,
+    ) -> u8 {
+        // we need to push the atom as a constant?
+
+        // todo maybe have a constant hashmap?
+        let const_index = self.push_constant(Object::BOOL(b));
+        let reg = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::LOAD_CONST,
+                arg_0: const_index,
+                arg_1: reg,
+                arg_2: 0,
+            },
+            pos.line.try_into().unwrap(),
+        );
+        return reg;
+    }
 
     fn gen_atom(
         &mut self,
@@ -1364,25 +2082,146 @@ impl BytecodeGenerator {
         panic!()
     }
 
+    fn gen_interpolated_string(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        parts: &Vec<ASTNode>,
+    ) -> u8 {
+        let mut registers: Vec<u8> = vec![];
+        for part in parts {
+            registers.push(self.visit(annotation_context.clone(), part));
+        }
+
+        let new_arg_registers = find_contiguous_slots!(self, &registers);
+        for i in 0..registers.len() {
+            let current_arg_reg = registers[i];
+            let new_reg = new_arg_registers[i];
+
+            if current_arg_reg != new_reg {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: current_arg_reg,
+                        arg_1: new_reg,
+                        arg_2: 0,
+                    },
+                    pos.line as usize,
+                );
+            }
+        }
+
+        let dest = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::BUILD_STRING,
+                arg_0: new_arg_registers[0],
+                arg_1: new_arg_registers.len() as u8,
+                arg_2: dest,
+            },
+            pos.line as usize,
+        );
+
+        for reg in registers {
+            free_slot!(self, reg);
+        }
+        for reg in new_arg_registers {
+            free_slot!(self, reg);
+        }
+
+        dest
+    }
+
+    fn resolve_variable(&mut self, name: &Rc<String>) -> Option<VariableLocation> {
+        let chunk = &self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+        if let Some(v) = chunk.variable_map.get(name) {
+            if chunk.cells.contains(name) {
+                return Some(VariableLocation::CELL(*v));
+            }
+            return Some(VariableLocation::REGISTER(*v));
+        }
+        let upvalue = self.resolve_upvalue(self.codegen_context.current_chunk_pointer, name);
+        if let Some(idx) = upvalue {
+            return Some(VariableLocation::UPVALUE(idx));
+        }
+        None
+    }
+
+    // finds (or adds) the upvalue for a variable captured from an enclosing function
+    fn resolve_upvalue(&mut self, chunk_idx: usize, name: &Rc<String>) -> Option<u8> {
+        // the outermost chunk has nothing enclosing it
+        if chunk_idx == 0 {
+            return None;
+        }
+        let existing = self.codegen_context.chunks[chunk_idx]
+            .upvalues
+            .iter()
+            .position(|(n, _)| n == name);
+        if let Some(idx) = existing {
+            return Some(idx as u8);
+        }
+
+        let parent = &self.codegen_context.chunks[chunk_idx - 1];
+        let source = if let Some(v) = parent.variable_map.get(name) {
+            if !parent.cells.contains(name) {
+                return None;
+            }
+            UpvalueSource::LOCAL(*v)
+        } else {
+            UpvalueSource::UPVALUE(self.resolve_upvalue(chunk_idx - 1, name)?)
+        };
+
+        self.codegen_context.chunks[chunk_idx]
+            .upvalues
+            .push((name.clone(), source));
+        Some((self.codegen_context.chunks[chunk_idx].upvalues.len() - 1) as u8)
+    }
+
     fn lookup_variable_recursively(
         &mut self,
         annotation_context: AnnotationContext,
         pos: &Position,
         t: &Token,
     ) -> Option<u8> {
-        let result = self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
-            .variable_map
-            .get(&t.as_identifier());
+        return self.load_variable(pos, &t.as_identifier());
+    }
 
-        if let Some(v) = result {
-            return Some(*v);
+    // the register holding the variable, loading it there first if it's somewhere else
+    fn load_variable(&mut self, pos: &Position, name: &Rc<String>) -> Option<u8> {
+        let result = self.resolve_variable(name);
+
+        if let Some(VariableLocation::REGISTER(v)) = result {
+            return Some(v);
+        } else if let Some(VariableLocation::CELL(cell)) = result {
+            let reg = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::LOAD_CELL,
+                    arg_0: cell,
+                    arg_1: reg,
+                    arg_2: 0,
+                },
+                pos.line as usize,
+            );
+            return Some(reg);
+        } else if let Some(VariableLocation::UPVALUE(idx)) = result {
+            let reg = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::LOAD_UPVALUE,
+                    arg_0: idx,
+                    arg_1: reg,
+                    arg_2: 0,
+                },
+                pos.line as usize,
+            );
+            return Some(reg);
         } else {
+            // only the builtins get here, they're in the same registers in every outermost frame
             let reg = alloc_slot!(self);
             let mut counter = self.codegen_context.current_chunk_pointer;
             loop {
-                let result = self.codegen_context.chunks[counter]
-                    .variable_map
-                    .get(&t.as_identifier());
+                let result = self.codegen_context.chunks[counter].variable_map.get(name);
                 if let Some(v) = result {
                     self.push_instruction(
                         Instruction {
@@ -1410,12 +2249,16 @@ impl BytecodeGenerator {
         pos: Position,
         t: &Token,
     ) -> u8 {
-        // todo we assume it exists so return the map
         let res = self.lookup_variable_recursively(annotation_context, &pos, t);
         if res.is_some() {
             return res.unwrap();
         }
-        panic!();
+        if self.test_only.contains(&t.as_identifier()) {
+            self.errors.push(CodegenError::TEST_ONLY(t.clone()));
+        } else {
+            self.errors.push(CodegenError::UNKNOWN_VARIABLE(t.clone()));
+        }
+        alloc_slot!(self)
     }
 
     fn get_variable(
@@ -1445,36 +2288,316 @@ impl BytecodeGenerator {
             // todo definitely define, lets initialise to 'blank'
         }
 
-        match value {
-            Some(v) => {
-                let location = self.visit(annotation_context.clone(), &v);
-
-                let existing_var = self.lookup_variable_recursively(annotation_context, &pos, var);
-                let var_location = if existing_var.is_some() {
-                    existing_var.unwrap()
-                } else {
-                    alloc_perm_slot!(self)
-                };
-
-                self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
-                    .variable_map
-                    .insert(var.as_identifier(), var_location);
+        match value {
+            Some(v) => {
+                let location = self.visit(annotation_context.clone(), &v);
+                let var_location = self.define_variable(annotation_context, &pos, var, location);
+                if var_location != location {
+                    free_slot!(self, location);
+                }
+                return var_location;
+            }
+            None => panic!(),
+        }
+    }
+
+    // declares the variable in the current block, which hides any with the same name outside it.
+    // declaring it again in the same block just gives it a new value
+    fn define_variable(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        var: &Token,
+        location: u8,
+    ) -> u8 {
+        let name = var.as_identifier();
+        let shadows = match self.scopes.last_mut() {
+            Some(scope) => scope.declared.insert(name.clone()),
+            None => false,
+        };
+        if shadows {
+            return self.define_block_variable(pos, name, location);
+        }
+        let chunk = &self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+        let existing_var = chunk.variable_map.get(&name).copied();
+        // captured variables are written through their cell so every closure sees the change
+        if let Some(cell) = existing_var.filter(|_| chunk.cells.contains(&name)) {
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::STORE_CELL,
+                    arg_0: cell,
+                    arg_1: location,
+                    arg_2: 0,
+                },
+                pos.line.try_into().unwrap(),
+            );
+            return location;
+        }
+
+        let var_location = if existing_var.is_some() {
+            existing_var.unwrap()
+        } else {
+            alloc_perm_slot!(self)
+        };
+
+        self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+            .variable_map
+            .insert(name, var_location);
+
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::MOV,
+                arg_0: location,
+                arg_1: var_location,
+                arg_2: 0,
+            },
+            pos.line.try_into().unwrap(),
+        );
+
+        return var_location;
+    }
+
+    // the first declaration of a name in a block always gets a new register, and a new cell if
+    // it's captured, so that whatever it shadows is left alone
+    fn define_block_variable(&mut self, pos: &Position, name: Rc<String>, location: u8) -> u8 {
+        let var_location = alloc_perm_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::MOV,
+                arg_0: location,
+                arg_1: var_location,
+                arg_2: 0,
+            },
+            pos.line.try_into().unwrap(),
+        );
+        let chunk = &mut self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+        let is_cell = chunk.cells.contains(&name);
+        chunk.variable_map.insert(name, var_location);
+        if is_cell {
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::NEW_CELL,
+                    arg_0: var_location,
+                    arg_1: 0,
+                    arg_2: 0,
+                },
+                pos.line.try_into().unwrap(),
+            );
+            return location;
+        }
+        return var_location;
+    }
+
+    // gives the nearest variable with this name a new value, whichever function declared it
+    fn assign_variable(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        var: &Token,
+        location: u8,
+    ) -> u8 {
+        let name = var.as_identifier();
+        let store = match self.resolve_variable(&name) {
+            Some(VariableLocation::REGISTER(reg)) => {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: location,
+                        arg_1: reg,
+                        arg_2: 0,
+                    },
+                    pos.line.try_into().unwrap(),
+                );
+                return reg;
+            }
+            Some(VariableLocation::CELL(cell)) => Some((OpInstruction::STORE_CELL, cell)),
+            Some(VariableLocation::UPVALUE(idx)) => Some((OpInstruction::STORE_UPVALUE, idx)),
+            None => None,
+        };
+        if let Some((op_instruction, target)) = store {
+            self.push_instruction(
+                Instruction {
+                    op_instruction,
+                    arg_0: target,
+                    arg_1: location,
+                    arg_2: 0,
+                },
+                pos.line.try_into().unwrap(),
+            );
+            return location;
+        }
+
+        // globals functions use are cells, so what's left are the builtins, which every frame
+        // that ran a program or module has in the same registers
+        let mut counter = self.codegen_context.current_chunk_pointer;
+        while counter > 0 {
+            counter -= 1;
+            let slot = self.codegen_context.chunks[counter]
+                .variable_map
+                .get(&name)
+                .copied();
+            if let Some(slot) = slot {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::STORE_CLOSURE,
+                        arg_0: counter as u8,
+                        arg_1: slot,
+                        arg_2: location,
+                    },
+                    pos.line.try_into().unwrap(),
+                );
+                return location;
+            }
+        }
+
+        self.errors
+            .push(CodegenError::UNDECLARED_VARIABLE(var.clone()));
+        // carry on as if it had been declared so its uses aren't reported as well
+        return self.define_variable(annotation_context, pos, var, location);
+    }
+
+    fn gen_destructure(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        pattern: &Pattern,
+        value: &Option<Box<ASTNode>>,
+    ) -> u8 {
+        match value {
+            Some(v) => {
+                let location = self.visit(annotation_context.clone(), &v);
+                self.bind_pattern(annotation_context, &pos, pattern, location, true);
+                return location;
+            }
+            // parameters are bound when the function is created
+            None => panic!(),
+        }
+    }
+
+    // takes the value in the register apart and binds each piece to the names in the pattern,
+    // either declaring them or giving the existing variables new values
+    fn bind_pattern(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        pattern: &Pattern,
+        value: u8,
+        define: bool,
+    ) {
+        match pattern {
+            Pattern::BIND(t) if define => {
+                self.define_variable(annotation_context, pos, t, value);
+                return;
+            }
+            Pattern::BIND(t) => {
+                self.assign_variable(annotation_context, pos, t, value);
+                return;
+            }
+            Pattern::WILDCARD(_) => return,
+            _ => {}
+        }
+
+        // the value may be in a register that has already been freed (i.e. a call result), so
+        // claim one for it before allocating anything else
+        let held = alloc_slot!(self);
+        if held != value {
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::MOV,
+                    arg_0: value,
+                    arg_1: held,
+                    arg_2: 0,
+                },
+                pos.line as usize,
+            );
+        }
+        let value = held;
 
+        match pattern {
+            Pattern::BIND(_) | Pattern::WILDCARD(_) => {}
+            Pattern::LITERAL(_) | Pattern::RANGE(_, _) => {
+                panic!("refutable patterns can only be used in a match")
+            }
+            Pattern::TUPLE(items, tuple_pos) => {
                 self.push_instruction(
                     Instruction {
-                        op_instruction: OpInstruction::MOV,
-                        arg_0: location,
-                        arg_1: var_location,
-                        arg_2: 0,
+                        op_instruction: OpInstruction::CHECK_ARITY,
+                        arg_0: value,
+                        arg_1: items.len() as u8,
+                        arg_2: std::cmp::min(tuple_pos.index, u8::MAX as u32) as u8,
                     },
-                    pos.line.try_into().unwrap(),
+                    tuple_pos.line as usize,
                 );
-
-                free_slot!(self, location);
-                return var_location;
+                for (i, item) in items.iter().enumerate() {
+                    let index_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::ADDI,
+                            arg_0: 0,
+                            arg_1: i as u8,
+                            arg_2: index_reg,
+                        },
+                        tuple_pos.line as usize,
+                    );
+                    let item_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::INDEX,
+                            arg_0: value,
+                            arg_1: index_reg,
+                            arg_2: item_reg,
+                        },
+                        tuple_pos.line as usize,
+                    );
+                    self.bind_pattern(
+                        annotation_context.clone(),
+                        tuple_pos,
+                        item,
+                        item_reg,
+                        define,
+                    );
+                    free_slot!(self, index_reg);
+                    free_slot!(self, item_reg);
+                }
+            }
+            Pattern::STRUCT(typ, fields, struct_pos) => {
+                let type_reg = self.visit(annotation_context.clone(), &typ);
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::CHECK_INSTANCE,
+                        arg_0: value,
+                        arg_1: type_reg,
+                        arg_2: std::cmp::min(struct_pos.index, u8::MAX as u32) as u8,
+                    },
+                    struct_pos.line as usize,
+                );
+                free_slot!(self, type_reg);
+                for (field, field_pattern) in fields {
+                    let string_reg =
+                        self.create_constant_string(field.as_identifier().to_string(), struct_pos);
+                    let field_reg = alloc_slot!(self);
+                    self.push_instruction(
+                        Instruction {
+                            op_instruction: OpInstruction::DESTRUCTURE_FIELD,
+                            arg_0: value,
+                            arg_1: string_reg,
+                            arg_2: field_reg,
+                        },
+                        struct_pos.line as usize,
+                    );
+                    self.bind_pattern(
+                        annotation_context.clone(),
+                        struct_pos,
+                        field_pattern,
+                        field_reg,
+                        define,
+                    );
+                    free_slot!(self, string_reg);
+                    free_slot!(self, field_reg);
+                }
             }
-            None => panic!(),
         }
+        free_slot!(self, value);
     }
 
     fn gen_assign(
@@ -1507,6 +2630,32 @@ impl BytecodeGenerator {
                 }
                 panic!()
             }
+            Statement::INDEX(obj, index) => {
+                let obj_reg = self.visit(annotation_context.clone(), &obj);
+                let index_reg = self.visit(annotation_context.clone(), &index);
+                let value_reg = self.visit(annotation_context.clone(), rhs);
+
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::INDEX_SET,
+                        arg_0: obj_reg,
+                        arg_1: index_reg,
+                        arg_2: value_reg,
+                    },
+                    pos.line as usize,
+                );
+                self.mark_position(&index.position);
+
+                return value_reg;
+            }
+            Statement::VARIABLE(t) => {
+                let location = self.visit(annotation_context.clone(), rhs);
+                let var_location = self.assign_variable(annotation_context, &pos, t, location);
+                if var_location != location {
+                    free_slot!(self, location);
+                }
+                return var_location;
+            }
             _ => todo!(),
         }
 
@@ -1521,22 +2670,15 @@ impl BytecodeGenerator {
         }
     }
 
-    fn parse_i64(&self, num: Rc<String>) -> i64 {
+    // the lexer has already checked the literal is in range
+    fn parse_i64(&self, num: &str) -> i64 {
         num.parse::<i64>().ok().unwrap()
     }
 
-    fn parse_f64(&self, s: Rc<String>) -> f64 {
+    fn parse_f64(&self, s: &str) -> f64 {
         s.parse::<f64>().ok().unwrap()
     }
 
-    fn parse_literal_num_string(&self, num: Rc<String>) -> DataType {
-        if num.contains(".") {
-            return DataType::F64;
-        } else {
-            return DataType::I64;
-        }
-    }
-
     fn gen_call(
         &mut self,
         annotation_context: AnnotationContext,
@@ -1623,6 +2765,8 @@ impl BytecodeGenerator {
 
             let mut kwarg_strings: Vec<Object> = vec![];
             let mut num_kwargs = 0;
+            let mut num_positional = 0;
+            let mut spread_flags: Vec<Object> = vec![];
 
             // todo args are currently not in successive registers.
             // tod fix this we have a few options.
@@ -1652,10 +2796,25 @@ impl BytecodeGenerator {
                             _ => panic!(),
                         }
                     }
-                    _ => arg_registers.push(self.visit(annotation_context.clone(), arg)),
+                    Statement::SPREAD(spread) => {
+                        // the number of arguments isn't known until runtime so only CALL_KW can
+                        // take them apart
+                        is_kw_call = true;
+                        num_positional += 1;
+                        spread_flags.push(Object::BOOL(true));
+                        arg_registers.push(self.visit(annotation_context.clone(), spread))
+                    }
+                    _ => {
+                        num_positional += 1;
+                        spread_flags.push(Object::BOOL(false));
+                        arg_registers.push(self.visit(annotation_context.clone(), arg))
+                    }
                 }
             }
             num_kwargs = kwarg_strings.len();
+            let mut call_info: Vec<Object> = vec![Object::I64(num_positional)];
+            call_info.extend(spread_flags);
+            call_info.extend(kwarg_strings);
 
             // todo find a register to put the args into!!!
 
@@ -1697,7 +2856,7 @@ impl BytecodeGenerator {
                 // build the tuple
                 // todo the issue is the gc ref strings wont get set when we init constants in the execution engine
                 // so we need to init nested gc refs
-                let gc_ref_data_idx = self.push_gc_ref_data(GCRefData::TUPLE(kwarg_strings));
+                let gc_ref_data_idx = self.push_gc_ref_data(GCRefData::TUPLE(call_info));
                 let constant_idx = self.push_constant(Object::GC_REF(GCRef {
                     index: gc_ref_data_idx as usize,
                     marked: false,
@@ -1725,14 +2884,8 @@ impl BytecodeGenerator {
                 );
 
                 free_slot!(self, callee_register);
-                for slot in &arg_registers {
-                    free_slot!(self, *slot);
-                }
-                for i in
-                    first_arg_register + 1..first_arg_register + new_arg_registers.len() as u8 + 1
-                {
-                    free_slot!(self, i);
-                }
+                free_slot!(self, const_reg);
+                self.free_call_registers(&arg_registers, &new_arg_registers);
 
                 return new_allocated_destination;
             } else {
@@ -1747,19 +2900,23 @@ impl BytecodeGenerator {
                 );
 
                 free_slot!(self, callee_register);
-                for slot in &arg_registers {
-                    free_slot!(self, *slot);
-                }
-                // we -1 because the destination is in here
-                for i in first_arg_register + 1..first_arg_register + arg_registers.len() as u8 - 1
-                {
-                    free_slot!(self, i);
-                }
+                self.free_call_registers(&arg_registers, &new_arg_registers);
                 return new_allocated_destination;
             }
         }
     }
 
+    // frees the arguments wherever they were moved from and to, the destination is last once they're
+    // moved and holds the result so stays allocated until whoever uses it frees it
+    fn free_call_registers(&mut self, arg_registers: &Vec<u8>, new_arg_registers: &Vec<u8>) {
+        let destination = new_arg_registers[new_arg_registers.len() - 1];
+        for slot in arg_registers.iter().chain(new_arg_registers.iter()) {
+            if *slot != destination {
+                free_slot!(self, *slot);
+            }
+        }
+    }
+
     fn gen_bin_op(
         &mut self,
         annotation_context: AnnotationContext,
@@ -1768,10 +2925,11 @@ impl BytecodeGenerator {
         e2: &Box<ASTNode>,
         op: &Op,
     ) -> u8 {
-        // todo only do literals & we need to deal with slot allocation
-
-        // lets see if e1 and e2 can fit in registers
+        if op == &Op::LOGICAL_AND || op == &Op::LOGICAL_OR {
+            return self.gen_logical_op(annotation_context, pos, e1, e2, op);
+        }
 
+        // small integer literals can be embedded directly in the instruction
         if op == &Op::ADD || op == &Op::SUB {
             if let (Statement::LITERAL_NUM(i1), Statement::LITERAL_NUM(i2)) =
                 (&e1.statement, &e2.statement)
@@ -1781,192 +2939,146 @@ impl BytecodeGenerator {
                     self.parse_embedding_instruction_number(&i2.typ),
                 ) {
                     let register = alloc_slot!(self);
-
-                    // todo check instruction type
                     self.push_instruction(
                         Instruction {
                             op_instruction: match op {
                                 Op::ADD => OpInstruction::ADDI,
-                                _ => panic!(),
+                                _ => OpInstruction::SUBI,
                             },
                             arg_0: n1,
                             arg_1: n2,
                             arg_2: register,
                         },
-                        pos.line.try_into().unwrap(),
-                    );
-                    return register;
-                } else {
-                    let lhs = self.visit(annotation_context.clone(), &e1);
-                    let rhs = self.visit(annotation_context.clone(), &e2);
-                    let register = alloc_slot!(self);
-
-                    // todo check instruction type
-                    self.push_instruction(
-                        Instruction {
-                            op_instruction: match op {
-                                Op::ADD => OpInstruction::ADD,
-                                Op::EQ => OpInstruction::EQUAL,
-                                Op::NEQ => OpInstruction::NOT_EQUALS,
-                                Op::GT => OpInstruction::GREATER_THAN,
-                                Op::GE => OpInstruction::GREATER_EQUAL,
-                                Op::LT => OpInstruction::LESS_THAN,
-                                Op::LE => OpInstruction::LESS_EQUAL,
-                                Op::LOGICAL_OR => OpInstruction::LOGICAL_OR,
-                                Op::MUL => OpInstruction::MUL,
-                                Op::BITWISE_OR => OpInstruction::BITWISE_OR,
-                                _ => panic!(),
-                            },
-                            arg_0: lhs,
-                            arg_1: rhs,
-                            arg_2: register,
-                        },
-                        pos.line.try_into().unwrap(),
-                    );
-                    return register;
-                }
-            } else if let Statement::LITERAL_NUM(i1) = &e1.statement {
-                // store the number in register 0
-
-                let rhs_register = self.visit(annotation_context.clone(), &e2);
-                let register: u8;
-
-                let embeddable = self.parse_embedding_instruction_number(&i1.typ);
-                if embeddable.is_some() {
-                    register = alloc_slot!(self);
-                    self.push_instruction(
-                        Instruction {
-                            op_instruction: OpInstruction::ADDI,
-                            arg_0: 0,
-                            arg_1: embeddable.unwrap(),
-                            arg_2: register,
-                        },
                         pos.line as usize,
                     );
-                } else {
-                    register = self.visit(annotation_context.clone(), &e1);
+                    return register;
                 }
+            }
+        }
 
-                self.push_instruction(
-                    Instruction {
-                        op_instruction: match op {
-                            Op::ADD => OpInstruction::ADD,
-                            Op::EQ => OpInstruction::EQUAL,
-                            Op::NEQ => OpInstruction::NOT_EQUALS,
-                            Op::GT => OpInstruction::GREATER_THAN,
-                            Op::GE => OpInstruction::GREATER_EQUAL,
-                            Op::LT => OpInstruction::LESS_THAN,
-                            Op::LE => OpInstruction::LESS_EQUAL,
-                            Op::LOGICAL_OR => OpInstruction::LOGICAL_OR,
-                            Op::MUL => OpInstruction::MUL,
-                            Op::BITWISE_OR => OpInstruction::BITWISE_OR,
-                            _ => panic!(),
-                        },
-                        arg_0: register,
-                        arg_1: rhs_register,
-                        arg_2: register,
-                    },
-                    pos.line as usize,
-                );
-
-                return register;
-            } else if let Statement::VARIABLE(v1) = &e1.statement {
-                // dealing with an identifier here so load it and perform add
-
-                let register = alloc_slot!(self);
-                let variable_register =
-                    self.get_variable(annotation_context.clone(), pos.clone(), v1);
+        let lhs = self.visit(annotation_context.clone(), e1);
+        let rhs = self.visit(annotation_context.clone(), e2);
+        let register = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: match op {
+                    Op::ADD => OpInstruction::ADD,
+                    Op::SUB => OpInstruction::SUB,
+                    Op::MUL => OpInstruction::MUL,
+                    Op::DIV => OpInstruction::DIV,
+                    Op::MOD => OpInstruction::MOD,
+                    Op::POW => OpInstruction::POW,
+                    Op::EQ => OpInstruction::EQUAL,
+                    Op::NEQ => OpInstruction::NOT_EQUALS,
+                    Op::GT => OpInstruction::GREATER_THAN,
+                    Op::GE => OpInstruction::GREATER_EQUAL,
+                    Op::LT => OpInstruction::LESS_THAN,
+                    Op::LE => OpInstruction::LESS_EQUAL,
+                    Op::BITWISE_OR => OpInstruction::BITWISE_OR,
+                    Op::BITWISE_AND => OpInstruction::BITWISE_AND,
+                    Op::BITWISE_XOR => OpInstruction::BITWISE_XOR,
+                    Op::SHIFT_LEFT => OpInstruction::SHIFT_LEFT,
+                    Op::SHIFT_RIGHT => OpInstruction::SHIFT_RIGHT,
+                    Op::IN => OpInstruction::IN,
+                    Op::LOGICAL_OR | Op::LOGICAL_AND => panic!("failing bin op {:?}", op),
+                },
+                arg_0: lhs,
+                arg_1: rhs,
+                arg_2: register,
+            },
+            pos.line as usize,
+        );
+        return register;
+    }
 
-                let rhs_register = self.visit(annotation_context.clone(), e2);
+    // the rhs is only evaluated if the lhs doesn't decide the result:
+    //
+    //     LOGICAL_OR   lhs lhs dest
+    //     IF_JMP_TRUE  dest end
+    //     ...rhs...
+    //     LOGICAL_OR   lhs rhs dest
+    // end:
+    fn gen_logical_op(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        e1: &Box<ASTNode>,
+        e2: &Box<ASTNode>,
+        op: &Op,
+    ) -> u8 {
+        let (op_instruction, jump_instruction) = match op {
+            Op::LOGICAL_AND => (OpInstruction::LOGICAL_AND, OpInstruction::IF_JMP_FALSE),
+            _ => (OpInstruction::LOGICAL_OR, OpInstruction::IF_JMP_TRUE),
+        };
 
-                self.push_instruction(
-                    Instruction {
-                        op_instruction: match op {
-                            Op::ADD => OpInstruction::ADD,
-                            Op::EQ => OpInstruction::EQUAL,
-                            Op::NEQ => OpInstruction::NOT_EQUALS,
-                            Op::GT => OpInstruction::GREATER_THAN,
-                            Op::GE => OpInstruction::GREATER_EQUAL,
-                            Op::LT => OpInstruction::LESS_THAN,
-                            Op::LE => OpInstruction::LESS_EQUAL,
-                            Op::LOGICAL_OR => OpInstruction::LOGICAL_OR,
-                            Op::MUL => OpInstruction::MUL,
-                            Op::BITWISE_OR => OpInstruction::BITWISE_OR,
-                            _ => panic!(),
-                        },
-                        arg_0: variable_register,
-                        arg_1: rhs_register,
-                        arg_2: register,
-                    },
-                    pos.line as usize,
-                );
+        let lhs = self.visit(annotation_context.clone(), e1);
+        let register = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: op_instruction.clone(),
+                arg_0: lhs,
+                arg_1: lhs,
+                arg_2: register,
+            },
+            pos.line as usize,
+        );
+        let jump_ip = current_ip!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: jump_instruction,
+                arg_0: register,
+                arg_1: 0,
+                arg_2: 0,
+            },
+            pos.line as usize,
+        );
 
-                return register;
-            } else {
-                let lhs_register = self.visit(annotation_context.clone(), &e1);
-                let rhs_register = self.visit(annotation_context.clone(), &e2);
+        let rhs = self.visit(annotation_context.clone(), e2);
+        self.push_instruction(
+            Instruction {
+                op_instruction: op_instruction,
+                arg_0: lhs,
+                arg_1: rhs,
+                arg_2: register,
+            },
+            pos.line as usize,
+        );
+        let ip_at_end = current_ip!(self);
+        let target = self.jump_target(ip_at_end, &pos);
+        set_arg_value_at_loc!(self, jump_ip, arg_1, target);
 
-                let register = alloc_slot!(self);
-                self.push_instruction(
-                    Instruction {
-                        op_instruction: match op {
-                            Op::ADD => OpInstruction::ADD,
-                            Op::EQ => OpInstruction::EQUAL,
-                            Op::NEQ => OpInstruction::NOT_EQUALS,
-                            Op::GT => OpInstruction::GREATER_THAN,
-                            Op::GE => OpInstruction::GREATER_EQUAL,
-                            Op::LT => OpInstruction::LESS_THAN,
-                            Op::LE => OpInstruction::LESS_EQUAL,
-                            Op::LOGICAL_OR => OpInstruction::LOGICAL_OR,
-                            Op::MUL => OpInstruction::MUL,
-                            Op::BITWISE_OR => OpInstruction::BITWISE_OR,
-                            _ => panic!(),
-                        },
-                        arg_0: lhs_register,
-                        arg_1: rhs_register,
-                        arg_2: register,
-                    },
-                    pos.line as usize,
-                );
+        return register;
+    }
 
-                return register;
-            }
-        } else {
-            let lhs = self.visit(annotation_context.clone(), e1);
-            let rhs = self.visit(annotation_context.clone(), e2);
-            let register = alloc_slot!(self);
-            self.push_instruction(
-                Instruction {
-                    op_instruction: match op {
-                        Op::ADD => OpInstruction::ADD,
-                        Op::EQ => OpInstruction::EQUAL,
-                        Op::NEQ => OpInstruction::NOT_EQUALS,
-                        Op::GT => OpInstruction::GREATER_THAN,
-                        Op::GE => OpInstruction::GREATER_EQUAL,
-                        Op::LT => OpInstruction::LESS_THAN,
-                        Op::LE => OpInstruction::LESS_EQUAL,
-                        Op::LOGICAL_OR => OpInstruction::LOGICAL_OR,
-                        Op::MUL => OpInstruction::MUL,
-                        Op::BITWISE_OR => OpInstruction::BITWISE_OR,
-                        _ => panic!(),
-                    },
-                    arg_0: lhs,
-                    arg_1: rhs,
-                    arg_2: register,
+    fn gen_unary_op(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        op: &UnaryOp,
+        e: &Box<ASTNode>,
+    ) -> u8 {
+        let value = self.visit(annotation_context.clone(), e);
+        let register = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: match op {
+                    UnaryOp::NEG => OpInstruction::NEG,
+                    UnaryOp::NOT => OpInstruction::NOT,
                 },
-                pos.line.try_into().unwrap(),
-            );
-            return register;
-        }
-
-        panic!("failing bin op {:?}", op);
+                arg_0: value,
+                arg_1: register,
+                arg_2: 0,
+            },
+            pos.line as usize,
+        );
+        return register;
     }
 
     fn create_function(
         &mut self,
         annotation_context: AnnotationContext,
         position: &Position,
-        name: Rc<String>,
+        name: Option<Rc<String>>,
         body: &ASTNode,
         params: &Vec<ASTNode>,
         return_type: &Option<DataType>,
@@ -1978,14 +3090,10 @@ impl BytecodeGenerator {
                 if let Type::IDENTIFIER(i) = &t.typ {
                     if i.to_string().eq("self") {
                         let t = typ.clone().unwrap();
-                        if let DataType::NAMED_REFERENCE(d) = t {
+                        if let DataType::NAMED_REFERENCE(d) | DataType::INSTANCE(d, _) = t {
                             is_method = true;
                             // todo add this function as a method
-                            method_obj = *self.codegen_context.chunks
-                                [self.codegen_context.current_chunk_pointer]
-                                .variable_map
-                                .get(&d)
-                                .unwrap();
+                            method_obj = self.load_variable(position, &d).unwrap();
                         }
                     }
                 }
@@ -2012,54 +3120,205 @@ impl BytecodeGenerator {
             position.line.try_into().unwrap(),
         );
 
-        self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
-            .variable_map
-            .insert(name.clone(), location);
+        // a function captured by a nested function (i.e. to recurse) lives in a cell
+        let mut cell: Option<u8> = None;
+        if let Some(name) = &name {
+            let chunk = &self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+            if chunk.cells.contains(name) {
+                cell = chunk.variable_map.get(name).copied();
+            } else {
+                self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                    .variable_map
+                    .insert(name.clone(), location);
+            }
+        }
+        // default values are evaluated once, here where the function is defined
+        let mut default_registers: Vec<u8> = vec![];
+        for param in params {
+            if let Statement::DEFINE(_, _, Some(default)) = &param.statement {
+                default_registers.push(self.visit(annotation_context.clone(), default));
+            }
+        }
+        let new_default_registers = find_contiguous_slots!(self, &default_registers);
+        for i in 0..default_registers.len() {
+            if default_registers[i] != new_default_registers[i] {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: default_registers[i],
+                        arg_1: new_default_registers[i],
+                        arg_2: 0,
+                    },
+                    position.line as usize,
+                );
+            }
+        }
+
         // after loading the function const, we the build it (this is binding self etc)
         self.push_instruction(
             Instruction {
                 op_instruction: OpInstruction::BUILD_FN,
                 arg_0: location,
-                arg_1: 0,
-                arg_2: 0,
+                arg_1: new_default_registers.first().copied().unwrap_or(0),
+                arg_2: new_default_registers.len() as u8,
             },
             position.line as usize,
         );
+        for reg in default_registers {
+            free_slot!(self, reg);
+        }
+        for reg in new_default_registers {
+            free_slot!(self, reg);
+        }
+        if let Some(cell) = cell {
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::STORE_CELL,
+                    arg_0: cell,
+                    arg_1: location,
+                    arg_2: 0,
+                },
+                position.line as usize,
+            );
+        }
 
         self.push_chunk();
 
         // setup locals
         let mut param_slots: Vec<u8> = vec![];
+        let mut param_names: Vec<Option<String>> = vec![];
         for param in params {
+            // the rest parameter is just a local holding a slice
+            let param = match &param.statement {
+                Statement::SPREAD(rest) => rest.as_ref(),
+                _ => param,
+            };
             if let Statement::DEFINE(v, _, _) = &param.statement {
-                let loc = alloc_slot!(self);
+                // perm so the temporaries in the body can't reuse it
+                let loc = alloc_perm_slot!(self);
                 // todo what happened here
                 self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
                     .variable_map
                     .insert(v.as_identifier(), loc);
                 param_slots.push(loc);
+                param_names.push(Some(v.as_identifier().to_string()));
+            } else if let Statement::DESTRUCTURE(_, None) = &param.statement {
+                // the argument goes in a slot of its own and is taken apart below
+                param_slots.push(alloc_slot!(self));
+                // and can only be passed positionally
+                param_names.push(None);
             } else {
                 panic!();
             }
         }
 
-        // todo enter new block?
-        self.visit(annotation_context, body);
+        self.setup_cells(position, params, body);
+
+        for (param, slot) in params.iter().zip(param_slots.clone()) {
+            if let Statement::DESTRUCTURE(pattern, None) = &param.statement {
+                self.bind_pattern(
+                    annotation_context.clone(),
+                    &param.position,
+                    pattern,
+                    slot,
+                    true,
+                );
+            }
+        }
+
+        // the body shares the function's scope with its parameters, and the blocks of the function
+        // it's in aren't visible as scopes from here
+        let outer_scopes = std::mem::take(&mut self.scopes);
+        let result = match &body.statement {
+            Statement::BLOCK(b) => self.gen_statements(annotation_context, b),
+            _ => self.visit(annotation_context, body),
+        };
+        self.scopes = outer_scopes;
+
+        // lambdas return their body, and a function which reaches the end of its body returns
+        // without a value rather than running off the end of its chunk
+        let has_value = name.is_none() && !matches!(body.statement, Statement::BLOCK(_));
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::RETURN,
+                arg_0: result,
+                arg_1: has_value as u8,
+                arg_2: 0,
+            },
+            position.line as usize,
+        );
 
         let c = self.pop_chunk();
 
         self.codegen_context.chunks[self.codegen_context.current_chunk_pointer].gc_ref_data
             [gc_ref_data_idx as usize] = GCRefData::FN(FnObject {
             chunk: c,
-            name: name.clone().to_string(),
+            name: name.map_or("lambda".to_string(), |n| n.to_string()),
             requires_method_binding: is_method,
             method_to_object: Some(method_obj),
             param_slots: param_slots,
+            param_names,
+            defaults: vec![],
+            variadic: params
+                .last()
+                .map_or(false, |p| matches!(p.statement, Statement::SPREAD(_))),
             bounded_object: None,
             constants_initialised: false,
+            upvalues: vec![],
+            annotations: None,
         });
-        // todo do we need this???
-        alloc_slot!(self)
+        location
+    }
+
+    // every local which a nested function refers to is boxed in a cell up front, so the
+    // enclosing function and all of its closures share the same value
+    fn setup_cells(&mut self, position: &Position, params: &Vec<ASTNode>, body: &ASTNode) {
+        let mut locals: HashSet<Rc<String>> = HashSet::new();
+        for param in params {
+            collect_locals(param, &mut locals);
+        }
+        collect_locals(body, &mut locals);
+
+        let mut captured: HashSet<Rc<String>> = HashSet::new();
+        collect_captures(body, &mut captured);
+
+        let mut cells: Vec<Rc<String>> = locals.intersection(&captured).cloned().collect();
+        cells.sort();
+        for name in cells {
+            // the repl and tests compile more code into the same chunk, which may have made it already
+            if self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                .cells
+                .contains(&name)
+            {
+                continue;
+            }
+            let existing = self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                .variable_map
+                .get(&name)
+                .copied();
+            let reg = match existing {
+                Some(reg) => reg,
+                None => {
+                    let reg = alloc_perm_slot!(self);
+                    self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                        .variable_map
+                        .insert(name.clone(), reg);
+                    reg
+                }
+            };
+            self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                .cells
+                .insert(name);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::NEW_CELL,
+                    arg_0: reg,
+                    arg_1: 0,
+                    arg_2: 0,
+                },
+                position.line as usize,
+            );
+        }
     }
 
     fn gen_named_function(
@@ -2073,13 +3332,24 @@ impl BytecodeGenerator {
         self.create_function(
             annotation_context,
             &token.pos,
-            token.as_identifier(),
+            Some(token.as_identifier()),
             statement,
             params,
             return_type,
         )
     }
 
+    fn gen_lambda(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        params: &Vec<ASTNode>,
+        return_type: &Option<DataType>,
+        body: &ASTNode,
+    ) -> u8 {
+        self.create_function(annotation_context, &pos, None, body, params, return_type)
+    }
+
     fn atom_from_type(&self, data_type: DataType) -> Object {
         match data_type {
             // todo use object "types" rather than atoms
@@ -2105,12 +3375,14 @@ impl BytecodeGenerator {
     ) -> u8 {
         // FIXME
         let mut field_definitions: HashMap<String, Object> = HashMap::new();
+        let mut field_names: Vec<String> = vec![];
 
         for decl in decls {
             if let Statement::DEFINE(token, typ, val) = &decl.statement {
                 if let Type::IDENTIFIER(i) = &token.typ {
                     field_definitions
                         .insert(i.to_string(), self.atom_from_type(typ.clone().unwrap()));
+                    field_names.push(i.to_string());
                     continue;
                 }
             }
@@ -2119,6 +3391,12 @@ impl BytecodeGenerator {
 
         let gc_ref_data_index = self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
             fields: field_definitions,
+            tag: Some(Rc::new(VariantTag {
+                enum_name: None,
+                name: token.as_identifier(),
+                fields: field_names,
+            })),
+            annotations: None,
         }));
         let index = self.push_constant(Object::GC_REF(GCRef {
             index: gc_ref_data_index as usize,
@@ -2135,13 +3413,143 @@ impl BytecodeGenerator {
             },
             token.pos.line.try_into().unwrap(),
         );
-        self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
-            .variable_map
-            .insert(token.as_identifier(), reg);
+        self.bind_declaration(token.as_identifier(), reg, token.pos.line as usize);
         // this is TERRIBLE, we need to somehow reference constants as variables
         reg
     }
 
+    // a declaration which a function refers to goes in the cell made for it up front, otherwise its
+    // name just refers to the register it was loaded into
+    fn bind_declaration(&mut self, name: Rc<String>, location: u8, line: usize) {
+        let chunk = &self.codegen_context.chunks[self.codegen_context.current_chunk_pointer];
+        if !chunk.cells.contains(&name) {
+            self.codegen_context.chunks[self.codegen_context.current_chunk_pointer]
+                .variable_map
+                .insert(name, location);
+            return;
+        }
+        let cell = *chunk.variable_map.get(&name).unwrap();
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::STORE_CELL,
+                arg_0: cell,
+                arg_1: location,
+                arg_2: 0,
+            },
+            line,
+        );
+    }
+
+    fn gen_named_enum(
+        &mut self,
+        annotation_context: AnnotationContext,
+        token: &Token,
+        variants: &Vec<(Token, Vec<ASTNode>)>,
+    ) -> u8 {
+        // the enum is a type holding each of its variants, constants can't hold references to other
+        // constants so the variants are set on it when it's loaded
+        let gc_ref_data_index = self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
+            fields: HashMap::new(),
+            tag: None,
+            annotations: None,
+        }));
+        let index = self.push_constant(Object::GC_REF(GCRef {
+            index: gc_ref_data_index as usize,
+            marked: false,
+        }));
+        let reg = alloc_perm_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::LOAD_CONST,
+                arg_0: index,
+                arg_1: reg,
+                arg_2: 0,
+            },
+            token.pos.line.try_into().unwrap(),
+        );
+        self.bind_declaration(token.as_identifier(), reg, token.pos.line as usize);
+
+        let is_prelude_result =
+            *token.as_identifier() == "Result" && self.codegen_context.prelude_result.is_empty();
+        for (variant, decls) in variants {
+            let mut field_definitions: HashMap<String, Object> = HashMap::new();
+            let mut field_names: Vec<String> = vec![];
+            for decl in decls {
+                if let Statement::DEFINE(field, typ, _) = &decl.statement {
+                    field_definitions.insert(
+                        field.as_identifier().to_string(),
+                        self.atom_from_type(typ.clone().unwrap()),
+                    );
+                    field_names.push(field.as_identifier().to_string());
+                    continue;
+                }
+                panic!();
+            }
+
+            let tag = Rc::new(VariantTag {
+                enum_name: Some(token.as_identifier()),
+                name: variant.as_identifier(),
+                fields: field_names,
+            });
+            if is_prelude_result {
+                self.codegen_context
+                    .prelude_result
+                    .insert(variant.as_identifier().to_string(), tag.clone());
+            }
+
+            // a variant with fields is a constructor for its values, one without is the value
+            let variant_data_index =
+                self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
+                    fields: field_definitions,
+                    tag: Some(tag),
+                    annotations: None,
+                }));
+            let variant_index = self.push_constant(Object::GC_REF(GCRef {
+                index: variant_data_index as usize,
+                marked: false,
+            }));
+            let variant_reg = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::LOAD_CONST,
+                    arg_0: variant_index,
+                    arg_1: variant_reg,
+                    arg_2: 0,
+                },
+                variant.pos.line.try_into().unwrap(),
+            );
+            if decls.is_empty() {
+                let prototype_reg =
+                    self.create_constant_string("__prototype__".to_string(), &variant.pos);
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::STRUCT_SET,
+                        arg_0: variant_reg,
+                        arg_1: prototype_reg,
+                        arg_2: reg,
+                    },
+                    variant.pos.line.try_into().unwrap(),
+                );
+                free_slot!(self, prototype_reg);
+            }
+
+            let name_reg =
+                self.create_constant_string(variant.as_identifier().to_string(), &variant.pos);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::STRUCT_SET,
+                    arg_0: reg,
+                    arg_1: name_reg,
+                    arg_2: variant_reg,
+                },
+                variant.pos.line.try_into().unwrap(),
+            );
+            free_slot!(self, name_reg);
+            free_slot!(self, variant_reg);
+        }
+        reg
+    }
+
     fn gen_slice(
         &mut self,
         annotation_context: AnnotationContext,
@@ -2194,12 +3602,68 @@ impl BytecodeGenerator {
         dest
     }
 
+    fn gen_dict(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: Position,
+        entries: &Vec<(ASTNode, ASTNode)>,
+    ) -> u8 {
+        let mut registers: Vec<u8> = vec![];
+        for (key, value) in entries {
+            registers.push(self.visit(annotation_context.clone(), key));
+            registers.push(self.visit(annotation_context.clone(), value));
+        }
+
+        let new_arg_registers = find_contiguous_slots!(self, &registers);
+        for i in 0..registers.len() {
+            let current_arg_reg = registers[i];
+            let new_reg = new_arg_registers[i];
+
+            if current_arg_reg != new_reg {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: current_arg_reg,
+                        arg_1: new_reg,
+                        arg_2: 0,
+                    },
+                    pos.line as usize,
+                );
+            }
+        }
+
+        let dest = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::BUILD_MAP,
+                // an empty map doesn't read any registers
+                arg_0: new_arg_registers.first().copied().unwrap_or(0),
+                arg_1: entries.len() as u8,
+                arg_2: dest,
+            },
+            pos.line as usize,
+        );
+
+        for reg in registers {
+            free_slot!(self, reg);
+        }
+        for reg in new_arg_registers {
+            free_slot!(self, reg);
+        }
+
+        dest
+    }
+
     fn gen_index(
         &mut self,
         annotation_context: AnnotationContext,
         obj: &Box<ASTNode>,
         index: &Box<ASTNode>,
     ) -> u8 {
+        if let Statement::RANGE(start, end) = &index.statement {
+            return self.gen_index_range(annotation_context, obj, &index.position, start, end);
+        }
+
         let dest = alloc_slot!(self);
 
         let obj_reg = self.visit(annotation_context.clone(), &obj);
@@ -2213,7 +3677,56 @@ impl BytecodeGenerator {
             },
             obj.position.line as usize,
         );
+        self.mark_position(&index.position);
+
+        dest
+    }
+
+    fn gen_index_range(
+        &mut self,
+        annotation_context: AnnotationContext,
+        obj: &Box<ASTNode>,
+        position: &Position,
+        start: &Box<ASTNode>,
+        end: &Box<ASTNode>,
+    ) -> u8 {
+        let obj_reg = self.visit(annotation_context.clone(), &obj);
+        let registers = vec![
+            self.visit(annotation_context.clone(), &start),
+            self.visit(annotation_context.clone(), &end),
+        ];
+        let new_registers = find_contiguous_slots!(self, &registers);
+        for (current_reg, new_reg) in registers.iter().zip(&new_registers) {
+            if current_reg != new_reg {
+                self.push_instruction(
+                    Instruction {
+                        op_instruction: OpInstruction::MOV,
+                        arg_0: *current_reg,
+                        arg_1: *new_reg,
+                        arg_2: 0,
+                    },
+                    obj.position.line as usize,
+                );
+            }
+        }
 
+        let dest = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::INDEX_RANGE,
+                arg_0: obj_reg,
+                arg_1: new_registers[0],
+                arg_2: dest,
+            },
+            obj.position.line as usize,
+        );
+        self.mark_position(position);
+        for reg in registers {
+            free_slot!(self, reg);
+        }
+        for reg in new_registers {
+            free_slot!(self, reg);
+        }
         dest
     }
 
@@ -2221,26 +3734,143 @@ impl BytecodeGenerator {
         &mut self,
         mut annotation_context: AnnotationContext,
         annotation: &Token,
-        args: &Vec<Token>,
+        args: &Vec<ASTNode>,
         expr: &Box<ASTNode>,
     ) -> u8 {
-        if let Type::IDENTIFIER(i) = &annotation.typ {
-            match i.as_str() {
-                "native_call" => {
-                    annotation_context.annotations.push(Annotation::NATIVE_CALL);
+        let declaration = expr.declaration();
+        let is_function = matches!(
+            declaration.statement,
+            Statement::NAMED_FUNCTION(..) | Statement::LAMBDA(..)
+        );
+        let is_type = matches!(
+            declaration.statement,
+            Statement::NAMED_TYPE_DECL(..) | Statement::NAMED_ENUM_DECL(..)
+        );
+        match annotation.as_identifier().as_str() {
+            "native_call" => {
+                annotation_context.annotations.push(Annotation::NATIVE_CALL);
+            }
+            "dll_call" => {
+                let dll = args.first().and_then(|arg| match &arg.statement {
+                    Statement::VARIABLE(v) => Some(v.as_identifier()),
+                    Statement::STRING(s) => Some(s.as_string()),
+                    _ => None,
+                });
+                if dll.is_none() {
+                    return self.invalid_annotation(annotation, "it takes the name of the dll");
+                }
+                annotation_context
+                    .annotations
+                    .push(Annotation::DLL_CALL(dll.unwrap().to_string()));
+            }
+            "deprecated" => {
+                // the analyser warns about the uses, so there's nothing to generate
+                let message_only = args.len() <= 1
+                    && args
+                        .iter()
+                        .all(|arg| matches!(arg.statement, Statement::STRING(_)));
+                if !message_only {
+                    return self.invalid_annotation(annotation, "it only takes a message string");
+                }
+                if !is_function && !is_type {
+                    return self.invalid_annotation(
+                        annotation,
+                        "only functions and types can be deprecated",
+                    );
+                }
+            }
+            "inline" => {
+                // only a hint, there's no optimiser to act on it yet so it's kept as metadata
+                if !args.is_empty() {
+                    return self.invalid_annotation(annotation, "it doesn't take any arguments");
+                }
+                if !is_function {
+                    return self.invalid_annotation(annotation, "only functions can be inlined");
+                }
+                return self.gen_metadata_annotation(annotation_context, annotation, args, expr);
+            }
+            "test_only" => {
+                if !args.is_empty() {
+                    return self.invalid_annotation(annotation, "it doesn't take any arguments");
                 }
-                "dll_call" => {
-                    if let Type::IDENTIFIER(i) = &args[0].typ {
-                        annotation_context
-                            .annotations
-                            .push(Annotation::DLL_CALL(i.to_string()));
+                if !self.test_build {
+                    if let Some(name) = declaration.declared_name() {
+                        self.test_only.insert(name);
                     }
+                    return alloc_slot!(self);
                 }
-                _ => panic!("unknown annotation {:?}", i),
             }
-            return self.visit(annotation_context, &expr);
+            _ => return self.gen_metadata_annotation(annotation_context, annotation, args, expr),
         }
-        panic!()
+        return self.visit(annotation_context, &expr);
+    }
+
+    // an annotation the compiler doesn't know about is kept on the function or type it's on, so it
+    // can be looked up at runtime with annotations(x)
+    fn gen_metadata_annotation(
+        &mut self,
+        annotation_context: AnnotationContext,
+        annotation: &Token,
+        args: &Vec<ASTNode>,
+        expr: &Box<ASTNode>,
+    ) -> u8 {
+        let annotatable = matches!(
+            expr.declaration().statement,
+            Statement::NAMED_FUNCTION(..)
+                | Statement::LAMBDA(..)
+                | Statement::NAMED_TYPE_DECL(..)
+                | Statement::NAMED_ENUM_DECL(..)
+        );
+        if !annotatable {
+            return self
+                .invalid_annotation(annotation, "only functions and types can be annotated");
+        }
+        if let Some(arg) = args.iter().find(|arg| !is_constant(arg)) {
+            self.errors
+                .push(CodegenError::NON_CONSTANT_ANNOTATION_ARGUMENT(
+                    arg.position.clone(),
+                ));
+            return alloc_slot!(self);
+        }
+
+        let target = self.visit(annotation_context.clone(), &expr);
+        let name =
+            self.create_constant_string(annotation.as_identifier().to_string(), &annotation.pos);
+        let args_tuple = if args.is_empty() {
+            let dest = alloc_slot!(self);
+            self.push_instruction(
+                Instruction {
+                    op_instruction: OpInstruction::BUILD_TUPLE,
+                    arg_0: 0,
+                    arg_1: 0,
+                    arg_2: dest,
+                },
+                annotation.pos.line as usize,
+            );
+            dest
+        } else {
+            self.gen_tuple(annotation_context, &annotation.pos, args)
+        };
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::ANNOTATE,
+                arg_0: target,
+                arg_1: name,
+                arg_2: args_tuple,
+            },
+            annotation.pos.line as usize,
+        );
+        free_slot!(self, name);
+        free_slot!(self, args_tuple);
+        target
+    }
+
+    fn invalid_annotation(&mut self, annotation: &Token, reason: &str) -> u8 {
+        self.errors.push(CodegenError::INVALID_ANNOTATION(
+            annotation.clone(),
+            reason.to_string(),
+        ));
+        alloc_slot!(self)
     }
 
     fn gen_return(
@@ -2304,49 +3934,123 @@ impl BytecodeGenerator {
         panic!();
     }
 
-    fn gen_import(&mut self, mut annotation_context: AnnotationContext, path: &Vec<Token>) -> u8 {
-        let t = &path[0];
-        let mut strings: Vec<String> = vec![];
-        for p in path {
-            strings.push(p.as_identifier().to_string());
-        }
-        let path_string = strings.join(".");
-        if let Type::IDENTIFIER(i) = &t.typ {
-            let s = self.create_constant_string(path_string.to_string(), &t.pos);
-            let destination = alloc_slot!(self);
+    fn gen_import(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        path: &Vec<Token>,
+        alias: &Option<Token>,
+    ) -> u8 {
+        let module = self.gen_load_module(path);
+        if let Some(alias) = alias {
+            return self.define_variable(annotation_context, pos, alias, module);
+        }
+        return module;
+    }
+
+    fn gen_from_import(
+        &mut self,
+        annotation_context: AnnotationContext,
+        pos: &Position,
+        path: &Vec<Token>,
+        names: &Vec<(Token, Option<Token>)>,
+    ) -> u8 {
+        let module = self.gen_load_module(path);
+        for (name, alias) in names {
+            let field = self.create_constant_string(name.as_identifier().to_string(), &name.pos);
+            let value = alloc_slot!(self);
             self.push_instruction(
                 Instruction {
-                    op_instruction: OpInstruction::IMPORT,
-                    arg_0: s,
-                    arg_1: destination,
-                    arg_2: 0,
+                    op_instruction: OpInstruction::STRUCT_ACCESS,
+                    arg_0: module,
+                    arg_1: field,
+                    arg_2: value,
                 },
-                t.pos.line as usize,
+                name.pos.line as usize,
             );
-            return destination;
+            let var = alias.as_ref().unwrap_or(name);
+            let var_location = self.define_variable(annotation_context.clone(), pos, var, value);
+            if var_location != value {
+                free_slot!(self, value);
+            }
         }
-        panic!()
+        return module;
+    }
+
+    fn gen_load_module(&mut self, path: &Vec<Token>) -> u8 {
+        let t = &path[0];
+        let path_string = path
+            .iter()
+            .map(|p| p.as_identifier().to_string())
+            .collect::<Vec<String>>()
+            .join(".");
+        let s = self.create_constant_string(path_string, &t.pos);
+        let destination = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::IMPORT,
+                arg_0: s,
+                arg_1: destination,
+                arg_2: 0,
+            },
+            t.pos.line as usize,
+        );
+        return destination;
     }
 
     fn gen_try(&mut self, mut annotation_context: AnnotationContext, rhs: &ASTNode) -> u8 {
         let rhs_reg = self.visit(annotation_context, rhs);
 
+        // only the prelude's Error is propagated, so it's checked against that variant
+        let error_gc_ref = self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
+            fields: HashMap::new(),
+            tag: self.codegen_context.prelude_result.get("Error").cloned(),
+            annotations: None,
+        }));
+        let constant_idx = self.push_constant(Object::GC_REF(GCRef {
+            index: error_gc_ref as usize,
+            marked: false,
+        }));
+        let error_reg = alloc_slot!(self);
+        self.push_instruction(
+            Instruction {
+                op_instruction: OpInstruction::LOAD_CONST,
+                arg_0: constant_idx,
+                arg_1: error_reg,
+                arg_2: 0,
+            },
+            rhs.position.line.try_into().unwrap(),
+        );
+
         let dest = alloc_slot!(self);
         self.push_instruction(
             Instruction {
                 op_instruction: OpInstruction::TRY,
                 arg_0: rhs_reg,
                 arg_1: dest,
-                arg_2: 0,
+                arg_2: error_reg,
             },
             rhs.position.line.try_into().unwrap(),
         );
 
         free_slot!(self, rhs_reg);
+        free_slot!(self, error_reg);
 
         dest
     }
 
+    // the prelude's variant if it's been declared so `try` treats the result as it would any other
+    fn result_tag(&self, name: &str) -> Rc<VariantTag> {
+        match self.codegen_context.prelude_result.get(name) {
+            Some(tag) => tag.clone(),
+            None => Rc::new(VariantTag {
+                enum_name: Some(Rc::new("Result".to_string())),
+                name: Rc::new(name.to_string()),
+                fields: vec![],
+            }),
+        }
+    }
+
     fn gen_assert(
         &mut self,
         mut annotation_context: AnnotationContext,
@@ -2371,11 +4075,13 @@ impl BytecodeGenerator {
         );
 
         // setup the error value
-        let mut fields = HashMap::new();
         // todo put the prototype and error object in here
-        fields.insert("Error".to_string(), Object::I64(123));
         let result_object_gc_ref =
-            self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject { fields }));
+            self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
+                fields: HashMap::new(),
+                tag: Some(self.result_tag("Error")),
+                annotations: None,
+            }));
         let obj = Object::GC_REF(GCRef {
             index: result_object_gc_ref as usize,
             marked: false,
@@ -2406,16 +4112,19 @@ impl BytecodeGenerator {
             .instructions
             .len();
 
+        let target = self.jump_target(current_instr_index, &expr.position);
         self.codegen_context.chunks[self.codegen_context.current_chunk_pointer].instructions
             [jmp_instr_index]
-            .arg_1 = current_instr_index as u8;
+            .arg_1 = target;
 
         // setup the ok value
-        let mut fields = HashMap::new();
         // todo put the prototype and error object in here
-        fields.insert("Data".to_string(), Object::I64(123));
         let result_object_gc_ref =
-            self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject { fields }));
+            self.push_gc_ref_data(GCRefData::DYNAMIC_OBJECT(DynamicObject {
+                fields: HashMap::new(),
+                tag: Some(self.result_tag("Data")),
+                annotations: None,
+            }));
         let obj = Object::GC_REF(GCRef {
             index: result_object_gc_ref as usize,
             marked: false,
@@ -2444,3 +4153,98 @@ impl BytecodeGenerator {
         result_reg
     }
 }
+
+// the names a function binds itself, without looking inside the functions nested in it
+fn collect_locals(node: &ASTNode, locals: &mut HashSet<Rc<String>>) {
+    match &node.statement {
+        Statement::DEFINE(t, _, _) => {
+            locals.insert(t.as_identifier());
+        }
+        Statement::DESTRUCTURE(pattern, _)
+        | Statement::FOR(pattern, _, _)
+        | Statement::MATCH_CASE(pattern, _, _) => {
+            locals.extend(pattern.names().iter().map(|t| t.as_identifier()));
+        }
+        Statement::IMPORT(_, Some(alias)) => {
+            locals.insert(alias.as_identifier());
+        }
+        Statement::FROM_IMPORT(_, names) => {
+            locals.extend(
+                names
+                    .iter()
+                    .map(|(name, alias)| alias.as_ref().unwrap_or(name).as_identifier()),
+            );
+        }
+        Statement::NAMED_FUNCTION(t, _, _, _)
+        | Statement::NAMED_TYPE_DECL(t, _)
+        | Statement::NAMED_ENUM_DECL(t, _) => {
+            locals.insert(t.as_identifier());
+            return;
+        }
+        Statement::LAMBDA(_, _, _) => return,
+        _ => {}
+    }
+    for child in node.children() {
+        collect_locals(child, locals);
+    }
+}
+
+// the names used by the functions nested in this node
+fn collect_captures(node: &ASTNode, captured: &mut HashSet<Rc<String>>) {
+    match &node.statement {
+        Statement::NAMED_FUNCTION(_, _, _, _) | Statement::LAMBDA(_, _, _) => {
+            collect_names(node, captured);
+            return;
+        }
+        _ => {}
+    }
+    for child in node.children() {
+        collect_captures(child, captured);
+    }
+}
+
+fn collect_names(node: &ASTNode, names: &mut HashSet<Rc<String>>) {
+    match &node.statement {
+        Statement::VARIABLE(t) | Statement::DEFINE(t, _, _) => {
+            names.insert(t.as_identifier());
+        }
+        Statement::DESTRUCTURE(pattern, _)
+        | Statement::DESTRUCTURE_ASSIGN(pattern, _)
+        | Statement::FOR(pattern, _, _)
+        | Statement::MATCH_CASE(pattern, _, _) => {
+            names.extend(pattern.names().iter().map(|t| t.as_identifier()));
+        }
+        Statement::NAMED_FUNCTION(_, params, _, body) | Statement::LAMBDA(params, _, body) => {
+            let mut inner: HashSet<Rc<String>> = HashSet::new();
+            collect_names(body, &mut inner);
+            let mut param_names: HashSet<Rc<String>> = HashSet::new();
+            for param in params {
+                collect_locals(param, &mut param_names);
+            }
+            for name in param_names {
+                inner.remove(&name);
+            }
+            names.extend(inner);
+            return;
+        }
+        _ => {}
+    }
+    for child in node.children() {
+        collect_names(child, names);
+    }
+}
+
+// annotation arguments are built once alongside what they annotate, so they can't refer to anything
+fn is_constant(node: &ASTNode) -> bool {
+    match &node.statement {
+        Statement::LITERAL_NUM(_)
+        | Statement::LITERAL_BOOL(_)
+        | Statement::STRING(_)
+        | Statement::ATOM(_) => true,
+        Statement::UNARY_OP(UnaryOp::NEG, operand) => {
+            matches!(operand.statement, Statement::LITERAL_NUM(_))
+        }
+        Statement::TUPLE(items) | Statement::SLICE(items) => items.iter().all(is_constant),
+        _ => false,
+    }
+}
@@ -1,14 +1,18 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, OpenOptions},
     io::Write,
+    rc::Rc,
     time::{Duration, Instant},
 };
 
 use crate::{
-    codegen::{BytecodeGenerator, Chunk, CodegenContext, CodegenResult, SlotManager},
+    analyse::{Analyser, TypeCheckError, TypeCheckWarning},
+    codegen::{BytecodeGenerator, Chunk, CodegenContext, CodegenError, CodegenResult, SlotManager},
     execution::{ExecutionEngine, ExecutionResult, Heap, ProcessContext, SharedExecutionContext},
-    lex, parse,
+    lex::{self, LexError},
+    module,
+    parse::{self, ParseError},
 };
 
 use crate::config::Config;
@@ -23,9 +27,20 @@ pub enum CompilationUnitStatus {
 pub struct CompilationResult {
     pub codegen_result: CodegenResult,
     pub execution_result: ExecutionResult,
+    // the names other modules can import from this one
+    pub exports: Vec<Rc<String>>,
+    pub warnings: Vec<TypeCheckWarning>,
     pub compilation_time: Duration,
     pub execution_time: Duration,
 }
+#[derive(Clone, Debug)]
+pub enum CompilationError {
+    LEX_ERRORS(Vec<LexError>),
+    PARSE_ERRORS(Vec<ParseError>),
+    TYPECHECK_ERROR(TypeCheckError),
+    CODEGEN_ERRORS(Vec<CodegenError>),
+}
+
 pub struct CompilationContext {
     pub codegen_context: CodegenContext,
     pub process_context: ProcessContext,
@@ -34,17 +49,24 @@ pub struct CompilationContext {
 pub struct CompilerFlags {
     pub init_builtins: bool,
     pub dump_bytecode: bool,
+    // keeps what's marked @test_only
+    pub test_build: bool,
 }
 
+pub type WarningHandler = Box<dyn Fn(&Vec<TypeCheckWarning>)>;
+
 pub struct Compiler {
     // keep track of files and their states
     pub compilation_units: HashMap<String, CompilationUnitStatus>,
+    // given the warnings once the code has compiled, so they're seen before it runs
+    pub on_warnings: Option<WarningHandler>,
 }
 
 impl Compiler {
     pub fn new() -> Self {
         return Compiler {
             compilation_units: HashMap::new(),
+            on_warnings: None,
         };
     }
 
@@ -58,7 +80,7 @@ impl Compiler {
         codegen_context: Option<CodegenContext>,
         process_context: Option<ProcessContext>,
         shared_execution_context: Option<SharedExecutionContext>,
-    ) -> CompilationResult {
+    ) -> Result<CompilationResult, CompilationError> {
         let start = Instant::now();
         let mut codegen_context = if codegen_context.is_some() {
             codegen_context.unwrap()
@@ -68,12 +90,16 @@ impl Compiler {
                 chunks: vec![Chunk {
                     slot_manager: SlotManager::new(),
                     debug_line_info: vec![],
+                    debug_position_info: HashMap::new(),
                     constant_pool: vec![],
                     gc_ref_data: vec![],
                     instructions: vec![],
                     variable_map: HashMap::new(),
                     string_interns: HashMap::new(),
+                    cells: HashSet::new(),
+                    upvalues: vec![],
                 }],
+                prelude_result: HashMap::new(),
             }
         };
         let mut process_context = if process_context.is_some() {
@@ -92,27 +118,60 @@ impl Compiler {
                 heap: Heap {
                     live_slots: HashMap::new(),
                     dead_objects: HashMap::new(),
+                    next_index: 0,
                 },
                 gila_abis_dlls: vec![],
+                modules: HashMap::new(),
+                importing: vec![],
             }
         };
         let mut lexer = lex::Lexer::new();
-        let mut bytecode_generator =
-            BytecodeGenerator::new(config.clone(), codegen_context.clone());
+        let mut bytecode_generator = BytecodeGenerator::new(
+            config.clone(),
+            codegen_context.clone(),
+            compiler_flags.test_build,
+        );
 
         if compiler_flags.init_builtins {
             bytecode_generator.init_builtins();
         }
 
+        let search_path = module::search_path(shared_execution_context.importing.last());
         let mut exec_engine =
             ExecutionEngine::new(config, shared_execution_context, process_context.clone());
         let tokens = lexer.lex(code.clone());
-        let mut parser = parse::Parser {
-            tokens: &tokens,
-            counter: 0,
-        };
-        let ast = parser.parse();
+        if tokens.is_err() {
+            self.compilation_units
+                .insert(compilation_unit.to_string(), CompilationUnitStatus::ERROR);
+            return Err(CompilationError::LEX_ERRORS(tokens.err().unwrap()));
+        }
+        let tokens = tokens.unwrap();
+        let mut parser = parse::Parser::new(&tokens);
+        let parse_result = parser.parse();
+        if parse_result.errors.len() > 0 {
+            self.compilation_units
+                .insert(compilation_unit.to_string(), CompilationUnitStatus::ERROR);
+            return Err(CompilationError::PARSE_ERRORS(parse_result.errors));
+        }
+        let ast = parse_result.ast;
+        let mut analyser = Analyser::new(search_path);
+        let typecheck_result = analyser.analyse(&ast);
+        if typecheck_result.is_err() {
+            self.compilation_units
+                .insert(compilation_unit.to_string(), CompilationUnitStatus::ERROR);
+            return Err(CompilationError::TYPECHECK_ERROR(
+                typecheck_result.err().unwrap(),
+            ));
+        }
         let codegen_result = bytecode_generator.generate(&ast);
+        if codegen_result.is_err() {
+            self.compilation_units
+                .insert(compilation_unit.to_string(), CompilationUnitStatus::ERROR);
+            return Err(CompilationError::CODEGEN_ERRORS(
+                codegen_result.err().unwrap(),
+            ));
+        }
+        let codegen_result = codegen_result.unwrap();
 
         if compiler_flags.dump_bytecode {
             let mut file = OpenOptions::new()
@@ -131,6 +190,9 @@ impl Compiler {
         }
 
         let compilation_elapsed = start.elapsed();
+        if let Some(on_warnings) = &self.on_warnings {
+            on_warnings(&analyser.warnings);
+        }
         let execution_start = Instant::now();
 
         let execution_result = exec_engine.exec(
@@ -150,11 +212,13 @@ impl Compiler {
         };
         let execution_time = execution_start.elapsed();
 
-        return CompilationResult {
+        return Ok(CompilationResult {
             codegen_result: codegen_result,
             execution_result: cloned_exec_result.clone(),
+            exports: module::exported_names(&ast),
+            warnings: analyser.warnings,
             compilation_time: compilation_elapsed,
             execution_time: execution_time,
-        };
+        });
     }
 }
@@ -0,0 +1,449 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    analyse::method_receiver,
+    ast::{ASTNode, Pattern, Statement},
+    lex::{Token, Type},
+    r#type::DataType,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    MARKDOWN,
+    HTML,
+}
+
+impl DocFormat {
+    pub fn from_name(name: &str) -> Option<DocFormat> {
+        return match name {
+            "md" | "markdown" => Some(DocFormat::MARKDOWN),
+            "html" => Some(DocFormat::HTML),
+            _ => None,
+        };
+    }
+
+    fn extension(&self) -> &'static str {
+        return match self {
+            DocFormat::MARKDOWN => "md",
+            DocFormat::HTML => "html",
+        };
+    }
+}
+
+// every .gila file under the directory, sorted so the docs come out the same each time
+pub fn gila_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![];
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(gila_files(&path));
+        } else if path.extension().is_some_and(|e| e == "gila") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    return files;
+}
+
+// the name a file is imported by, relative to the directory being documented i.e. net/http.gila
+// is net.http
+pub fn module_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file).with_extension("");
+    return relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(".");
+}
+
+// a top level declaration with its doc comment and whether it's exported
+struct Item<'a> {
+    doc: Option<Rc<String>>,
+    exported: bool,
+    declaration: &'a ASTNode,
+}
+
+fn items(ast: &ASTNode) -> Vec<Item<'_>> {
+    let Statement::PROGRAM(program) = &ast.statement else {
+        return vec![];
+    };
+    let mut items: Vec<Item> = vec![];
+    for node in program {
+        let mut item = Item {
+            doc: None,
+            exported: false,
+            declaration: node,
+        };
+        loop {
+            match &item.declaration.statement {
+                Statement::EXPORT(declaration) => {
+                    item.exported = true;
+                    item.declaration = declaration;
+                }
+                Statement::DOC(doc, declaration) => {
+                    item.doc = Some(doc.clone());
+                    item.declaration = declaration;
+                }
+                Statement::ANNOTATION(_, _, declaration) => item.declaration = declaration,
+                _ => break,
+            }
+        }
+        if item.declaration.declared_name().is_some() {
+            items.push(item);
+        }
+    }
+    return items;
+}
+
+// the names of the types, enums and interfaces in a module
+fn type_names(ast: &ASTNode) -> Vec<Rc<String>> {
+    return items(ast)
+        .iter()
+        .filter_map(|item| match &item.declaration.statement {
+            Statement::NAMED_TYPE_DECL(t, _)
+            | Statement::NAMED_ENUM_DECL(t, _)
+            | Statement::NAMED_INTERFACE_DECL(t, _) => Some(t.as_identifier()),
+            _ => None,
+        })
+        .collect();
+}
+
+// renders a page for each module and an index of them, as (file name, contents) pairs. the
+// modules are (name, ast) pairs
+pub fn render(modules: &Vec<(String, ASTNode)>, format: DocFormat) -> Vec<(String, String)> {
+    // where each type is documented, so signatures can link to it
+    let mut type_modules: HashMap<Rc<String>, String> = HashMap::new();
+    for (name, ast) in modules {
+        for type_name in type_names(ast) {
+            type_modules.entry(type_name).or_insert(name.clone());
+        }
+    }
+
+    let mut pages: Vec<(String, String)> = vec![];
+    let mut index = Page::new(format, &type_modules, "");
+    index.heading(1, "Modules", None);
+    let links = modules
+        .iter()
+        .map(|(name, _)| index.link(name, &format!("{}.{}", name, format.extension())))
+        .collect();
+    index.list(links);
+    pages.push((
+        format!("index.{}", format.extension()),
+        index.finish("Modules"),
+    ));
+
+    for (name, ast) in modules {
+        let mut page = Page::new(format, &type_modules, name);
+        page.module(ast);
+        pages.push((
+            format!("{}.{}", name, format.extension()),
+            page.finish(name),
+        ));
+    }
+    return pages;
+}
+
+struct Page<'a> {
+    format: DocFormat,
+    type_modules: &'a HashMap<Rc<String>, String>,
+    module: &'a str,
+    out: String,
+}
+
+impl<'a> Page<'a> {
+    fn new(
+        format: DocFormat,
+        type_modules: &'a HashMap<Rc<String>, String>,
+        module: &'a str,
+    ) -> Self {
+        return Page {
+            format,
+            type_modules,
+            module,
+            out: "".to_string(),
+        };
+    }
+
+    fn module(&mut self, ast: &ASTNode) {
+        self.heading(1, self.module, None);
+        let items = items(ast);
+        let types = type_names(ast);
+        for item in &items {
+            // methods are documented with their type
+            if let Statement::NAMED_FUNCTION(_, params, _, _) = &item.declaration.statement {
+                if method_receiver(params).is_some_and(|receiver| types.contains(&receiver)) {
+                    continue;
+                }
+            }
+            self.item(2, item);
+            let name = item.declaration.declared_name();
+            if !name.as_ref().is_some_and(|name| types.contains(name)) {
+                continue;
+            }
+            for method in &items {
+                if let Statement::NAMED_FUNCTION(_, params, _, _) = &method.declaration.statement {
+                    if method_receiver(params) == name {
+                        self.item(3, method);
+                    }
+                }
+            }
+        }
+    }
+
+    fn item(&mut self, level: usize, item: &Item) {
+        let name = item.declaration.declared_name().unwrap();
+        let anchor = match &item.declaration.statement {
+            Statement::NAMED_TYPE_DECL(_, _)
+            | Statement::NAMED_ENUM_DECL(_, _)
+            | Statement::NAMED_INTERFACE_DECL(_, _) => Some(name.as_str()),
+            _ => None,
+        };
+        self.heading(level, &name, anchor);
+        let export = if item.exported { "export " } else { "" };
+        match &item.declaration.statement {
+            Statement::NAMED_FUNCTION(_, params, return_type, _) => {
+                let signature = self.function_signature(params, return_type);
+                self.signature(&format!("{}{} fn{}", export, self.text(&name), signature));
+            }
+            Statement::DEFINE(_, typ, _) => {
+                let typ = match typ {
+                    Some(typ) => format!(": {}", self.data_type(typ)),
+                    None => "".to_string(),
+                };
+                self.signature(&format!("{}let {}{}", export, self.text(&name), typ));
+            }
+            Statement::NAMED_TYPE_DECL(_, decls) => {
+                self.signature(&format!("{}{} type", export, self.text(&name)));
+                self.doc(&item.doc);
+                self.list(decls.iter().map(|decl| self.param(decl)).collect());
+                return;
+            }
+            Statement::NAMED_ENUM_DECL(_, variants) => {
+                self.signature(&format!("{}{} enum", export, self.text(&name)));
+                self.doc(&item.doc);
+                let lines = variants
+                    .iter()
+                    .map(|(variant, decls)| {
+                        let mut line = self.text(&variant.as_identifier());
+                        if decls.len() > 0 {
+                            line.push_str(&self.params(decls));
+                        }
+                        line
+                    })
+                    .collect();
+                self.list(lines);
+                return;
+            }
+            Statement::NAMED_INTERFACE_DECL(_, methods) => {
+                self.signature(&format!("{}{} interface", export, self.text(&name)));
+                self.doc(&item.doc);
+                let lines = methods
+                    .iter()
+                    .map(|(method, params, return_type)| {
+                        let signature = self.function_signature(params, return_type);
+                        format!("{} fn{}", self.text(&method.as_identifier()), signature)
+                    })
+                    .collect();
+                self.list(lines);
+                return;
+            }
+            _ => {}
+        }
+        self.doc(&item.doc);
+    }
+
+    // (a: i64, b = 1, ...rest) -> string
+    fn function_signature(&self, params: &Vec<ASTNode>, return_type: &Option<DataType>) -> String {
+        let mut signature = self.params(params);
+        if let Some(return_type) = return_type {
+            signature.push_str(&format!(" -> {}", self.data_type(return_type)));
+        }
+        return signature;
+    }
+
+    fn params(&self, params: &Vec<ASTNode>) -> String {
+        return format!(
+            "({})",
+            params
+                .iter()
+                .map(|p| self.param(p))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+
+    fn param(&self, param: &ASTNode) -> String {
+        match &param.statement {
+            Statement::DEFINE(t, typ, default) => {
+                let mut param = self.text(&t.as_identifier());
+                if let Some(typ) = typ {
+                    param.push_str(&format!(": {}", self.data_type(typ)));
+                }
+                if let Some(default) = default {
+                    param.push_str(&format!(" = {}", self.text(&literal(default))));
+                }
+                return param;
+            }
+            Statement::SPREAD(define) => format!("...{}", self.param(define)),
+            Statement::DESTRUCTURE(pattern, _) => self.text(&pattern_text(pattern)),
+            _ => "".to_string(),
+        }
+    }
+
+    // the type with each type that's documented linked to
+    fn data_type(&self, typ: &DataType) -> String {
+        return typ.print_with(&|name| match self.type_modules.get(name) {
+            Some(module) if module == self.module => self.link(name, &format!("#{}", name)),
+            Some(module) => self.link(
+                name,
+                &format!("{}.{}#{}", module, self.format.extension(), name),
+            ),
+            None => self.text(name),
+        });
+    }
+
+    fn link(&self, text: &str, href: &str) -> String {
+        return match self.format {
+            DocFormat::MARKDOWN => format!("[{}]({})", self.text(text), href),
+            DocFormat::HTML => format!("<a href=\"{}\">{}</a>", href, self.text(text)),
+        };
+    }
+
+    // escapes text so it's shown as it's written
+    fn text(&self, text: &str) -> String {
+        let mut escaped = "".to_string();
+        for c in text.chars() {
+            match (self.format, c) {
+                (DocFormat::MARKDOWN, '\\' | '_' | '*' | '[' | ']' | '<' | '`') => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                (DocFormat::HTML, '<') => escaped.push_str("&lt;"),
+                (DocFormat::HTML, '>') => escaped.push_str("&gt;"),
+                (DocFormat::HTML, '&') => escaped.push_str("&amp;"),
+                (DocFormat::HTML, '"') => escaped.push_str("&quot;"),
+                _ => escaped.push(c),
+            }
+        }
+        return escaped;
+    }
+
+    fn heading(&mut self, level: usize, text: &str, anchor: Option<&str>) {
+        let text = self.text(text);
+        match (self.format, anchor) {
+            (DocFormat::MARKDOWN, Some(anchor)) => self.out.push_str(&format!(
+                "<a id=\"{}\"></a>\n\n{} {}\n\n",
+                anchor,
+                "#".repeat(level),
+                text
+            )),
+            (DocFormat::MARKDOWN, None) => {
+                self.out
+                    .push_str(&format!("{} {}\n\n", "#".repeat(level), text))
+            }
+            (DocFormat::HTML, Some(anchor)) => self.out.push_str(&format!(
+                "<h{} id=\"{}\">{}</h{}>\n",
+                level, anchor, text, level
+            )),
+            (DocFormat::HTML, None) => self
+                .out
+                .push_str(&format!("<h{}>{}</h{}>\n", level, text, level)),
+        }
+    }
+
+    // already escaped, as it can have links in
+    fn signature(&mut self, signature: &str) {
+        match self.format {
+            DocFormat::MARKDOWN => self.out.push_str(&format!("> {}\n\n", signature)),
+            DocFormat::HTML => self
+                .out
+                .push_str(&format!("<pre><code>{}</code></pre>\n", signature)),
+        }
+    }
+
+    // doc comments are markdown, so they're copied straight into markdown pages
+    fn doc(&mut self, doc: &Option<Rc<String>>) {
+        let Some(doc) = doc else {
+            return;
+        };
+        match self.format {
+            DocFormat::MARKDOWN => self.out.push_str(&format!("{}\n\n", doc)),
+            DocFormat::HTML => {
+                for paragraph in doc.split("\n\n") {
+                    let paragraph = self.text(paragraph.trim());
+                    self.out.push_str(&format!("<p>{}</p>\n", paragraph));
+                }
+            }
+        }
+    }
+
+    fn list(&mut self, items: Vec<String>) {
+        if items.is_empty() {
+            return;
+        }
+        match self.format {
+            DocFormat::MARKDOWN => {
+                for item in items {
+                    self.out.push_str(&format!("- {}\n", item));
+                }
+                self.out.push('\n');
+            }
+            DocFormat::HTML => {
+                self.out.push_str("<ul>\n");
+                for item in items {
+                    self.out.push_str(&format!("<li>{}</li>\n", item));
+                }
+                self.out.push_str("</ul>\n");
+            }
+        }
+    }
+
+    fn finish(self, title: &str) -> String {
+        return match self.format {
+            DocFormat::MARKDOWN => self.out,
+            DocFormat::HTML => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+                self.text(title),
+                self.out
+            ),
+        };
+    }
+}
+
+// a parameter's default value as it was written, if it's simple enough to show
+fn literal(node: &ASTNode) -> String {
+    return match &node.statement {
+        Statement::LITERAL_NUM(t) => t.as_number().to_string(),
+        Statement::STRING(t) => format!("\"{}\"", t.as_string()),
+        Statement::LITERAL_BOOL(b) => b.to_string(),
+        Statement::ATOM(Token {
+            typ: Type::ATOM(atom),
+            ..
+        }) => format!(":{}", atom),
+        Statement::VARIABLE(t) => t.as_identifier().to_string(),
+        _ => "...".to_string(),
+    };
+}
+
+fn pattern_text(pattern: &Pattern) -> String {
+    return match pattern {
+        Pattern::BIND(t) => t.as_identifier().to_string(),
+        Pattern::TUPLE(items, _) => format!(
+            "({})",
+            items
+                .iter()
+                .map(pattern_text)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        _ => "_".to_string(),
+    };
+}
@@ -1,11 +1,12 @@
-use crate::compiler::{Compiler, CompilerFlags};
-use crate::lex::Type;
+use crate::compiler::{CompilationError, Compiler, CompilerFlags};
+use crate::lex::{Position, Type};
+use crate::module::{self, ModuleSource};
 use core::panic;
 use deepsize::DeepSizeOf;
 use libloading::{Library, Symbol};
 use std::hash::Hash;
 use std::ops::Deref;
-use std::os::windows::thread;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::{collections::HashMap, fmt::format, fs::File, rc::Rc};
 use std::{env, fs, iter, vec};
@@ -14,7 +15,7 @@ use std::{env, fs, iter, vec};
 // use std::os::windows::io::AsRawHandle;
 
 use crate::{
-    codegen::{Chunk, Instruction, OpInstruction},
+    codegen::{Chunk, Instruction, OpInstruction, UpvalueSource},
     config::Config,
 };
 
@@ -34,7 +35,7 @@ macro_rules! stack_set {
 macro_rules! increment_ip {
     ($self:expr) => {
         $self.environment.stack_frames[$self.environment.stack_frame_pointer]
-            .instruction_pointer += 1;
+            .instruction_pointer += 1
     };
 }
 
@@ -44,24 +45,86 @@ pub enum RuntimeError {
     INVALID_OPERATION(String),
     INVALID_GC_REF,
     INVALID_ACCESS(String),
-    OUT_OF_BOUNDS,
+    // where the index is once it's known, the index and the length of what was indexed
+    OUT_OF_BOUNDS(Option<Position>, i64, usize),
+    // where the index is if known, and the value which isn't an integer
+    INVALID_INDEX(Option<Position>, String),
+    // where the range is if known, and its start and end
+    REVERSED_RANGE(Option<Position>, i64, i64),
     OUT_OF_MEMORY,
     UNKNOWN_MODULE,
+    // the files in the cycle, starting and ending with the same one
+    CIRCULAR_IMPORT(Vec<PathBuf>),
+    // line, the module's file, the name it doesn't export
+    UNKNOWN_EXPORT(usize, PathBuf, String),
     INVALID_TYP,
+    // the module that didn't compile and why
+    COMPILATION_ERROR(PathBuf, Box<CompilationError>),
+    // line, number of names in the pattern, number of items in the value
+    DESTRUCTURE_MISMATCH(usize, usize, usize),
+    // line and column of the pattern, what the pattern takes apart, the value it was given
+    CANNOT_DESTRUCTURE(usize, usize, String, String),
+    // line, type name, field name
+    MISSING_FIELD(usize, String, String),
+    // line and column of the match, the value that wasn't matched
+    NO_MATCH(usize, usize, String),
+    // line, function name, parameter name
+    MISSING_ARGUMENT(usize, String, String),
+    // line, function name, argument name
+    UNKNOWN_ARGUMENT(usize, String, String),
+    // line, function name, parameter name
+    DUPLICATE_ARGUMENT(usize, String, String),
+    // line, function name, most arguments it takes, number of arguments given
+    TOO_MANY_ARGUMENTS(usize, String, usize, usize),
+    // line, builtin name, number of arguments it takes, number of arguments given
+    WRONG_ARGUMENT_COUNT(usize, String, usize, usize),
+    // line, builtin name, the argument it can't take
+    INVALID_ARGUMENT(usize, String, String),
+    // line, type name
+    POSITIONAL_CONSTRUCTOR(usize, String),
+    // line, type name, the field it doesn't have
+    UNKNOWN_FIELD(usize, String, String),
+    // line, type name, the field which wasn't given
+    UNSET_FIELD(usize, String, String),
+    // line, the value that was called
+    NOT_CALLABLE(usize, String),
+    // line, the value that was looped over
+    NOT_ITERABLE(usize, String),
 }
+// which variant of an enum a constructor or value is, or which type made it when there's no enum.
+// instances share their constructor's tag, so two objects are the same variant when their tags are
+// the same Rc
+#[derive(DeepSizeOf, Debug)]
+pub struct VariantTag {
+    pub enum_name: Option<Rc<String>>,
+    pub name: Rc<String>,
+    // the declared fields, in order, so printing doesn't depend on the field map
+    pub fields: Vec<String>,
+}
+
 #[derive(DeepSizeOf, Debug, Clone)]
 pub struct DynamicObject {
     // todo perhaps this should be builtin-strings or RC'd?
     pub fields: HashMap<String, Object>,
+    pub tag: Option<Rc<VariantTag>>,
+    // for types, a map from the name of each annotation on it to its arguments
+    pub annotations: Option<GCRef>,
 }
 
 impl DynamicObject {
     pub fn new(map: HashMap<String, Object>) -> Self {
-        DynamicObject { fields: map }
+        DynamicObject {
+            fields: map,
+            tag: None,
+            annotations: None,
+        }
     }
 
-    pub fn print(&self) -> String {
-        return format!("DynamicObject={:?}", self.fields);
+    pub fn is_variant(&self, name: &str) -> bool {
+        match &self.tag {
+            Some(tag) => tag.enum_name.is_some() && *tag.name == name,
+            None => false,
+        }
     }
 }
 #[derive(DeepSizeOf, Debug, Clone)]
@@ -74,9 +137,19 @@ pub struct FnObject {
     // the slot for the local variable it needs to bind to
     pub method_to_object: Option<u8>,
     pub param_slots: Vec<u8>,
+    // for passing arguments by name, a parameter which is destructured has no name
+    pub param_names: Vec<Option<String>>,
+    // the values of the last defaults.len() parameters when they aren't passed, set by BUILD_FN
+    pub defaults: Vec<Object>,
+    // the last parameter is a `...rest` which collects the extra positional arguments
+    pub variadic: bool,
     // todo maybe make a BoundedFn object?
     pub bounded_object: Option<GCRef>,
     pub constants_initialised: bool,
+    // the cells this closure captured, in the order of the chunk's upvalues
+    pub upvalues: Vec<Object>,
+    // a map from the name of each annotation on the function to its arguments
+    pub annotations: Option<GCRef>,
 }
 
 #[derive(Debug, Clone)]
@@ -123,6 +196,57 @@ pub struct SliceObject {
     pub s: Vec<Object>,
 }
 
+// what a map key is hashed as, only values that can't change once they're in the map can be keys
+#[derive(DeepSizeOf, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    BOOL(bool),
+    I64(i64),
+    ATOM(Rc<String>),
+    STRING(Rc<String>),
+    TUPLE(Vec<MapKey>),
+}
+
+#[derive(DeepSizeOf, Debug, Clone)]
+pub struct MapObject {
+    // in insertion order so printing and iterating don't depend on the hashing
+    pub entries: Vec<(Object, Object)>,
+    // the position of each key in entries
+    pub indexes: HashMap<MapKey, usize>,
+}
+
+impl MapObject {
+    pub fn new() -> Self {
+        MapObject {
+            entries: vec![],
+            indexes: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&Object> {
+        match self.indexes.get(key) {
+            Some(index) => Some(&self.entries[*index].1),
+            None => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: MapKey, key_obj: Object, value: Object) {
+        match self.indexes.get(&key) {
+            Some(index) => self.entries[*index].1 = value,
+            None => {
+                self.indexes.insert(key, self.entries.len());
+                self.entries.push((key_obj, value));
+            }
+        }
+    }
+}
+
+// the keys of a map or the items of a slice being looped over, taken when the loop starts
+#[derive(DeepSizeOf, Debug, Clone)]
+pub struct MapIteratorObject {
+    pub keys: Vec<Object>,
+    pub counter: usize,
+}
+
 #[derive(DeepSizeOf, Debug, Clone)]
 pub struct GilaABIDLLObject {
     pub id: usize,
@@ -136,65 +260,117 @@ pub enum GCRefData {
     STRING(StringObject),
     SLICE(SliceObject),
     DYNAMIC_OBJECT(DynamicObject),
+    MAP(MapObject),
+    MAP_ITERATOR(MapIteratorObject),
+    // a captured variable shared between a function and its closures
+    CELL(Object),
 }
 
 impl GCRefData {
     pub fn as_slice(&self) -> Result<&SliceObject, RuntimeError> {
         match self {
             Self::SLICE(s) => Ok(s),
-            // todo results
-            _ => panic!(),
+            _ => Err(RuntimeError::INVALID_TYP),
         }
     }
 
     pub fn print(&self, shared_execution_context: &SharedExecutionContext) -> String {
+        let mut seen: Vec<usize> = vec![];
+        return self
+            .format(&mut |item| Ok(item.print_nested(shared_execution_context, &mut seen)))
+            .unwrap();
+    }
+
+    // how the object reads, with render deciding how each thing it holds reads
+    pub fn format(
+        &self,
+        render: &mut dyn FnMut(&Object) -> Result<String, RuntimeError>,
+    ) -> Result<String, RuntimeError> {
         match self {
-            Self::STRING(s) => s.s.to_string(),
+            Self::STRING(s) => Ok(s.s.to_string()),
+            Self::CELL(value) => render(value),
             Self::FN(fn_object) => {
                 if fn_object.bounded_object.is_some() {
-                    return format!("<bounded fn {}>", fn_object.name);
+                    return Ok(format!("<bounded fn {}>", fn_object.name));
                 } else {
-                    return format!("<fn {}>", fn_object.name);
+                    return Ok(format!("<fn {}>", fn_object.name));
                 }
             }
             Self::GILA_ABI_FUNCTION_OBJECT(fn_object) => {
-                return format!("<gila abi function object {:?}>", fn_object);
+                return Ok(format!("<gila abi function object {:?}>", fn_object));
             }
             Self::SLICE(slice) => {
-                format!(
-                    "[{}]",
-                    slice
-                        .s
-                        .iter()
-                        .map(|item| item.print(shared_execution_context))
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+                let items = slice
+                    .s
+                    .iter()
+                    .map(&mut *render)
+                    .collect::<Result<Vec<_>, _>>();
+                if items.is_err() {
+                    return Err(items.err().unwrap());
+                }
+                Ok(format!("[{}]", items.unwrap().join(", ")))
+            }
+            Self::MAP(m) => {
+                let mut entries: Vec<String> = vec![];
+                for (key, value) in m.entries.iter() {
+                    let key = render(key);
+                    if key.is_err() {
+                        return Err(key.err().unwrap());
+                    }
+                    let value = render(value);
+                    if value.is_err() {
+                        return Err(value.err().unwrap());
+                    }
+                    entries.push(format!("{}: {}", key.unwrap(), value.unwrap()));
+                }
+                Ok(format!("{{{}}}", entries.join(", ")))
             }
+            Self::MAP_ITERATOR(_) => Ok("<map iterator>".to_string()),
             Self::TUPLE(t) => {
-                format!(
-                    "({})",
-                    t.iter()
-                        .map(|item| item.print(shared_execution_context))
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+                let items = t.iter().map(&mut *render).collect::<Result<Vec<_>, _>>();
+                if items.is_err() {
+                    return Err(items.err().unwrap());
+                }
+                Ok(format!("({})", items.unwrap().join(", ")))
+            }
+            Self::DYNAMIC_OBJECT(d) if d.tag.is_some() => {
+                let tag = d.tag.as_ref().unwrap();
+                // instances point back at what made them, anything else is a type or a variant
+                if !d.fields.contains_key("__prototype__") {
+                    return match &tag.enum_name {
+                        Some(enum_name) => Ok(format!("<variant {}.{}>", enum_name, tag.name)),
+                        None => Ok(format!("<type {}>", tag.name)),
+                    };
+                }
+                if tag.fields.is_empty() && tag.enum_name.is_some() {
+                    return Ok(tag.name.to_string());
+                }
+                let mut fields: Vec<String> = vec![];
+                for field in tag.fields.iter() {
+                    if let Some(value) = d.fields.get(field) {
+                        let value = render(value);
+                        if value.is_err() {
+                            return Err(value.err().unwrap());
+                        }
+                        fields.push(format!("{}={}", field, value.unwrap()));
+                    }
+                }
+                Ok(format!("{}({})", tag.name, fields.join(", ")))
             }
             Self::DYNAMIC_OBJECT(d) => {
-                format!(
-                    "{{{}}}",
-                    d.fields
-                        .iter()
-                        .map(|(key, value)| format!(
-                            "{}={}",
-                            key,
-                            value.print(shared_execution_context)
-                        ))
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                )
+                // sorted so it reads the same every time
+                let mut keys: Vec<&String> = d.fields.keys().collect();
+                keys.sort();
+                let mut fields: Vec<String> = vec![];
+                for key in keys {
+                    let value = render(&d.fields[key]);
+                    if value.is_err() {
+                        return Err(value.err().unwrap());
+                    }
+                    fields.push(format!("{}={}", key, value.unwrap()));
+                }
+                Ok(format!("{{{}}}", fields.join(" ")))
             }
-            _ => panic!("Cant print self {:?}", self),
         }
     }
 }
@@ -217,10 +393,14 @@ impl Object {
         match self {
             Self::GC_REF(gc_ref) => {
                 // println!("marking {}", gc_ref.index);
-                shared_execution_context
+                let unmarked = shared_execution_context
                     .heap
                     .dead_objects
                     .remove(&gc_ref.index);
+                // already traced, closures can refer back to themselves through their cells
+                if unmarked.is_none() {
+                    return Ok(());
+                }
 
                 let derefed = shared_execution_context.heap.deref(&gc_ref);
                 if derefed.is_err() {
@@ -229,10 +409,11 @@ impl Object {
                 match derefed.unwrap() {
                     GCRefData::FN(f) => {
                         if f.bounded_object.is_some() {
-                            shared_execution_context
-                                .heap
-                                .dead_objects
-                                .remove(&f.bounded_object.unwrap().index);
+                            let res = Object::GC_REF(f.bounded_object.unwrap())
+                                .mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
                         }
 
                         for constant in f.chunk.constant_pool {
@@ -241,6 +422,27 @@ impl Object {
                                 return Err(res.err().unwrap());
                             }
                         }
+
+                        for upvalue in f.upvalues {
+                            let res = upvalue.mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                        }
+
+                        if f.annotations.is_some() {
+                            let res = Object::GC_REF(f.annotations.unwrap())
+                                .mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                        }
+                    }
+                    GCRefData::CELL(value) => {
+                        let res = value.mark(shared_execution_context);
+                        if res.is_err() {
+                            return Err(res.err().unwrap());
+                        }
                     }
                     GCRefData::DYNAMIC_OBJECT(d) => {
                         for (_, value) in d.fields {
@@ -249,6 +451,14 @@ impl Object {
                                 return Err(res.err().unwrap());
                             }
                         }
+
+                        if d.annotations.is_some() {
+                            let res = Object::GC_REF(d.annotations.unwrap())
+                                .mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                        }
                     }
                     GCRefData::SLICE(s) => {
                         for value in s.s {
@@ -266,6 +476,26 @@ impl Object {
                             }
                         }
                     }
+                    GCRefData::MAP(m) => {
+                        for (key, value) in m.entries {
+                            let res = key.mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                            let res = value.mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                        }
+                    }
+                    GCRefData::MAP_ITERATOR(it) => {
+                        for key in it.keys {
+                            let res = key.mark(shared_execution_context);
+                            if res.is_err() {
+                                return Err(res.err().unwrap());
+                            }
+                        }
+                    }
                     _ => {}
                 }
 
@@ -387,13 +617,49 @@ impl Object {
         }
     }
 
+    pub fn map_key(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+    ) -> Result<MapKey, RuntimeError> {
+        match self {
+            Self::BOOL(b) => Ok(MapKey::BOOL(*b)),
+            Self::I64(i) => Ok(MapKey::I64(*i)),
+            Self::ATOM(a) => Ok(MapKey::ATOM(a.clone())),
+            Self::GC_REF(gc_ref) => {
+                let res = shared_execution_context.heap.deref(gc_ref);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                match res.unwrap() {
+                    GCRefData::STRING(s) => Ok(MapKey::STRING(s.s)),
+                    GCRefData::TUPLE(t) => {
+                        let mut keys: Vec<MapKey> = vec![];
+                        for item in t {
+                            let key = item.map_key(shared_execution_context);
+                            if key.is_err() {
+                                return Err(key.err().unwrap());
+                            }
+                            keys.push(key.unwrap());
+                        }
+                        Ok(MapKey::TUPLE(keys))
+                    }
+                    _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                        "{} can't be used as a map key",
+                        self.print(shared_execution_context)
+                    ))),
+                }
+            }
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "{} can't be used as a map key",
+                self.print(shared_execution_context)
+            ))),
+        }
+    }
+
     pub fn as_i64(&self) -> Result<i64, RuntimeError> {
         match &self {
             Self::I64(i) => Ok(*i),
-            _ => {
-                println!("tried to unwrap us to i64 {:?}", self);
-                return Err(RuntimeError::INVALID_TYP);
-            }
+            _ => Err(RuntimeError::INVALID_TYP),
         }
     }
 
@@ -415,48 +681,260 @@ impl Object {
             Self::I64(i) => i.to_string(),
             Self::ATOM(a) => format!(":{}", a.to_string()),
             Self::GILA_ABI_DLL(id) => format!("<gila abi dll {}>", id),
-            Self::GC_REF(gc_ref) => {
-                let res = shared_execution_context.heap.deref(&gc_ref);
-                let obj: String;
-                if res.is_ok() {
-                    obj = res.unwrap().print(shared_execution_context);
-                } else {
-                    shared_execution_context.heap.dump_heap();
-                    panic!("tried to deref {}", gc_ref.index);
-                }
-                obj
-            }
+            Self::GC_REF(_) => self.print_nested(shared_execution_context, &mut vec![]),
         }
     }
 
-    pub fn index(
+    // seen is what's being printed further out, so something which holds itself prints as ...
+    // instead of forever
+    fn print_nested(
         &self,
         shared_execution_context: &SharedExecutionContext,
+        seen: &mut Vec<usize>,
+    ) -> String {
+        let Self::GC_REF(gc_ref) = self else {
+            return self.print(shared_execution_context);
+        };
+        if seen.contains(&gc_ref.index) {
+            return "...".to_string();
+        }
+        let res = shared_execution_context.heap.deref(gc_ref);
+        if res.is_err() {
+            shared_execution_context.heap.dump_heap();
+            panic!("tried to deref {}", gc_ref.index);
+        }
+        seen.push(gc_ref.index);
+        let obj = res
+            .unwrap()
+            .format(&mut |item| Ok(item.print_nested(shared_execution_context, seen)))
+            .unwrap();
+        seen.pop();
+        obj
+    }
+
+    pub fn index(
+        &self,
+        shared_execution_context: &mut SharedExecutionContext,
+        config: &Config,
         obj_to_index: &Object,
     ) -> Result<Object, RuntimeError> {
-        let gc_ref_data = self.as_gc_ref(shared_execution_context);
+        let gc_ref_data = match self {
+            Self::GC_REF(gc_ref) => shared_execution_context.heap.deref(gc_ref),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant index {}",
+                self.print(shared_execution_context)
+            ))),
+        };
         if gc_ref_data.is_err() {
             return Err(gc_ref_data.err().unwrap());
         }
         match gc_ref_data.unwrap() {
             GCRefData::SLICE(s) => {
-                let idx = obj_to_index.as_i64();
-                let unwrapped = idx.unwrap();
-                if unwrapped >= s.s.len() as i64 {
-                    return Err(RuntimeError::OUT_OF_BOUNDS);
+                let idx = position_of_index(shared_execution_context, obj_to_index, s.s.len());
+                if idx.is_err() {
+                    return Err(idx.err().unwrap());
                 }
-                return Ok(s.s[unwrapped as usize].clone());
+                return Ok(s.s[idx.unwrap()].clone());
             }
             GCRefData::TUPLE(t) => {
-                let idx = obj_to_index.as_i64();
-                let unwrapped = idx.unwrap();
-                if unwrapped >= t.len() as i64 {
-                    return Err(RuntimeError::OUT_OF_BOUNDS);
+                let idx = position_of_index(shared_execution_context, obj_to_index, t.len());
+                if idx.is_err() {
+                    return Err(idx.err().unwrap());
                 }
-                return Ok(t[unwrapped as usize].clone());
+                return Ok(t[idx.unwrap()].clone());
+            }
+            // indexing a string gives back the character as a string
+            GCRefData::STRING(s) => {
+                let idx =
+                    position_of_index(shared_execution_context, obj_to_index, s.s.chars().count());
+                if idx.is_err() {
+                    return Err(idx.err().unwrap());
+                }
+                let c = s.s.chars().nth(idx.unwrap()).unwrap();
+                let string_obj = shared_execution_context.heap.alloc(
+                    GCRefData::STRING(StringObject {
+                        s: Rc::new(c.to_string()),
+                    }),
+                    config,
+                );
+                if string_obj.is_err() {
+                    return Err(string_obj.err().unwrap());
+                }
+                return Ok(Object::GC_REF(string_obj.unwrap()));
+            }
+            GCRefData::MAP(m) => {
+                let key = obj_to_index.map_key(shared_execution_context);
+                if key.is_err() {
+                    return Err(key.err().unwrap());
+                }
+                match m.get(&key.unwrap()) {
+                    Some(value) => return Ok(value.clone()),
+                    None => {
+                        return Err(RuntimeError::INVALID_ACCESS(format!(
+                            "no key {} in map",
+                            obj_to_index.print(shared_execution_context)
+                        )))
+                    }
+                }
+            }
+            other => {
+                return Err(RuntimeError::INVALID_OPERATION(format!(
+                    "cant index {}",
+                    other.print(shared_execution_context)
+                )))
+            }
+        }
+    }
+
+    // the part of a slice, tuple or string between two indexes, which count from the end when
+    // they're negative. the end can be the length, as it isn't included
+    pub fn index_range(
+        &self,
+        shared_execution_context: &mut SharedExecutionContext,
+        config: &Config,
+        start: &Object,
+        end: &Object,
+    ) -> Result<Object, RuntimeError> {
+        let gc_ref_data = match self {
+            Self::GC_REF(gc_ref) => shared_execution_context.heap.deref(gc_ref),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant slice {}",
+                self.print(shared_execution_context)
+            ))),
+        };
+        if gc_ref_data.is_err() {
+            return Err(gc_ref_data.err().unwrap());
+        }
+        let gc_ref_data = gc_ref_data.unwrap();
+        let len = match &gc_ref_data {
+            GCRefData::SLICE(s) => s.s.len(),
+            GCRefData::TUPLE(t) => t.len(),
+            GCRefData::STRING(s) => s.s.chars().count(),
+            other => {
+                return Err(RuntimeError::INVALID_OPERATION(format!(
+                    "cant slice {}",
+                    other.print(shared_execution_context)
+                )))
+            }
+        };
+        let bound = |obj: &Object| -> Result<(i64, usize), RuntimeError> {
+            let i = obj.as_i64();
+            if i.is_err() {
+                return Err(RuntimeError::INVALID_INDEX(
+                    None,
+                    obj.print(shared_execution_context),
+                ));
+            }
+            let i = i.unwrap();
+            // a range with no end is parsed as ending at i64::MAX, which means the whole way
+            if i == i64::MAX {
+                return Ok((i, len));
+            }
+            let position = if i < 0 { i + len as i64 } else { i };
+            if position < 0 || position > len as i64 {
+                return Err(RuntimeError::OUT_OF_BOUNDS(None, i, len));
+            }
+            Ok((i, position as usize))
+        };
+        let start = bound(start);
+        if start.is_err() {
+            return Err(start.err().unwrap());
+        }
+        let end = bound(end);
+        if end.is_err() {
+            return Err(end.err().unwrap());
+        }
+        let ((start_index, start), (end_index, end)) = (start.unwrap(), end.unwrap());
+        if end < start {
+            return Err(RuntimeError::REVERSED_RANGE(None, start_index, end_index));
+        }
+
+        let sliced = match gc_ref_data {
+            GCRefData::SLICE(s) => GCRefData::SLICE(SliceObject {
+                s: s.s[start..end].to_vec(),
+            }),
+            GCRefData::TUPLE(t) => GCRefData::TUPLE(t[start..end].to_vec()),
+            GCRefData::STRING(s) => GCRefData::STRING(StringObject {
+                s: Rc::new(s.s.chars().skip(start).take(end - start).collect()),
+            }),
+            _ => unreachable!(),
+        };
+        let alloc = shared_execution_context.heap.alloc(sliced, config);
+        if alloc.is_err() {
+            return Err(alloc.err().unwrap());
+        }
+        Ok(Object::GC_REF(alloc.unwrap()))
+    }
+
+    pub fn index_set(
+        &self,
+        shared_execution_context: &mut SharedExecutionContext,
+        obj_to_index: &Object,
+        value: Object,
+    ) -> Result<(), RuntimeError> {
+        let gc_ref = self.as_gc_ref_index();
+        if gc_ref.is_err() {
+            return Err(gc_ref.err().unwrap());
+        }
+        let gc_ref = gc_ref.unwrap();
+        let gc_ref_data = shared_execution_context.heap.deref(&gc_ref);
+        if gc_ref_data.is_err() {
+            return Err(gc_ref_data.err().unwrap());
+        }
+        match gc_ref_data.unwrap() {
+            GCRefData::MAP(mut m) => {
+                let key = obj_to_index.map_key(shared_execution_context);
+                if key.is_err() {
+                    return Err(key.err().unwrap());
+                }
+                m.insert(key.unwrap(), obj_to_index.clone(), value);
+                return shared_execution_context
+                    .heap
+                    .set(&gc_ref, GCRefData::MAP(m));
+            }
+            GCRefData::SLICE(mut s) => {
+                let idx = position_of_index(shared_execution_context, obj_to_index, s.s.len());
+                if idx.is_err() {
+                    return Err(idx.err().unwrap());
+                }
+                s.s[idx.unwrap()] = value;
+                return shared_execution_context
+                    .heap
+                    .set(&gc_ref, GCRefData::SLICE(s));
+            }
+            other => {
+                return Err(RuntimeError::INVALID_OPERATION(format!(
+                    "cant assign to an index of {}",
+                    other.print(shared_execution_context)
+                )))
+            }
+        }
+    }
+
+    // whether self is one of the keys of container
+    pub fn is_in(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+        container: &Object,
+    ) -> Result<bool, RuntimeError> {
+        if let Object::GC_REF(gc_ref) = container {
+            let res = shared_execution_context.heap.deref(gc_ref);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+            if let GCRefData::MAP(m) = res.unwrap() {
+                let key = self.map_key(shared_execution_context);
+                if key.is_err() {
+                    return Err(key.err().unwrap());
+                }
+                return Ok(m.indexes.contains_key(&key.unwrap()));
             }
-            _ => panic!(),
         }
+        Err(RuntimeError::INVALID_OPERATION(format!(
+            "cant check for {} in {}",
+            self.print(shared_execution_context),
+            container.print(shared_execution_context)
+        )))
     }
 
     pub fn add(
@@ -572,6 +1050,11 @@ impl Object {
             Self::I64(i1) => {
                 // integer addition
                 match other {
+                    Object::I64(0) => {
+                        return Err(RuntimeError::INVALID_OPERATION(
+                            "division by zero".to_string(),
+                        ))
+                    }
                     Object::I64(i2) => return Ok(Object::I64(i1 / i2)),
                     _ => {
                         return Err(RuntimeError::INVALID_OPERATION(
@@ -597,6 +1080,121 @@ impl Object {
         }
     }
 
+    pub fn sub(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+        other: Object,
+    ) -> Result<Object, RuntimeError> {
+        match (self, &other) {
+            (Self::I64(i1), Object::I64(i2)) => Ok(Object::I64(i1 - i2)),
+            (Self::I64(i1), Object::F64(f2)) => Ok(Object::F64(*i1 as f64 - f2)),
+            (Self::F64(f1), Object::I64(i2)) => Ok(Object::F64(f1 - *i2 as f64)),
+            (Self::F64(f1), Object::F64(f2)) => Ok(Object::F64(f1 - f2)),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant subtract {} from {}",
+                other.print(shared_execution_context),
+                self.print(shared_execution_context)
+            ))),
+        }
+    }
+
+    pub fn modulo(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+        other: Object,
+    ) -> Result<Object, RuntimeError> {
+        match (self, &other) {
+            (Self::I64(i1), Object::I64(i2)) => match i1.checked_rem(*i2) {
+                Some(i) => Ok(Object::I64(i)),
+                None => Err(RuntimeError::INVALID_OPERATION(format!(
+                    "cant take {} % {}",
+                    i1, i2
+                ))),
+            },
+            (Self::F64(f1), Object::F64(f2)) => Ok(Object::F64(f1 % f2)),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant take {} % {}",
+                self.print(shared_execution_context),
+                other.print(shared_execution_context)
+            ))),
+        }
+    }
+
+    pub fn pow(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+        other: Object,
+    ) -> Result<Object, RuntimeError> {
+        match (self, &other) {
+            // a negative exponent can't give an integer back
+            (Self::I64(i1), Object::I64(i2)) if *i2 < 0 => {
+                Ok(Object::F64((*i1 as f64).powf(*i2 as f64)))
+            }
+            (Self::I64(i1), Object::I64(i2)) => {
+                match u32::try_from(*i2).ok().and_then(|e| i1.checked_pow(e)) {
+                    Some(i) => Ok(Object::I64(i)),
+                    None => Err(RuntimeError::INVALID_OPERATION(format!(
+                        "{} ** {} overflowed",
+                        i1, i2
+                    ))),
+                }
+            }
+            (Self::I64(i1), Object::F64(f2)) => Ok(Object::F64((*i1 as f64).powf(*f2))),
+            (Self::F64(f1), Object::I64(i2)) => Ok(Object::F64(f1.powf(*i2 as f64))),
+            (Self::F64(f1), Object::F64(f2)) => Ok(Object::F64(f1.powf(*f2))),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant take {} ** {}",
+                self.print(shared_execution_context),
+                other.print(shared_execution_context)
+            ))),
+        }
+    }
+
+    // used for the bitwise and shift operators which only make sense on integers
+    pub fn integer_op(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+        other: Object,
+        symbol: &str,
+        op: fn(i64, i64) -> Option<i64>,
+    ) -> Result<Object, RuntimeError> {
+        match (self, &other) {
+            (Self::I64(i1), Object::I64(i2)) => match op(*i1, *i2) {
+                Some(i) => Ok(Object::I64(i)),
+                None => Err(RuntimeError::INVALID_OPERATION(format!(
+                    "{} {} {} is out of range",
+                    i1, symbol, i2
+                ))),
+            },
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "only support i64{}i64 but got {} and {}",
+                symbol,
+                self.print(shared_execution_context),
+                other.print(shared_execution_context)
+            ))),
+        }
+    }
+
+    pub fn negate(
+        &self,
+        shared_execution_context: &SharedExecutionContext,
+    ) -> Result<Object, RuntimeError> {
+        match self {
+            Self::I64(i) => match i.checked_neg() {
+                Some(i) => Ok(Object::I64(i)),
+                None => Err(RuntimeError::INVALID_OPERATION(format!(
+                    "-({}) overflowed",
+                    i
+                ))),
+            },
+            Self::F64(f) => Ok(Object::F64(-f)),
+            _ => Err(RuntimeError::INVALID_OPERATION(format!(
+                "cant negate {}",
+                self.print(shared_execution_context)
+            ))),
+        }
+    }
+
     pub fn equals(
         &self,
         shared_execution_context: &SharedExecutionContext,
@@ -615,12 +1213,7 @@ impl Object {
                 // integer addition
                 match other {
                     Object::I64(i2) => return Ok(*i1 == i2),
-                    _ => {
-                        return Err(RuntimeError::INVALID_OPERATION(format!(
-                            "i64 only supports == with i64 but got {}",
-                            other.print(shared_execution_context)
-                        )))
-                    }
+                    _ => return Ok(false),
                 }
             }
             Self::GC_REF(gc_ref) => {
@@ -717,10 +1310,11 @@ impl Object {
                 // integer addition
                 match other {
                     Object::I64(i2) => return Ok(*i1 > i2),
+                    Object::F64(f2) => return Ok((*i1 as f64) > f2),
                     _ => {
                         return Err(RuntimeError::INVALID_OPERATION(
                             format!(
-                                "i64 > only supports i64 but got {}",
+                                "i64 > only supports numbers but got {}",
                                 other.print(shared_execution_context)
                             )
                             .to_string(),
@@ -729,9 +1323,19 @@ impl Object {
                 }
             }
             // Self::HEAP_OBJECT(h1) => h1.data.add(other),
+            Self::F64(f1) => match other {
+                Object::I64(i2) => return Ok(*f1 > i2 as f64),
+                Object::F64(f2) => return Ok(*f1 > f2),
+                _ => {
+                    return Err(RuntimeError::INVALID_OPERATION(format!(
+                        "f64 > only supports numbers but got {}",
+                        other.print(shared_execution_context)
+                    )))
+                }
+            },
             _ => {
                 return Err(RuntimeError::INVALID_OPERATION(
-                    "> only supports i64".to_string(),
+                    "> only supports numbers".to_string(),
                 ))
             }
         }
@@ -747,10 +1351,11 @@ impl Object {
                 // integer addition
                 match other {
                     Object::I64(i2) => return Ok(*i1 >= i2),
+                    Object::F64(f2) => return Ok((*i1 as f64) >= f2),
                     _ => {
                         return Err(RuntimeError::INVALID_OPERATION(
                             format!(
-                                "i64 >= only supports i64 but got {}",
+                                "i64 >= only supports numbers but got {}",
                                 other.print(shared_execution_context)
                             )
                             .to_string(),
@@ -758,9 +1363,19 @@ impl Object {
                     }
                 }
             }
+            Self::F64(f1) => match other {
+                Object::I64(i2) => return Ok(*f1 >= i2 as f64),
+                Object::F64(f2) => return Ok(*f1 >= f2),
+                _ => {
+                    return Err(RuntimeError::INVALID_OPERATION(format!(
+                        "f64 >= only supports numbers but got {}",
+                        other.print(shared_execution_context)
+                    )))
+                }
+            },
             _ => {
                 return Err(RuntimeError::INVALID_OPERATION(
-                    ">= only supports i64".to_string(),
+                    ">= only supports numbers".to_string(),
                 ))
             }
         }
@@ -776,10 +1391,11 @@ impl Object {
                 // integer addition
                 match other {
                     Object::I64(i2) => return Ok(*i1 < i2),
+                    Object::F64(f2) => return Ok((*i1 as f64) < f2),
                     _ => {
                         return Err(RuntimeError::INVALID_OPERATION(
                             format!(
-                                "i64 < only supports i64 but got {}",
+                                "i64 < only supports numbers but got {}",
                                 other.print(shared_execution_context)
                             )
                             .to_string(),
@@ -787,9 +1403,19 @@ impl Object {
                     }
                 }
             }
+            Self::F64(f1) => match other {
+                Object::I64(i2) => return Ok(*f1 < i2 as f64),
+                Object::F64(f2) => return Ok(*f1 < f2),
+                _ => {
+                    return Err(RuntimeError::INVALID_OPERATION(format!(
+                        "f64 < only supports numbers but got {}",
+                        other.print(shared_execution_context)
+                    )))
+                }
+            },
             _ => {
                 return Err(RuntimeError::INVALID_OPERATION(
-                    "< only supports i64".to_string(),
+                    "< only supports numbers".to_string(),
                 ))
             }
         }
@@ -805,10 +1431,11 @@ impl Object {
                 // integer addition
                 match other {
                     Object::I64(i2) => return Ok(*i1 <= i2),
+                    Object::F64(f2) => return Ok((*i1 as f64) <= f2),
                     _ => {
                         return Err(RuntimeError::INVALID_OPERATION(
                             format!(
-                                "i64 <= only supports i64 but got {}",
+                                "i64 <= only supports numbers but got {}",
                                 other.print(shared_execution_context)
                             )
                             .to_string(),
@@ -816,9 +1443,19 @@ impl Object {
                     }
                 }
             }
+            Self::F64(f1) => match other {
+                Object::I64(i2) => return Ok(*f1 <= i2 as f64),
+                Object::F64(f2) => return Ok(*f1 <= f2),
+                _ => {
+                    return Err(RuntimeError::INVALID_OPERATION(format!(
+                        "f64 <= only supports numbers but got {}",
+                        other.print(shared_execution_context)
+                    )))
+                }
+            },
             _ => {
                 return Err(RuntimeError::INVALID_OPERATION(
-                    "<= only supports i64".to_string(),
+                    "<= only supports numbers".to_string(),
                 ))
             }
         }
@@ -861,6 +1498,8 @@ pub struct Heap {
     // linked list of objects
     pub live_slots: HashMap<usize, GCRefData>,
     pub dead_objects: HashMap<usize, GCRefData>,
+    // slots are never reused, after a sweep the number of live slots is no longer free
+    pub next_index: usize,
 }
 
 #[derive(Debug, Clone, DeepSizeOf)]
@@ -882,7 +1521,8 @@ impl Heap {
         }
 
         // todo for now just push to end
-        let index = self.live_slots.len();
+        let index = self.next_index;
+        self.next_index += 1;
         self.live_slots.insert(index, gc_ref_dat);
         Ok(GCRef {
             index,
@@ -891,10 +1531,6 @@ impl Heap {
     }
 
     pub fn deref(&self, gc_ref: &GCRef) -> Result<GCRefData, RuntimeError> {
-        if gc_ref.index >= self.live_slots.len() {
-            // self.dump_heap();
-            return Err(RuntimeError::INVALID_GC_REF);
-        }
         let derefed = self.live_slots.get(&gc_ref.index);
         if derefed.is_none() {
             return Err(RuntimeError::INVALID_GC_REF);
@@ -937,6 +1573,10 @@ pub struct ProcessContext {
 pub struct SharedExecutionContext {
     pub heap: Heap,
     pub gila_abis_dlls: Vec<Arc<Library>>,
+    // modules which have already been run, by the canonical path they were loaded from
+    pub modules: HashMap<PathBuf, GCRef>,
+    // the files being run, each one imported by the one before it
+    pub importing: Vec<PathBuf>,
 }
 
 impl SharedExecutionContext {
@@ -967,8 +1607,101 @@ pub fn native_print(
     return Object::I64(0);
 }
 
-// return the new contexts
-fn native_len(
+// where an index points in something of length len, negative indexes count back from the end
+fn position_of_index(
+    shared_execution_context: &SharedExecutionContext,
+    index: &Object,
+    len: usize,
+) -> Result<usize, RuntimeError> {
+    let i = index.as_i64();
+    if i.is_err() {
+        return Err(RuntimeError::INVALID_INDEX(
+            None,
+            index.print(shared_execution_context),
+        ));
+    }
+    let i = i.unwrap();
+    let position = if i < 0 { i + len as i64 } else { i };
+    if position < 0 || position >= len as i64 {
+        return Err(RuntimeError::OUT_OF_BOUNDS(None, i, len));
+    }
+    Ok(position as usize)
+}
+
+// the name of a builtin and how many arguments it takes, natives loaded from a library aren't
+// known
+fn native_signature(native_fn: &GilaABIFunctionObject) -> Option<(&'static str, usize)> {
+    let GilaABIFunctionObject::RUST_CALL_CONVENTION(f) = native_fn else {
+        return None;
+    };
+    let natives: [(GilaABINativeFnType, &'static str, usize); 6] = [
+        (native_print, "print", 1),
+        (native_len, "len", 1),
+        (native_append, "append", 2),
+        (native_annotations, "annotations", 1),
+        (native_load_gila_abi_dll, "load_gila_abi_dll", 1),
+        (native_load_c_abi_dll, "load_c_abi_dll", 1),
+    ];
+    return natives
+        .iter()
+        .find(|(native, _, _)| std::ptr::fn_addr_eq(*f, *native))
+        .map(|(_, name, arity)| (*name, *arity));
+}
+
+// whether a native can be called with the value as its first argument, len needs something with a
+// length and append needs a slice
+fn native_accepts(
+    shared_execution_context: &SharedExecutionContext,
+    native_fn: &GilaABIFunctionObject,
+    value: &Object,
+) -> bool {
+    let GilaABIFunctionObject::RUST_CALL_CONVENTION(f) = native_fn else {
+        return true;
+    };
+    let is_len = std::ptr::fn_addr_eq(*f, native_len as GilaABINativeFnType);
+    let is_append = std::ptr::fn_addr_eq(*f, native_append as GilaABINativeFnType);
+    if !is_len && !is_append {
+        return true;
+    }
+    let Object::GC_REF(gc_ref) = value else {
+        return false;
+    };
+    return match shared_execution_context.heap.deref(gc_ref) {
+        Ok(GCRefData::SLICE(_)) => true,
+        Ok(GCRefData::MAP(_) | GCRefData::TUPLE(_) | GCRefData::STRING(_)) => is_len,
+        _ => false,
+    };
+}
+
+fn is_native_print(native_fn: &GilaABIFunctionObject) -> bool {
+    match native_fn {
+        GilaABIFunctionObject::RUST_CALL_CONVENTION(f) => {
+            std::ptr::fn_addr_eq(*f, native_print as GilaABINativeFnType)
+        }
+        _ => false,
+    }
+}
+
+// the engine swaps the argument for its map of annotations before calling this
+fn native_annotations(
+    shared_execution_context: &mut SharedExecutionContext,
+    execution_context: &mut ProcessContext,
+    args: Vec<Object>,
+) -> Object {
+    return args[0].clone();
+}
+
+fn is_native_annotations(native_fn: &GilaABIFunctionObject) -> bool {
+    match native_fn {
+        GilaABIFunctionObject::RUST_CALL_CONVENTION(f) => {
+            std::ptr::fn_addr_eq(*f, native_annotations as GilaABINativeFnType)
+        }
+        _ => false,
+    }
+}
+
+// return the new contexts
+fn native_len(
     shared_execution_context: &mut SharedExecutionContext,
     execution_context: &mut ProcessContext,
     args: Vec<Object>,
@@ -979,13 +1712,15 @@ fn native_len(
             if res.is_err() {
                 panic!();
             }
-            let unwrapped = res.unwrap();
-            let slice = unwrapped.as_slice();
-            if slice.is_err() {
-                panic!();
-            }
-
-            return Object::I64(slice.unwrap().s.len().try_into().unwrap());
+            let len = match res.unwrap() {
+                GCRefData::MAP(m) => m.entries.len(),
+                GCRefData::SLICE(s) => s.s.len(),
+                GCRefData::TUPLE(t) => t.len(),
+                GCRefData::STRING(s) => s.s.chars().count(),
+                // the engine checks the argument has a length before calling
+                _ => panic!(),
+            };
+            return Object::I64(len.try_into().unwrap());
         }
         _ => panic!(),
     };
@@ -1070,6 +1805,8 @@ pub struct ExecutionEngine {
     pub running: bool,
     pub shared_execution_context: SharedExecutionContext,
     pub environment: ProcessContext,
+    // the objects whose __str is running, so one that prints itself isn't stringified by it again
+    stringifying: Vec<usize>,
 }
 
 #[derive(Clone)]
@@ -1090,6 +1827,7 @@ impl ExecutionEngine {
             running: true,
             shared_execution_context,
             environment,
+            stringifying: vec![],
         }
     }
 
@@ -1181,6 +1919,20 @@ impl ExecutionEngine {
         self.environment.stack_frames[self.environment.stack_frame_pointer].stack[5] =
             Object::GC_REF(alloc);
 
+        // 6 is gila_socket
+        let alloc_res = self.shared_execution_context.heap.alloc(
+            GCRefData::GILA_ABI_FUNCTION_OBJECT(GilaABIFunctionObject::RUST_CALL_CONVENTION(
+                native_annotations,
+            )),
+            &config,
+        );
+        if alloc_res.is_err() {
+            return Err(alloc_res.err().unwrap());
+        }
+        let alloc = alloc_res.unwrap();
+        self.environment.stack_frames[self.environment.stack_frame_pointer].stack[7] =
+            Object::GC_REF(alloc);
+
         Ok(())
     }
 
@@ -1204,11 +1956,15 @@ impl ExecutionEngine {
                 requires_method_binding: false,
                 method_to_object: None,
                 param_slots: vec![],
+                param_names: vec![],
+                defaults: vec![],
+                variadic: false,
                 bounded_object: None,
                 constants_initialised: false,
+                upvalues: vec![],
+                annotations: None,
             }));
             self.zero_stack();
-            self.init_constants();
         } else {
             // todo this is causing an issue with garbage collection, because we are losing the old
             // stack frame root
@@ -1221,10 +1977,19 @@ impl ExecutionEngine {
             // i think this may actually be okay, because on a module import we just need to take the top level exports!
             self.environment.stack_frames[self.environment.stack_frame_pointer]
                 .instruction_pointer = 0;
-            self.init_constants();
         }
 
-        self.init_builtins(self.config.clone());
+        let mut res = self.init_constants();
+        if res.is_ok() {
+            res = self.init_builtins(self.config.clone());
+        }
+        if let Err(e) = res {
+            return ExecutionResult {
+                result: Err(e),
+                shared_execution_context: self.shared_execution_context.clone(),
+                process_context: self.environment.clone(),
+            };
+        }
 
         let mut reg = 0;
         while self.running {
@@ -1320,6 +2085,8 @@ impl ExecutionEngine {
                                     }
                                     Err(e) => return Err(e),
                                 }
+                            } else {
+                                new_vec.push(item.clone());
                             }
                         }
                         let new_tuple = &GCRefData::TUPLE(new_vec);
@@ -1388,6 +2155,8 @@ impl ExecutionEngine {
                                     }
                                     Err(e) => return Err(e),
                                 }
+                            } else {
+                                new_vec.push(item.clone());
                             }
                         }
                         let new_tuple = &GCRefData::TUPLE(new_vec);
@@ -1423,19 +2192,39 @@ impl ExecutionEngine {
         match instr.op_instruction {
             OpInstruction::RETURN => self.exec_return(instr),
             OpInstruction::TRY => self.exec_try(instr),
-            OpInstruction::EQUAL => self.exec_equal(instr),
-            OpInstruction::NOT_EQUALS => self.exec_nequal(instr),
-            OpInstruction::GREATER_THAN => self.exec_greater(instr),
-            OpInstruction::GREATER_EQUAL => self.exec_greater_equals(instr),
-            OpInstruction::LESS_THAN => self.exec_less_than(instr),
-            OpInstruction::LESS_EQUAL => self.exec_less_equals(instr),
+            OpInstruction::EQUAL => self.exec_overloadable(instr, "__eq", Self::exec_equal),
+            OpInstruction::NOT_EQUALS => self.exec_overloadable(instr, "__ne", Self::exec_nequal),
+            OpInstruction::GREATER_THAN => {
+                self.exec_overloadable(instr, "__gt", Self::exec_greater)
+            }
+            OpInstruction::GREATER_EQUAL => {
+                self.exec_overloadable(instr, "__ge", Self::exec_greater_equals)
+            }
+            OpInstruction::LESS_THAN => self.exec_overloadable(instr, "__lt", Self::exec_less_than),
+            OpInstruction::LESS_EQUAL => {
+                self.exec_overloadable(instr, "__le", Self::exec_less_equals)
+            }
             OpInstruction::LOGICAL_OR => self.exec_logical_or(instr),
+            OpInstruction::LOGICAL_AND => self.exec_logical_and(instr),
+            OpInstruction::NOT => self.exec_not(instr),
+            OpInstruction::NEG => self.exec_neg(instr),
             OpInstruction::BITWISE_OR => self.exec_bitwise_or(instr),
+            OpInstruction::BITWISE_AND => self.exec_integer_op(instr, "&", |a, b| Some(a & b)),
+            OpInstruction::BITWISE_XOR => self.exec_integer_op(instr, "^", |a, b| Some(a ^ b)),
+            OpInstruction::SHIFT_LEFT => self.exec_integer_op(instr, "<<", |a, b| {
+                u32::try_from(b).ok().and_then(|b| a.checked_shl(b))
+            }),
+            OpInstruction::SHIFT_RIGHT => self.exec_integer_op(instr, ">>", |a, b| {
+                u32::try_from(b).ok().and_then(|b| a.checked_shr(b))
+            }),
             OpInstruction::ADDI => self.exec_addi(instr),
             OpInstruction::SUBI => self.exec_subi(instr),
-            OpInstruction::ADD => self.exec_add(instr),
-            OpInstruction::MUL => self.exec_mul(instr),
-            OpInstruction::DIV => self.exec_mul(instr),
+            OpInstruction::ADD => self.exec_overloadable(instr, "__add", Self::exec_add),
+            OpInstruction::SUB => self.exec_overloadable(instr, "__sub", Self::exec_sub),
+            OpInstruction::MUL => self.exec_overloadable(instr, "__mul", Self::exec_mul),
+            OpInstruction::DIV => self.exec_overloadable(instr, "__div", Self::exec_div),
+            OpInstruction::MOD => self.exec_overloadable(instr, "__mod", Self::exec_mod),
+            OpInstruction::POW => self.exec_overloadable(instr, "__pow", Self::exec_pow),
             OpInstruction::CALL => self.exec_call(instr),
             OpInstruction::CALL_KW => self.exec_call_kw(instr),
             OpInstruction::NATIVE_CALL => self.exec_native_call(instr),
@@ -1444,14 +2233,34 @@ impl ExecutionEngine {
             OpInstruction::IF_JMP_TRUE => self.exec_if_jmp_true(instr),
             OpInstruction::JMP => self.exec_jmp(instr),
             OpInstruction::FOR_ITER => self.exec_for_iter(instr),
+            OpInstruction::GET_ITER => self.exec_get_iter(instr),
+            OpInstruction::CHECK_ARITY => self.exec_check_arity(instr),
+            OpInstruction::HAS_LENGTH => self.exec_has_length(instr),
+            OpInstruction::IS_INSTANCE => self.exec_is_instance(instr),
+            OpInstruction::CHECK_INSTANCE => self.exec_check_instance(instr),
+            OpInstruction::DESTRUCTURE_FIELD => self.exec_destructure_field(instr),
+            OpInstruction::SAME_TYPE => self.exec_same_type(instr),
+            OpInstruction::MATCH_FAIL => self.exec_match_fail(instr),
             OpInstruction::BUILD_SLICE => self.exec_build_slice(instr),
             OpInstruction::BUILD_TUPLE => self.exec_build_tuple(instr),
+            OpInstruction::BUILD_MAP => self.exec_build_map(instr),
+            OpInstruction::BUILD_STRING => self.exec_build_string(instr),
             OpInstruction::BUILD_FN => self.exec_build_fn(instr),
             OpInstruction::INDEX => self.exec_index(instr),
+            OpInstruction::INDEX_SET => self.exec_index_set(instr),
+            OpInstruction::INDEX_RANGE => self.exec_index_range(instr),
+            OpInstruction::IN => self.exec_in(instr),
             OpInstruction::LOAD_CLOSURE => self.exec_load_closure(instr),
+            OpInstruction::STORE_CLOSURE => self.exec_store_closure(instr),
+            OpInstruction::NEW_CELL => self.exec_new_cell(instr),
+            OpInstruction::LOAD_CELL => self.exec_load_cell(instr),
+            OpInstruction::STORE_CELL => self.exec_store_cell(instr),
+            OpInstruction::LOAD_UPVALUE => self.exec_load_upvalue(instr),
+            OpInstruction::STORE_UPVALUE => self.exec_store_upvalue(instr),
             OpInstruction::STRUCT_ACCESS => self.exec_struct_access(instr),
             OpInstruction::STRUCT_SET => self.exec_struct_set(instr),
             OpInstruction::IMPORT => self.exec_import(instr),
+            OpInstruction::ANNOTATE => self.exec_annotate(instr),
             OpInstruction::MOV => self.exec_mov(instr),
             _ => panic!("unknown instruction {:?}", instr.op_instruction),
         }
@@ -1541,24 +2350,29 @@ impl ExecutionEngine {
 
         let data = gc_ref.unwrap();
 
-        if data.fields.contains_key("Data") {
-            let the_data = data.fields.get("Data").unwrap();
-            stack_set!(self, instr.arg_1, the_data.clone());
+        let error = stack_access!(self, instr.arg_2).clone();
+        let is_error = self.is_instance(result, &error);
+        if is_error.is_err() {
+            return Err(is_error.err().unwrap());
+        }
+
+        if !is_error.unwrap() {
+            // unwrap what the Data variant holds
+            let the_data = match data.fields.get("data") {
+                Some(d) => d.clone(),
+                None => result.clone(),
+            };
+            stack_set!(self, instr.arg_1, the_data);
             increment_ip!(self);
             return Ok(instr.arg_1);
         } else {
             let the_error = result.clone();
             if self.environment.stack_frame_pointer == 0 {
                 return Err(RuntimeError::TOP_LEVEL_ERROR(
-                    data.fields
-                        .get("Error")
-                        .unwrap()
-                        .as_dynamic_object(&self.shared_execution_context)
-                        .unwrap()
-                        .fields
-                        .get("msg")
-                        .unwrap()
-                        .print(&self.shared_execution_context),
+                    match data.fields.get("msg") {
+                        Some(msg) => msg.print(&self.shared_execution_context),
+                        None => the_error.print(&self.shared_execution_context),
+                    },
                 ));
             }
             self.perform_return(Some(the_error))
@@ -1659,11 +2473,69 @@ impl ExecutionEngine {
         Ok(greater.arg_2)
     }
 
+    fn exec_logical_and(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0);
+        let rhs = stack_access!(self, instr.arg_1);
+
+        let result = lhs.truthy(&self.shared_execution_context, &self.environment)
+            && rhs.truthy(&self.shared_execution_context, &self.environment);
+        stack_set!(self, instr.arg_2, Object::BOOL(result));
+        increment_ip!(self);
+
+        Ok(instr.arg_2)
+    }
+
+    fn exec_not(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0);
+
+        let result = !value.truthy(&self.shared_execution_context, &self.environment);
+        stack_set!(self, instr.arg_1, Object::BOOL(result));
+        increment_ip!(self);
+
+        Ok(instr.arg_1)
+    }
+
+    fn exec_neg(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0);
+
+        let result = value.negate(&self.shared_execution_context);
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        stack_set!(self, instr.arg_1, result.unwrap());
+        increment_ip!(self);
+
+        Ok(instr.arg_1)
+    }
+
+    fn exec_integer_op(
+        &mut self,
+        instr: &Instruction,
+        symbol: &str,
+        op: fn(i64, i64) -> Option<i64>,
+    ) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0);
+        let rhs = stack_access!(self, instr.arg_1);
+
+        let result = lhs.integer_op(&self.shared_execution_context, rhs.clone(), symbol, op);
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, result.unwrap());
+        increment_ip!(self);
+
+        Ok(instr.arg_2)
+    }
+
     fn create_dynamic_object(
         &mut self,
         fields: HashMap<String, Object>,
     ) -> Result<Object, RuntimeError> {
-        let gc_ref_data = GCRefData::DYNAMIC_OBJECT(DynamicObject { fields });
+        let gc_ref_data = GCRefData::DYNAMIC_OBJECT(DynamicObject {
+            fields,
+            tag: None,
+            annotations: None,
+        });
         let gc_ref_result = self
             .shared_execution_context
             .heap
@@ -1703,7 +2575,7 @@ impl ExecutionEngine {
             }
             stack_set!(self, instr.arg_2, obj.unwrap());
         } else {
-            todo!()
+            return self.exec_integer_op(instr, "|", |a, b| Some(a | b));
         }
         increment_ip!(self);
 
@@ -1712,7 +2584,7 @@ impl ExecutionEngine {
 
     fn exec_addi(&mut self, addi: &Instruction) -> Result<u8, RuntimeError> {
         self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-            [addi.arg_2 as usize] = Object::I64((addi.arg_0 + addi.arg_1).into());
+            [addi.arg_2 as usize] = Object::I64(addi.arg_0 as i64 + addi.arg_1 as i64);
 
         self.environment.stack_frames[self.environment.stack_frame_pointer].instruction_pointer +=
             1;
@@ -1721,7 +2593,7 @@ impl ExecutionEngine {
     }
     fn exec_subi(&mut self, subi: &Instruction) -> Result<u8, RuntimeError> {
         self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-            [subi.arg_2 as usize] = Object::I64((subi.arg_0 - subi.arg_1).into());
+            [subi.arg_2 as usize] = Object::I64(subi.arg_0 as i64 - subi.arg_1 as i64);
 
         self.environment.stack_frames[self.environment.stack_frame_pointer].instruction_pointer +=
             1;
@@ -1735,6 +2607,38 @@ impl ExecutionEngine {
         let rhs = &self.environment.stack_frames[self.environment.stack_frame_pointer].stack
             [add.arg_1 as usize];
 
+        // joining an object with a string reads it the same way print would, either way round
+        let heap = &self.shared_execution_context.heap;
+        let data = |obj: &Object| match obj {
+            Object::GC_REF(gc_ref) => heap.deref(gc_ref).ok(),
+            _ => None,
+        };
+        let is_string = |data: &Option<GCRefData>| matches!(data, Some(GCRefData::STRING(_)));
+        let (lhs_data, rhs_data) = (data(lhs), data(rhs));
+        let concatenation = is_string(&lhs_data)
+            || (matches!(lhs_data, Some(GCRefData::DYNAMIC_OBJECT(_))) && is_string(&rhs_data));
+        if concatenation {
+            let (lhs, rhs) = (lhs.clone(), rhs.clone());
+            let mut s = "".to_string();
+            for part in [lhs, rhs] {
+                let part = self.stringify(&part, add.arg_2, &mut vec![]);
+                if part.is_err() {
+                    return Err(part.err().unwrap());
+                }
+                s.push_str(&part.unwrap());
+            }
+            let string_obj = self.shared_execution_context.heap.alloc(
+                GCRefData::STRING(StringObject { s: Rc::new(s) }),
+                &self.config,
+            );
+            if string_obj.is_err() {
+                return Err(string_obj.err().unwrap());
+            }
+            stack_set!(self, add.arg_2, Object::GC_REF(string_obj.unwrap()));
+            increment_ip!(self);
+            return Ok(add.arg_2);
+        }
+
         let addition: Result<Object, RuntimeError> = lhs.add(
             &mut self.shared_execution_context,
             &self.config,
@@ -1752,6 +2656,48 @@ impl ExecutionEngine {
         }
     }
 
+    fn exec_sub(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0);
+        let rhs = stack_access!(self, instr.arg_1);
+
+        let result = lhs.sub(&self.shared_execution_context, rhs.clone());
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, result.unwrap());
+        increment_ip!(self);
+
+        Ok(instr.arg_2)
+    }
+
+    fn exec_mod(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0);
+        let rhs = stack_access!(self, instr.arg_1);
+
+        let result = lhs.modulo(&self.shared_execution_context, rhs.clone());
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, result.unwrap());
+        increment_ip!(self);
+
+        Ok(instr.arg_2)
+    }
+
+    fn exec_pow(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0);
+        let rhs = stack_access!(self, instr.arg_1);
+
+        let result = lhs.pow(&self.shared_execution_context, rhs.clone());
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, result.unwrap());
+        increment_ip!(self);
+
+        Ok(instr.arg_2)
+    }
+
     fn exec_mul(&mut self, add: &Instruction) -> Result<u8, RuntimeError> {
         let lhs = &self.environment.stack_frames[self.environment.stack_frame_pointer].stack
             [add.arg_0 as usize];
@@ -1799,14 +2745,17 @@ impl ExecutionEngine {
     }
 
     fn exec_call_kw(&mut self, call: &Instruction) -> Result<u8, RuntimeError> {
-        let fn_object = &self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-            [call.arg_0 as usize];
+        let fn_object = self.environment.stack_frames[self.environment.stack_frame_pointer].stack
+            [call.arg_0 as usize]
+            .clone();
         let gc_ref_object: &GCRef = match &fn_object {
             Object::GC_REF(r) => r,
-            _ => panic!(
-                "exec_call_kw: can only call func or constructor but got {:?}",
-                fn_object
-            ),
+            _ => {
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ))
+            }
         };
         let dereferenced_data = self.shared_execution_context.heap.deref(gc_ref_object);
         if dereferenced_data.is_err() {
@@ -1814,98 +2763,233 @@ impl ExecutionEngine {
             return Err(dereferenced_data.err().unwrap());
         }
 
+        let mut num_positional: usize = 0;
+        let mut spread_flags: Vec<bool> = vec![];
         let mut kwarg_strings: Vec<String> = vec![];
-        match &dereferenced_data.unwrap() {
-            GCRefData::DYNAMIC_OBJECT(d) => {
-                let kwargs_tuple = stack_access!(self, call.arg_1);
-
-                match kwargs_tuple {
-                    Object::GC_REF(kwargs_gc_ref) => {
-                        let res = self.shared_execution_context.heap.deref(kwargs_gc_ref);
-                        if res.is_err() {
-                            return Err(res.err().unwrap());
-                        }
-                        match res.unwrap() {
-                            GCRefData::TUPLE(t) => {
-                                for item in t {
-                                    match item {
-                                        Object::GC_REF(item_gc_ref) => {
-                                            let res = self
-                                                .shared_execution_context
-                                                .heap
-                                                .deref(&item_gc_ref);
-                                            if res.is_err() {
-                                                return Err(res.err().unwrap());
-                                            }
-                                            match res.unwrap() {
-                                                GCRefData::STRING(s) => {
-                                                    kwarg_strings.push(s.s.to_string());
-                                                }
-                                                _ => panic!(),
-                                            }
+        let kwargs_tuple = stack_access!(self, call.arg_1);
+        match kwargs_tuple {
+            Object::GC_REF(kwargs_gc_ref) => {
+                let res = self.shared_execution_context.heap.deref(kwargs_gc_ref);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                match res.unwrap() {
+                    GCRefData::TUPLE(t) => {
+                        for item in t {
+                            match item {
+                                Object::I64(n) => num_positional = n as usize,
+                                Object::BOOL(b) => spread_flags.push(b),
+                                Object::GC_REF(item_gc_ref) => {
+                                    let res =
+                                        self.shared_execution_context.heap.deref(&item_gc_ref);
+                                    if res.is_err() {
+                                        return Err(res.err().unwrap());
+                                    }
+                                    match res.unwrap() {
+                                        GCRefData::STRING(s) => {
+                                            kwarg_strings.push(s.s.to_string());
                                         }
                                         _ => panic!(),
                                     }
                                 }
+                                _ => panic!(),
                             }
-                            _ => panic!(),
                         }
                     }
                     _ => panic!(),
                 }
+            }
+            _ => panic!(),
+        }
 
-                // println!("{:?}", kwarg_strings);
-
-                let mut arg_values: Vec<Object> = vec![];
-                for i in call.arg_2..call.arg_2 + kwarg_strings.len() as u8 {
-                    arg_values.push(stack_access!(self, i).clone());
-                }
-
-                // println!("{:?}", arg_values);
-
-                let destination = call.arg_2 + kwarg_strings.len() as u8;
-
-                let mut fields: HashMap<String, Object> = HashMap::new();
+        let mut positional_values: Vec<Object> = vec![];
+        for i in 0..num_positional {
+            let value = stack_access!(self, call.arg_2 + i as u8).clone();
+            if !spread_flags[i] {
+                positional_values.push(value);
+                continue;
+            }
+            let items = self.spread_items(&value);
+            if items.is_err() {
+                return Err(items.err().unwrap());
+            }
+            positional_values.extend(items.unwrap());
+        }
+        let mut arg_values: Vec<Object> = vec![];
+        let kwargs_start = call.arg_2 + num_positional as u8;
+        for i in kwargs_start..kwargs_start + kwarg_strings.len() as u8 {
+            arg_values.push(stack_access!(self, i).clone());
+        }
 
-                fields.insert("__prototype__".to_string(), fn_object.clone());
+        let destination = kwargs_start + kwarg_strings.len() as u8;
 
-                // todo actually typecheck
-                let mut i = 0;
-                for kwarg in kwarg_strings {
-                    fields.insert(kwarg, arg_values[i].clone());
-                    i += 1;
+        match &dereferenced_data.unwrap() {
+            GCRefData::FN(f) => {
+                let res = self.call_fn(
+                    f,
+                    positional_values,
+                    kwarg_strings.into_iter().zip(arg_values).collect(),
+                    destination,
+                );
+                if res.is_err() {
+                    return Err(res.err().unwrap());
                 }
-
-                let gc_ref = self.shared_execution_context.heap.alloc(
-                    GCRefData::DYNAMIC_OBJECT(DynamicObject { fields }),
-                    &self.config,
+            }
+            GCRefData::DYNAMIC_OBJECT(d) if d.fields.contains_key("__prototype__") => {
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ));
+            }
+            GCRefData::DYNAMIC_OBJECT(d) => {
+                if num_positional > 0 {
+                    return Err(RuntimeError::POSITIONAL_CONSTRUCTOR(
+                        self.current_line(),
+                        self.type_name(&fn_object),
+                    ));
+                }
+                let res = self.construct(
+                    &fn_object,
+                    d,
+                    kwarg_strings.into_iter().zip(arg_values).collect(),
+                    destination,
                 );
-
-                if gc_ref.is_err() {
-                    return Err(gc_ref.err().unwrap());
+                if res.is_err() {
+                    return Err(res.err().unwrap());
                 }
-
-                let gc_ref_res = gc_ref.unwrap();
-
-                stack_set!(self, destination, Object::GC_REF(gc_ref_res.clone()));
+            }
+            GCRefData::GILA_ABI_FUNCTION_OBJECT(native_fn) => {
+                if !kwarg_strings.is_empty() {
+                    return Err(RuntimeError::INVALID_OPERATION(
+                        "native functions can't take keyword arguments".to_string(),
+                    ));
+                }
+                let res = self.check_native_args(native_fn, &positional_values);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                let mut positional_values = positional_values;
+                if is_native_print(native_fn) {
+                    let res = self.stringify_args(positional_values, destination);
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
+                    }
+                    positional_values = res.unwrap();
+                } else if is_native_annotations(native_fn) {
+                    let res = self.annotations_arg(positional_values);
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
+                    }
+                    positional_values = res.unwrap();
+                }
+                let result = unsafe {
+                    native_fn.invoke(
+                        &mut self.shared_execution_context,
+                        &mut self.environment,
+                        positional_values,
+                    )
+                };
+                stack_set!(self, destination, result);
                 increment_ip!(self);
             }
             _ => {
-                panic!()
-                // self.environment.stack_frames[self.environment.stack_frame_pointer]
-                //     .instruction_pointer += 1;
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ));
             }
         }
 
         Ok(0)
     }
 
+    fn check_native_args(
+        &self,
+        native_fn: &GilaABIFunctionObject,
+        args: &[Object],
+    ) -> Result<(), RuntimeError> {
+        if let Some((name, arity)) = native_signature(native_fn) {
+            if args.len() != arity {
+                return Err(RuntimeError::WRONG_ARGUMENT_COUNT(
+                    self.current_line(),
+                    name.to_string(),
+                    arity,
+                    args.len(),
+                ));
+            }
+            if !native_accepts(&self.shared_execution_context, native_fn, &args[0]) {
+                return Err(RuntimeError::INVALID_ARGUMENT(
+                    self.current_line(),
+                    name.to_string(),
+                    args[0].print(&self.shared_execution_context),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // a new instance of the type, which points back at it. instances of an enum variant carry its
+    // tag
+    fn construct(
+        &mut self,
+        typ: &Object,
+        type_object: &DynamicObject,
+        kwargs: Vec<(String, Object)>,
+        destination: u8,
+    ) -> Result<(), RuntimeError> {
+        let declared: Vec<String> = match &type_object.tag {
+            Some(tag) => tag.fields.clone(),
+            None => vec![],
+        };
+        let mut fields: HashMap<String, Object> = HashMap::new();
+        fields.insert("__prototype__".to_string(), typ.clone());
+        // todo actually typecheck
+        for (name, value) in kwargs {
+            if !declared.contains(&name) {
+                return Err(RuntimeError::UNKNOWN_FIELD(
+                    self.current_line(),
+                    self.type_name(typ),
+                    name,
+                ));
+            }
+            fields.insert(name, value);
+        }
+        if let Some(unset) = declared.iter().find(|field| !fields.contains_key(*field)) {
+            return Err(RuntimeError::UNSET_FIELD(
+                self.current_line(),
+                self.type_name(typ),
+                unset.clone(),
+            ));
+        }
+
+        let gc_ref = self.shared_execution_context.heap.alloc(
+            GCRefData::DYNAMIC_OBJECT(DynamicObject {
+                fields,
+                tag: type_object.tag.clone(),
+                annotations: None,
+            }),
+            &self.config,
+        );
+        if gc_ref.is_err() {
+            return Err(gc_ref.err().unwrap());
+        }
+        stack_set!(self, destination, Object::GC_REF(gc_ref.unwrap()));
+        increment_ip!(self);
+        Ok(())
+    }
+
     fn exec_call(&mut self, call: &Instruction) -> Result<u8, RuntimeError> {
-        let fn_object = &self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-            [call.arg_0 as usize];
+        let fn_object = self.environment.stack_frames[self.environment.stack_frame_pointer].stack
+            [call.arg_0 as usize]
+            .clone();
         let gc_ref_object: &GCRef = match &fn_object {
             Object::GC_REF(r) => r,
-            _ => panic!("can only call func or constructor"),
+            _ => {
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ))
+            }
         };
         let dereferenced_data = self.shared_execution_context.heap.deref(gc_ref_object);
         if dereferenced_data.is_err() {
@@ -1922,29 +3006,15 @@ impl ExecutionEngine {
                 // todo ive just added this +1 and i think... this is the return reg?
                 let destination = starting_reg + num_args;
 
-                // fixme this sucks, we shouldn't clone functions it's so expensive
-                // fixme why is this a Box?
-                self.push_stack_frame(Box::new(f.clone()), destination);
-                self.zero_stack();
-                // self.init_constants();
-
-                let mut start = 0;
-                if f.bounded_object.is_some() {
-                    start = 1;
-                    self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-                        [f.param_slots[0] as usize] =
-                        Object::GC_REF(f.bounded_object.clone().unwrap());
-                }
-
-                for i in start..num_args {
-                    let arg_register = starting_reg as usize + i as usize;
-                    let arg = &self.environment.stack_frames
-                        [self.environment.stack_frame_pointer - 1]
-                        .stack[arg_register];
-                    self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-                        [f.param_slots[i as usize] as usize] = arg.clone();
+                let mut args: Vec<Object> = vec![];
+                for i in starting_reg..destination {
+                    args.push(stack_access!(self, i).clone());
                 }
 
+                let res = self.call_fn(f, args, vec![], destination);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
                 return Ok(call.arg_1 + call.arg_2);
             }
 
@@ -1961,6 +3031,23 @@ impl ExecutionEngine {
                         .stack[arg_register];
                     args.push(arg.clone());
                 }
+                let res = self.check_native_args(native_fn, &args);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                if is_native_print(native_fn) {
+                    let res = self.stringify_args(args, destination);
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
+                    }
+                    args = res.unwrap();
+                } else if is_native_annotations(native_fn) {
+                    let res = self.annotations_arg(args);
+                    if res.is_err() {
+                        return Err(res.err().unwrap());
+                    }
+                    args = res.unwrap();
+                }
 
                 let result = unsafe {
                     native_fn.invoke(
@@ -1972,8 +3059,29 @@ impl ExecutionEngine {
                 stack_set!(self, destination, result);
                 increment_ip!(self);
             }
+            GCRefData::DYNAMIC_OBJECT(d) if d.fields.contains_key("__prototype__") => {
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ));
+            }
+            GCRefData::DYNAMIC_OBJECT(d) => {
+                if call.arg_2 > 0 {
+                    return Err(RuntimeError::POSITIONAL_CONSTRUCTOR(
+                        self.current_line(),
+                        self.type_name(&fn_object),
+                    ));
+                }
+                let res = self.construct(&fn_object, d, vec![], call.arg_1);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+            }
             _ => {
-                panic!("exec_call: must be fn or native fn but got {:?}", unrwapped);
+                return Err(RuntimeError::NOT_CALLABLE(
+                    self.current_line(),
+                    fn_object.print(&self.shared_execution_context),
+                ));
             }
         }
 
@@ -2081,11 +3189,11 @@ impl ExecutionEngine {
     }
 
     fn exec_for_iter(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
-        let iterator_obj = stack_access!(self, instr.arg_0);
+        let iterator_obj = stack_access!(self, instr.arg_0).clone();
 
-        match iterator_obj {
+        match iterator_obj.clone() {
             Object::GC_REF(gc_ref) => {
-                let res = self.shared_execution_context.heap.deref(gc_ref);
+                let res = self.shared_execution_context.heap.deref(&gc_ref);
                 if res.is_err() {
                     return Err(res.err().unwrap());
                 }
@@ -2145,7 +3253,7 @@ impl ExecutionEngine {
                                 }
                                 match res.unwrap() {
                                     GCRefData::FN(method) => {
-                                        let result = self.execute_fn(&method, instr.arg_2);
+                                        let result = self.execute_fn(&method, vec![], instr.arg_2);
                                         if result.is_err() {
                                             return Err(result.err().unwrap());
                                         }
@@ -2166,49 +3274,462 @@ impl ExecutionEngine {
                         if done {
                             self.environment.stack_frames[self.environment.stack_frame_pointer]
                                 .instruction_pointer = instr.arg_1 as usize;
+                        } else {
+                            // the iterator has moved on so look at it again for the current value
+                            let iterator_obj = self.shared_execution_context.heap.deref(&gc_ref);
+                            if iterator_obj.is_err() {
+                                return Err(iterator_obj.err().unwrap());
+                            }
+                            if let GCRefData::DYNAMIC_OBJECT(d) = iterator_obj.unwrap() {
+                                if let Some(value) = d.fields.get("value") {
+                                    stack_set!(self, instr.arg_2, value.clone());
+                                }
+                            }
                         }
 
                         return Ok(0);
                         //
                     }
-                    _ => panic!("doing for_iter need dynamic obj but found {:?}", unwrapped),
+                    GCRefData::MAP_ITERATOR(mut iterator) => {
+                        if iterator.counter == iterator.keys.len() {
+                            self.environment.stack_frames[self.environment.stack_frame_pointer]
+                                .instruction_pointer = instr.arg_1 as usize;
+                            return Ok(0);
+                        }
+                        stack_set!(self, instr.arg_2, iterator.keys[iterator.counter].clone());
+                        iterator.counter += 1;
+                        let res = self
+                            .shared_execution_context
+                            .heap
+                            .set(&gc_ref, GCRefData::MAP_ITERATOR(iterator));
+                        if res.is_err() {
+                            return Err(res.err().unwrap());
+                        }
+                        increment_ip!(self);
+                        return Ok(instr.arg_2);
+                    }
+                    _ => {
+                        return Err(RuntimeError::NOT_ITERABLE(
+                            self.current_line(),
+                            iterator_obj.print(&self.shared_execution_context),
+                        ))
+                    }
                 }
             }
-            _ => panic!(
-                "expected iter object to be gc_ref but got {:?}",
-                iterator_obj
-            ),
+            _ => {
+                return Err(RuntimeError::NOT_ITERABLE(
+                    self.current_line(),
+                    iterator_obj.print(&self.shared_execution_context),
+                ))
+            }
         }
     }
 
-    fn execute_fn(
+    fn exec_get_iter(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let iterable = stack_access!(self, instr.arg_0).clone();
+        let mut iterator = iterable.clone();
+        if let Object::GC_REF(gc_ref) = &iterable {
+            let res = self.shared_execution_context.heap.deref(gc_ref);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+            let keys = match res.unwrap() {
+                GCRefData::MAP(m) => Some(m.entries.into_iter().map(|(key, _)| key).collect()),
+                GCRefData::SLICE(s) => Some(s.s),
+                _ => None,
+            };
+            if let Some(keys) = keys {
+                let alloc = self.shared_execution_context.heap.alloc(
+                    GCRefData::MAP_ITERATOR(MapIteratorObject { keys, counter: 0 }),
+                    &self.config,
+                );
+                if alloc.is_err() {
+                    return Err(alloc.err().unwrap());
+                }
+                iterator = Object::GC_REF(alloc.unwrap());
+            }
+        }
+        stack_set!(self, instr.arg_1, iterator);
+        increment_ip!(self);
+        Ok(instr.arg_1)
+    }
+
+    fn exec_check_arity(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        let found = match &value {
+            Object::GC_REF(gc_ref) => {
+                let res = self.shared_execution_context.heap.deref(gc_ref);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                match res.unwrap() {
+                    GCRefData::TUPLE(t) => Some(t.len()),
+                    GCRefData::SLICE(s) => Some(s.s.len()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if found.is_none() {
+            return Err(RuntimeError::CANNOT_DESTRUCTURE(
+                self.current_line(),
+                instr.arg_2 as usize,
+                "a tuple or list".to_string(),
+                value.print(&self.shared_execution_context),
+            ));
+        }
+        let found = found.unwrap();
+
+        if found != instr.arg_1 as usize {
+            return Err(RuntimeError::DESTRUCTURE_MISMATCH(
+                self.current_line(),
+                instr.arg_1 as usize,
+                found,
+            ));
+        }
+        increment_ip!(self);
+        Ok(instr.arg_0)
+    }
+
+    fn exec_has_length(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        let length = match &value {
+            Object::GC_REF(gc_ref) => {
+                let res = self.shared_execution_context.heap.deref(gc_ref);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+                match res.unwrap() {
+                    GCRefData::TUPLE(t) => Some(t.len()),
+                    GCRefData::SLICE(s) => Some(s.s.len()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        stack_set!(
+            self,
+            instr.arg_2,
+            Object::BOOL(length == Some(instr.arg_1 as usize))
+        );
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
+    fn exec_is_instance(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        let typ = stack_access!(self, instr.arg_1).clone();
+        let is_instance = self.is_instance(&value, &typ);
+        if is_instance.is_err() {
+            return Err(is_instance.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, Object::BOOL(is_instance.unwrap()));
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
+    fn exec_check_instance(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        let typ = stack_access!(self, instr.arg_1).clone();
+        let is_instance = self.is_instance(&value, &typ);
+        if is_instance.is_err() {
+            return Err(is_instance.err().unwrap());
+        }
+        if !is_instance.unwrap() {
+            return Err(RuntimeError::CANNOT_DESTRUCTURE(
+                self.current_line(),
+                instr.arg_2 as usize,
+                format!("a {}", self.type_name(&typ)),
+                value.print(&self.shared_execution_context),
+            ));
+        }
+        increment_ip!(self);
+        Ok(instr.arg_0)
+    }
+
+    fn exec_destructure_field(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let obj = stack_access!(self, instr.arg_0).clone();
+        let field = stack_access!(self, instr.arg_1).print(&self.shared_execution_context);
+        // the instance check before this means it's an object of the pattern's type
+        let fields = match &obj {
+            Object::GC_REF(gc_ref) => match self.shared_execution_context.heap.deref(gc_ref) {
+                Ok(GCRefData::DYNAMIC_OBJECT(d)) => d.fields,
+                Ok(_) => HashMap::new(),
+                Err(e) => return Err(e),
+            },
+            _ => HashMap::new(),
+        };
+        let value = fields.get(&field).filter(|_| field != "__prototype__");
+        if value.is_none() {
+            let type_name = match fields.get("__prototype__") {
+                Some(prototype) => self.type_name(prototype),
+                None => obj.print(&self.shared_execution_context),
+            };
+            return Err(RuntimeError::MISSING_FIELD(
+                self.current_line(),
+                type_name,
+                field,
+            ));
+        }
+        stack_set!(self, instr.arg_2, value.unwrap().clone());
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
+    // what a type or enum variant is called, for errors
+    fn type_name(&self, typ: &Object) -> String {
+        if let Object::GC_REF(gc_ref) = typ {
+            if let Ok(GCRefData::DYNAMIC_OBJECT(d)) =
+                self.shared_execution_context.heap.deref(gc_ref)
+            {
+                if let Some(tag) = d.tag {
+                    return tag.name.to_string();
+                }
+            }
+        }
+        typ.print(&self.shared_execution_context)
+    }
+
+    fn is_instance(&self, value: &Object, typ: &Object) -> Result<bool, RuntimeError> {
+        let type_index = match typ {
+            Object::GC_REF(gc_ref) => gc_ref.index,
+            _ => {
+                return Err(RuntimeError::INVALID_OPERATION(format!(
+                    "cannot match against {} as it is not a type",
+                    typ.print(&self.shared_execution_context)
+                )))
+            }
+        };
+
+        let type_tag = match self.shared_execution_context.heap.deref(&GCRef {
+            index: type_index,
+            marked: false,
+        }) {
+            Ok(GCRefData::DYNAMIC_OBJECT(d)) => d.tag,
+            Ok(_) => None,
+            Err(e) => return Err(e),
+        };
+
+        // enum values share their variant's tag, anything else points back at the type that
+        // constructed it
+        let mut is_instance = false;
+        if let Object::GC_REF(gc_ref) = &value {
+            let res = self.shared_execution_context.heap.deref(gc_ref);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+            if let GCRefData::DYNAMIC_OBJECT(d) = res.unwrap() {
+                is_instance = match (&type_tag, &d.tag) {
+                    (Some(type_tag), Some(tag)) => Rc::ptr_eq(type_tag, tag),
+                    (Some(_), None) => false,
+                    _ => match d.fields.get("__prototype__") {
+                        Some(Object::GC_REF(prototype)) => prototype.index == type_index,
+                        _ => false,
+                    },
+                };
+            }
+        }
+        Ok(is_instance)
+    }
+
+    fn exec_same_type(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let lhs = stack_access!(self, instr.arg_0).clone();
+        let rhs = stack_access!(self, instr.arg_1).clone();
+        let same_type = match (&lhs, &rhs) {
+            (Object::GC_REF(lhs_ref), Object::GC_REF(rhs_ref)) => {
+                let lhs_data = self.shared_execution_context.heap.deref(lhs_ref);
+                if lhs_data.is_err() {
+                    return Err(lhs_data.err().unwrap());
+                }
+                let rhs_data = self.shared_execution_context.heap.deref(rhs_ref);
+                if rhs_data.is_err() {
+                    return Err(rhs_data.err().unwrap());
+                }
+                std::mem::discriminant(&lhs_data.unwrap())
+                    == std::mem::discriminant(&rhs_data.unwrap())
+            }
+            // ints and floats compare with each other so either can match a range of the other
+            (Object::I64(_), Object::F64(_)) | (Object::F64(_), Object::I64(_)) => true,
+            _ => std::mem::discriminant(&lhs) == std::mem::discriminant(&rhs),
+        };
+        stack_set!(self, instr.arg_2, Object::BOOL(same_type));
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
+    fn exec_match_fail(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        return Err(RuntimeError::NO_MATCH(
+            self.current_line(),
+            instr.arg_1 as usize,
+            value.print(&self.shared_execution_context),
+        ));
+    }
+
+    // the source line of the instruction being executed
+    fn current_line(&self) -> usize {
+        let frame = &self.environment.stack_frames[self.environment.stack_frame_pointer];
+        frame.fn_object.chunk.debug_line_info[frame.instruction_pointer]
+    }
+
+    // gives an error from somewhere that doesn't know where it is the position of the instruction
+    // being executed
+    fn position_error(&self, e: RuntimeError) -> RuntimeError {
+        let frame = &self.environment.stack_frames[self.environment.stack_frame_pointer];
+        let position = frame
+            .fn_object
+            .chunk
+            .debug_position_info
+            .get(&frame.instruction_pointer)
+            .copied();
+        return match e {
+            RuntimeError::OUT_OF_BOUNDS(None, index, len) => {
+                RuntimeError::OUT_OF_BOUNDS(position, index, len)
+            }
+            RuntimeError::INVALID_INDEX(None, index) => {
+                RuntimeError::INVALID_INDEX(position, index)
+            }
+            RuntimeError::REVERSED_RANGE(None, start, end) => {
+                RuntimeError::REVERSED_RANGE(position, start, end)
+            }
+            e => e,
+        };
+    }
+
+    // the values a spread argument i.e. f(...xs) is taken apart into
+    fn spread_items(&self, value: &Object) -> Result<Vec<Object>, RuntimeError> {
+        if let Object::GC_REF(gc_ref) = value {
+            let res = self.shared_execution_context.heap.deref(gc_ref);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+            match res.unwrap() {
+                GCRefData::SLICE(s) => return Ok(s.s.clone()),
+                GCRefData::TUPLE(t) => return Ok(t.clone()),
+                _ => {}
+            }
+        }
+        return Err(RuntimeError::INVALID_OPERATION(format!(
+            "can only spread a slice or tuple but got {}",
+            value.print(&self.shared_execution_context)
+        )));
+    }
+
+    // match up the arguments of a call with the function's parameters, then enter it. the object
+    // a method is bound to is always its first parameter, and a variadic function's extra
+    // positional arguments are collected into a slice for its last
+    fn call_fn(
         &mut self,
-        fn_object: &FnObject,
+        f: &FnObject,
+        positional: Vec<Object>,
+        keywords: Vec<(String, Object)>,
         destination: u8,
-    ) -> Result<Option<Object>, RuntimeError> {
-        self.push_stack_frame(Box::new(fn_object.clone()), destination);
-        self.zero_stack();
-        // self.init_constants();
+    ) -> Result<(), RuntimeError> {
+        let first_param = if f.bounded_object.is_some() { 1 } else { 0 };
+        let num_params = f.param_slots.len() - first_param - f.variadic as usize;
+        let mut positional = positional;
+        let mut rest: Vec<Object> = vec![];
+        if f.variadic && positional.len() > num_params {
+            rest = positional.split_off(num_params);
+        }
+        if positional.len() > num_params {
+            return Err(RuntimeError::TOO_MANY_ARGUMENTS(
+                self.current_line(),
+                f.name.to_string(),
+                num_params,
+                positional.len() + keywords.len(),
+            ));
+        }
 
-        if fn_object.bounded_object.is_some() {
-            self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-                [fn_object.param_slots[0] as usize] =
-                Object::GC_REF(fn_object.bounded_object.clone().unwrap());
+        let mut values: Vec<Option<Object>> = vec![None; num_params];
+        for (i, value) in positional.into_iter().enumerate() {
+            values[i] = Some(value);
+        }
+        for (name, value) in keywords {
+            let index = f.param_names[first_param..first_param + num_params]
+                .iter()
+                .position(|param| param.as_deref() == Some(name.as_str()));
+            match index {
+                Some(i) if values[i].is_some() => {
+                    return Err(RuntimeError::DUPLICATE_ARGUMENT(
+                        self.current_line(),
+                        f.name.to_string(),
+                        name,
+                    ))
+                }
+                Some(i) => values[i] = Some(value),
+                None => {
+                    return Err(RuntimeError::UNKNOWN_ARGUMENT(
+                        self.current_line(),
+                        f.name.to_string(),
+                        name,
+                    ))
+                }
+            }
+        }
 
-            let v = stack_access!(self, fn_object.param_slots[0]);
+        let first_default = first_param + num_params - f.defaults.len();
+        let mut args: Vec<Object> = vec![];
+        for (i, value) in values.into_iter().enumerate() {
+            match value {
+                Some(value) => args.push(value),
+                None if i + first_param >= first_default => {
+                    args.push(f.defaults[i + first_param - first_default].clone())
+                }
+                None => {
+                    return Err(RuntimeError::MISSING_ARGUMENT(
+                        self.current_line(),
+                        f.name.to_string(),
+                        f.param_names[i + first_param]
+                            .clone()
+                            .unwrap_or(format!("#{}", i + first_param)),
+                    ))
+                }
+            }
         }
 
-        // todo everything up until this point... self is correct.
+        if f.variadic {
+            let rest_slice = self
+                .shared_execution_context
+                .heap
+                .alloc(GCRefData::SLICE(SliceObject { s: rest }), &self.config);
+            if rest_slice.is_err() {
+                return Err(rest_slice.err().unwrap());
+            }
+            args.push(Object::GC_REF(rest_slice.unwrap()));
+        }
+
+        // fixme this sucks, we shouldn't clone functions it's so expensive
+        // fixme why is this a Box?
+        self.push_stack_frame(Box::new(f.clone()), destination);
+        self.zero_stack();
 
-        // todo pass other args
+        if f.bounded_object.is_some() {
+            stack_set!(
+                self,
+                f.param_slots[0],
+                Object::GC_REF(f.bounded_object.clone().unwrap())
+            );
+        }
+        for (i, arg) in args.into_iter().enumerate() {
+            stack_set!(self, f.param_slots[i + first_param], arg);
+        }
+        Ok(())
+    }
+
+    fn execute_fn(
+        &mut self,
+        fn_object: &FnObject,
+        args: Vec<Object>,
+        destination: u8,
+    ) -> Result<Option<Object>, RuntimeError> {
+        let res = self.call_fn(fn_object, args, vec![], destination);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
 
         let current_stack_frame = self.environment.stack_frame_pointer;
         while self.running {
-            // we have returned
-            if self.environment.stack_frame_pointer != current_stack_frame {
-                let result = stack_access!(self, destination);
-                return Ok(Some(result.clone()));
-            }
             let instr = {
                 let current_frame =
                     &self.environment.stack_frames[self.environment.stack_frame_pointer];
@@ -2222,6 +3743,13 @@ impl ExecutionEngine {
                 return Err(e);
             }
 
+            // we have returned, which comes first as the caller may have nothing left to run. calls
+            // the function makes push frames above it, so only a lower frame means it's done
+            if self.environment.stack_frame_pointer < current_stack_frame {
+                let result = stack_access!(self, destination);
+                return Ok(Some(result.clone()));
+            }
+
             if self.environment.stack_frames[self.environment.stack_frame_pointer]
                 .instruction_pointer
                 == self.environment.stack_frames[self.environment.stack_frame_pointer]
@@ -2292,6 +3820,56 @@ impl ExecutionEngine {
         Ok(instr.arg_2)
     }
 
+    fn exec_build_map(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let mut map = MapObject::new();
+        for i in 0..instr.arg_1 as usize {
+            let key = stack_access!(self, instr.arg_0 as usize + i * 2).clone();
+            let value = stack_access!(self, instr.arg_0 as usize + i * 2 + 1).clone();
+            let map_key = key.map_key(&self.shared_execution_context);
+            if map_key.is_err() {
+                return Err(map_key.err().unwrap());
+            }
+            map.insert(map_key.unwrap(), key, value);
+        }
+
+        let map_obj = self
+            .shared_execution_context
+            .heap
+            .alloc(GCRefData::MAP(map), &self.config);
+        if map_obj.is_err() {
+            return Err(map_obj.err().unwrap());
+        }
+
+        stack_set!(self, instr.arg_2, Object::GC_REF(map_obj.unwrap()));
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
+    fn exec_build_string(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let mut s = "".to_string();
+        for i in 0..instr.arg_1 {
+            let part = stack_access!(self, instr.arg_0 as usize + i as usize).clone();
+            let part = self.stringify(&part, instr.arg_2, &mut vec![]);
+            if part.is_err() {
+                return Err(part.err().unwrap());
+            }
+            s.push_str(&part.unwrap());
+        }
+
+        let string_obj = self.shared_execution_context.heap.alloc(
+            GCRefData::STRING(StringObject { s: Rc::new(s) }),
+            &self.config,
+        );
+
+        if string_obj.is_err() {
+            return Err(string_obj.err().unwrap());
+        }
+
+        stack_set!(self, instr.arg_2, Object::GC_REF(string_obj.unwrap()));
+        increment_ip!(self);
+        Ok(instr.arg_2)
+    }
+
     fn init_tuple(
         &mut self,
         t: &Vec<Object>,
@@ -2332,14 +3910,14 @@ impl ExecutionEngine {
     }
 
     fn exec_build_fn(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
-        let fn_ref = stack_access!(self, instr.arg_0).clone();
+        let mut fn_ref = stack_access!(self, instr.arg_0).clone();
         let fn_result = fn_ref.as_fn(&self.shared_execution_context);
 
         if fn_result.is_err() {
             return Err(fn_result.err().unwrap());
         }
 
-        let f = fn_result.unwrap();
+        let mut f = fn_result.unwrap();
         // initialise constants
 
         if !f.constants_initialised {
@@ -2349,15 +3927,56 @@ impl ExecutionEngine {
             if new_f_result.is_err() {
                 return Err(new_f_result.err().unwrap());
             }
-            let res = self.shared_execution_context.heap.set(
-                &fn_ref.as_gc_ref_index().unwrap(),
-                GCRefData::FN(new_f_result.unwrap()),
-            );
+            f = new_f_result.unwrap();
+            let res = self
+                .shared_execution_context
+                .heap
+                .set(&fn_ref.as_gc_ref_index().unwrap(), GCRefData::FN(f.clone()));
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
+        }
+
+        if instr.arg_2 > 0 {
+            f.defaults = vec![];
+            for i in instr.arg_1..instr.arg_1 + instr.arg_2 {
+                f.defaults.push(stack_access!(self, i).clone());
+            }
+            let res = self
+                .shared_execution_context
+                .heap
+                .set(&fn_ref.as_gc_ref_index().unwrap(), GCRefData::FN(f.clone()));
             if res.is_err() {
                 return Err(res.err().unwrap());
             }
         }
 
+        // each closure gets its own function object holding the cells it captured
+        if f.chunk.upvalues.len() > 0 {
+            let mut closure = f.clone();
+            for (_, source) in &f.chunk.upvalues {
+                let upvalue = match source {
+                    UpvalueSource::LOCAL(reg) => stack_access!(self, *reg).clone(),
+                    UpvalueSource::UPVALUE(idx) => self.environment.stack_frames
+                        [self.environment.stack_frame_pointer]
+                        .fn_object
+                        .upvalues[*idx as usize]
+                        .clone(),
+                };
+                closure.upvalues.push(upvalue);
+            }
+            let alloc = self
+                .shared_execution_context
+                .heap
+                .alloc(GCRefData::FN(closure), &self.config);
+            if alloc.is_err() {
+                return Err(alloc.err().unwrap());
+            }
+            // methods are bound as the closure so they have their upvalues too
+            fn_ref = Object::GC_REF(alloc.unwrap());
+            stack_set!(self, instr.arg_0, fn_ref.clone());
+        }
+
         if f.requires_method_binding {
             let obj_to_bind_to = stack_access!(self, f.method_to_object.unwrap());
 
@@ -2395,21 +4014,63 @@ impl ExecutionEngine {
     }
 
     fn exec_index(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
-        // todo for now only slices can be indexed
         let obj_to_index = self.environment.stack_frames[self.environment.stack_frame_pointer]
             .stack[instr.arg_0 as usize]
             .clone();
-        let index_obj = &self.environment.stack_frames[self.environment.stack_frame_pointer].stack
-            [instr.arg_1 as usize];
-        let res = obj_to_index.index(&self.shared_execution_context, index_obj);
+        let index_obj = self.environment.stack_frames[self.environment.stack_frame_pointer].stack
+            [instr.arg_1 as usize]
+            .clone();
+        let res = obj_to_index.index(&mut self.shared_execution_context, &self.config, &index_obj);
         if res.is_err() {
-            return Err(res.err().unwrap());
+            return Err(self.position_error(res.err().unwrap()));
+        }
+        stack_set!(self, instr.arg_2, res.unwrap());
+        increment_ip!(self);
+        return Ok(instr.arg_2);
+    }
+
+    fn exec_index_range(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let obj = stack_access!(self, instr.arg_0).clone();
+        let start = stack_access!(self, instr.arg_1).clone();
+        let end = stack_access!(self, instr.arg_1 + 1).clone();
+        let res = obj.index_range(
+            &mut self.shared_execution_context,
+            &self.config,
+            &start,
+            &end,
+        );
+        if res.is_err() {
+            return Err(self.position_error(res.err().unwrap()));
         }
         stack_set!(self, instr.arg_2, res.unwrap());
         increment_ip!(self);
         return Ok(instr.arg_2);
     }
 
+    fn exec_index_set(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let obj = stack_access!(self, instr.arg_0).clone();
+        let index_obj = stack_access!(self, instr.arg_1).clone();
+        let value = stack_access!(self, instr.arg_2).clone();
+        let res = obj.index_set(&mut self.shared_execution_context, &index_obj, value);
+        if res.is_err() {
+            return Err(self.position_error(res.err().unwrap()));
+        }
+        increment_ip!(self);
+        return Ok(instr.arg_2);
+    }
+
+    fn exec_in(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let item = stack_access!(self, instr.arg_0);
+        let container = stack_access!(self, instr.arg_1);
+        let res = item.is_in(&self.shared_execution_context, container);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        stack_set!(self, instr.arg_2, Object::BOOL(res.unwrap()));
+        increment_ip!(self);
+        return Ok(instr.arg_2);
+    }
+
     fn exec_load_closure(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
         let val = &self.environment.stack_frames[instr.arg_0 as usize].stack[instr.arg_1 as usize];
         stack_set!(self, instr.arg_2, val.clone());
@@ -2417,6 +4078,355 @@ impl ExecutionEngine {
         Ok(0)
     }
 
+    fn exec_store_closure(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let val = stack_access!(self, instr.arg_2).clone();
+        self.environment.stack_frames[instr.arg_0 as usize].stack[instr.arg_1 as usize] = val;
+        increment_ip!(self);
+        Ok(0)
+    }
+
+    fn exec_annotate(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let target = stack_access!(self, instr.arg_0).clone();
+        let name = stack_access!(self, instr.arg_1).clone();
+        let args = stack_access!(self, instr.arg_2).clone();
+        let map_ref = self.annotations_of(&target);
+        if map_ref.is_err() {
+            return Err(map_ref.err().unwrap());
+        }
+        let map_ref = map_ref.unwrap();
+        let key = name.map_key(&self.shared_execution_context);
+        if key.is_err() {
+            return Err(key.err().unwrap());
+        }
+        let map = self.shared_execution_context.heap.deref(&map_ref);
+        if map.is_err() {
+            return Err(map.err().unwrap());
+        }
+        let GCRefData::MAP(mut map) = map.unwrap() else {
+            return Err(RuntimeError::INVALID_GC_REF);
+        };
+        map.insert(key.unwrap(), name, args);
+        let res = self
+            .shared_execution_context
+            .heap
+            .set(&map_ref, GCRefData::MAP(map));
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        increment_ip!(self);
+        Ok(instr.arg_0)
+    }
+
+    // the map of annotations on a function or type, made the first time it's asked for
+    fn annotations_of(&mut self, obj: &Object) -> Result<GCRef, RuntimeError> {
+        let not_annotatable = RuntimeError::INVALID_OPERATION(
+            "only functions and types have annotations".to_string(),
+        );
+        let Object::GC_REF(gc_ref) = obj else {
+            return Err(not_annotatable);
+        };
+        let data = self.shared_execution_context.heap.deref(gc_ref);
+        if data.is_err() {
+            return Err(data.err().unwrap());
+        }
+        let data = data.unwrap();
+        let existing = match &data {
+            GCRefData::FN(f) => f.annotations.clone(),
+            GCRefData::DYNAMIC_OBJECT(d) => d.annotations.clone(),
+            _ => return Err(not_annotatable),
+        };
+        if existing.is_some() {
+            return Ok(existing.unwrap());
+        }
+
+        let map_ref = self
+            .shared_execution_context
+            .heap
+            .alloc(GCRefData::MAP(MapObject::new()), &self.config);
+        if map_ref.is_err() {
+            return Err(map_ref.err().unwrap());
+        }
+        let map_ref = map_ref.unwrap();
+        let data = match data {
+            GCRefData::FN(mut f) => {
+                f.annotations = Some(map_ref.clone());
+                GCRefData::FN(f)
+            }
+            GCRefData::DYNAMIC_OBJECT(mut d) => {
+                d.annotations = Some(map_ref.clone());
+                GCRefData::DYNAMIC_OBJECT(d)
+            }
+            _ => return Err(not_annotatable),
+        };
+        let res = self.shared_execution_context.heap.set(gc_ref, data);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        Ok(map_ref)
+    }
+
+    fn annotations_arg(&mut self, args: Vec<Object>) -> Result<Vec<Object>, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::INVALID_OPERATION(
+                "annotations takes one argument".to_string(),
+            ));
+        }
+        let map_ref = self.annotations_of(&args[0]);
+        if map_ref.is_err() {
+            return Err(map_ref.err().unwrap());
+        }
+        Ok(vec![Object::GC_REF(map_ref.unwrap())])
+    }
+
+    fn exec_new_cell(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let value = stack_access!(self, instr.arg_0).clone();
+        let alloc = self
+            .shared_execution_context
+            .heap
+            .alloc(GCRefData::CELL(value), &self.config);
+        if alloc.is_err() {
+            return Err(alloc.err().unwrap());
+        }
+        stack_set!(self, instr.arg_0, Object::GC_REF(alloc.unwrap()));
+        increment_ip!(self);
+        Ok(instr.arg_0)
+    }
+
+    fn read_cell(&self, cell: &Object) -> Result<Object, RuntimeError> {
+        let gc_ref = cell.as_gc_ref(&self.shared_execution_context);
+        if gc_ref.is_err() {
+            return Err(gc_ref.err().unwrap());
+        }
+        match gc_ref.unwrap() {
+            GCRefData::CELL(value) => Ok(value),
+            other => Err(RuntimeError::INVALID_OPERATION(format!(
+                "expected a cell but got {:?}",
+                other
+            ))),
+        }
+    }
+
+    fn write_cell(&mut self, cell: &Object, value: Object) -> Result<(), RuntimeError> {
+        let gc_ref = cell.as_gc_ref_index();
+        if gc_ref.is_err() {
+            return Err(gc_ref.err().unwrap());
+        }
+        self.shared_execution_context
+            .heap
+            .set(&gc_ref.unwrap(), GCRefData::CELL(value))
+    }
+
+    fn exec_load_cell(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let cell = stack_access!(self, instr.arg_0).clone();
+        let value = self.read_cell(&cell);
+        if value.is_err() {
+            return Err(value.err().unwrap());
+        }
+        stack_set!(self, instr.arg_1, value.unwrap());
+        increment_ip!(self);
+        Ok(instr.arg_1)
+    }
+
+    fn exec_store_cell(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let cell = stack_access!(self, instr.arg_0).clone();
+        let value = stack_access!(self, instr.arg_1).clone();
+        let res = self.write_cell(&cell, value);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        increment_ip!(self);
+        Ok(instr.arg_0)
+    }
+
+    fn exec_load_upvalue(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let cell = self.environment.stack_frames[self.environment.stack_frame_pointer]
+            .fn_object
+            .upvalues[instr.arg_0 as usize]
+            .clone();
+        let value = self.read_cell(&cell);
+        if value.is_err() {
+            return Err(value.err().unwrap());
+        }
+        stack_set!(self, instr.arg_1, value.unwrap());
+        increment_ip!(self);
+        Ok(instr.arg_1)
+    }
+
+    fn exec_store_upvalue(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
+        let cell = self.environment.stack_frames[self.environment.stack_frame_pointer]
+            .fn_object
+            .upvalues[instr.arg_0 as usize]
+            .clone();
+        let value = stack_access!(self, instr.arg_1).clone();
+        let res = self.write_cell(&cell, value);
+        if res.is_err() {
+            return Err(res.err().unwrap());
+        }
+        increment_ip!(self);
+        Ok(0)
+    }
+
+    // user types choose what an operator does with a method like __add(self, other) on the left
+    // hand side, anything else goes to the builtin
+    fn exec_overloadable(
+        &mut self,
+        instr: &Instruction,
+        method: &str,
+        builtin: fn(&mut Self, &Instruction) -> Result<u8, RuntimeError>,
+    ) -> Result<u8, RuntimeError> {
+        // the fast path, only things on the heap can have methods
+        let Object::GC_REF(gc_ref) = stack_access!(self, instr.arg_0).clone() else {
+            return builtin(self, instr);
+        };
+        let obj = match self.shared_execution_context.heap.deref(&gc_ref) {
+            Ok(GCRefData::DYNAMIC_OBJECT(d)) => d,
+            Ok(_) => return builtin(self, instr),
+            Err(e) => return Err(e),
+        };
+        let other = stack_access!(self, instr.arg_1).clone();
+        let mut result = self.call_method(&gc_ref, &obj, method, vec![other.clone()], instr.arg_2);
+        // != is the opposite of == unless it's been given its own meaning
+        if method == "__ne" && matches!(result, Ok(None)) {
+            result = match self.call_method(&gc_ref, &obj, "__eq", vec![other], instr.arg_2) {
+                Ok(Some(Object::BOOL(b))) => Ok(Some(Object::BOOL(!b))),
+                other => other,
+            };
+        }
+        if result.is_err() {
+            return Err(result.err().unwrap());
+        }
+        match result.unwrap() {
+            Some(result) => {
+                stack_set!(self, instr.arg_2, result);
+                increment_ip!(self);
+                Ok(instr.arg_2)
+            }
+            None => builtin(self, instr),
+        }
+    }
+
+    // print is native so it can't call __str itself, it's given the strings instead
+    fn stringify_args(
+        &mut self,
+        args: Vec<Object>,
+        scratch: u8,
+    ) -> Result<Vec<Object>, RuntimeError> {
+        let mut strings: Vec<Object> = vec![];
+        for arg in args {
+            // strings and primitives already print the same way
+            let printable = match &arg {
+                Object::GC_REF(gc_ref) => matches!(
+                    self.shared_execution_context.heap.deref(gc_ref),
+                    Ok(GCRefData::STRING(_))
+                ),
+                _ => true,
+            };
+            if printable {
+                strings.push(arg);
+                continue;
+            }
+            let s = self.stringify(&arg, scratch, &mut vec![]);
+            if s.is_err() {
+                return Err(s.err().unwrap());
+            }
+            let string_obj = self.shared_execution_context.heap.alloc(
+                GCRefData::STRING(StringObject {
+                    s: Rc::new(s.unwrap()),
+                }),
+                &self.config,
+            );
+            if string_obj.is_err() {
+                return Err(string_obj.err().unwrap());
+            }
+            strings.push(Object::GC_REF(string_obj.unwrap()));
+        }
+        Ok(strings)
+    }
+
+    // how an object reads in print, interpolation and string concatenation. objects can choose
+    // for themselves with a __str method, whose result goes through scratch, a register in the
+    // current frame which is about to be overwritten anyway
+    fn stringify(
+        &mut self,
+        obj: &Object,
+        scratch: u8,
+        seen: &mut Vec<usize>,
+    ) -> Result<String, RuntimeError> {
+        let Object::GC_REF(gc_ref) = obj else {
+            return Ok(obj.print(&self.shared_execution_context));
+        };
+        if seen.contains(&gc_ref.index) {
+            return Ok("...".to_string());
+        }
+        let data = self.shared_execution_context.heap.deref(gc_ref);
+        if data.is_err() {
+            return Err(data.err().unwrap());
+        }
+        let data = data.unwrap();
+        // inside its own __str an object gets the default formatting
+        let in_own_str = self.stringifying.contains(&gc_ref.index);
+        if let (GCRefData::DYNAMIC_OBJECT(d), false) = (&data, in_own_str) {
+            self.stringifying.push(gc_ref.index);
+            let result = self.call_method(gc_ref, d, "__str", vec![], scratch);
+            self.stringifying.pop();
+            if result.is_err() {
+                return Err(result.err().unwrap());
+            }
+            if let Some(result) = result.unwrap() {
+                if let Object::GC_REF(string) = &result {
+                    if let Ok(GCRefData::STRING(s)) =
+                        self.shared_execution_context.heap.deref(string)
+                    {
+                        return Ok(s.s.to_string());
+                    }
+                }
+                return Err(RuntimeError::INVALID_OPERATION(format!(
+                    "__str should return a string but returned {}",
+                    result.print(&self.shared_execution_context)
+                )));
+            }
+        }
+
+        seen.push(gc_ref.index);
+        let res = data.format(&mut |item| self.stringify(item, scratch, seen));
+        seen.pop();
+        res
+    }
+
+    // calls a method the object's type defines, or gives back None if it doesn't have one. the
+    // caller carries on from where it was once the method returns
+    fn call_method(
+        &mut self,
+        gc_ref: &GCRef,
+        obj: &DynamicObject,
+        name: &str,
+        args: Vec<Object>,
+        destination: u8,
+    ) -> Result<Option<Object>, RuntimeError> {
+        if !obj.fields.contains_key("__prototype__") {
+            return Ok(None);
+        }
+        let method = match self.recursively_access_struct(name.to_string(), obj.clone()) {
+            Ok(Object::GC_REF(method)) => self.shared_execution_context.heap.deref(&method),
+            _ => return Ok(None),
+        };
+        let mut method = match method {
+            Ok(GCRefData::FN(f)) => f,
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if method.requires_method_binding {
+            method.bounded_object = Some(gc_ref.clone());
+        }
+
+        let instruction_pointer =
+            self.environment.stack_frames[self.environment.stack_frame_pointer].instruction_pointer;
+        let result = self.execute_fn(&method, args, destination);
+        self.environment.stack_frames[self.environment.stack_frame_pointer].instruction_pointer =
+            instruction_pointer;
+        result
+    }
+
     fn recursively_access_struct(
         &self,
         field: String,
@@ -2434,9 +4444,10 @@ impl ExecutionEngine {
             }
             let prototype = next_prototype_in_chain.fields.get("__prototype__");
             if prototype.is_none() {
-                return Err(RuntimeError::INVALID_ACCESS(
-                    "__prototype__ is none".to_string(),
-                ));
+                return Err(RuntimeError::INVALID_ACCESS(format!(
+                    "couldn't find field '{}' to access",
+                    field
+                )));
             }
             match prototype.unwrap() {
                 Object::GC_REF(g) => {
@@ -2493,6 +4504,14 @@ impl ExecutionEngine {
                                             .recursively_access_struct(s.s.to_string(), o.clone());
 
                                         if result.is_err() {
+                                            // a module only has what it exports
+                                            if let Some(path) = self.module_path(obj_gc_ref) {
+                                                return Err(RuntimeError::UNKNOWN_EXPORT(
+                                                    self.current_line(),
+                                                    path,
+                                                    s.s.to_string(),
+                                                ));
+                                            }
                                             return Err(result.err().unwrap());
                                         }
 
@@ -2657,7 +4676,6 @@ impl ExecutionEngine {
     }
 
     fn exec_import(&mut self, instr: &Instruction) -> Result<u8, RuntimeError> {
-        // todo
         let import_path = stack_access!(self, instr.arg_0);
         match import_path {
             Object::GC_REF(gc_ref) => {
@@ -2667,169 +4685,174 @@ impl ExecutionEngine {
                 }
                 match data.unwrap() {
                     GCRefData::STRING(s) => {
-                        // paths are the areas that we can find the module
-                        let paths = vec!["./".to_string()];
-                        for path in paths {
-                            let cloned = path.clone();
-                            let split = cloned.split(".").collect::<Vec<&str>>();
-                            let last_module = split[split.len() - 1];
-                            let full_path = path + &s.s.replace(".", "/");
-                            let mut full_path_with_extension = full_path.to_string();
-                            full_path_with_extension.push_str(".gila");
-                            // todo alot of duplicate code here
-                            if fs::metadata(full_path.to_string())
-                                .map(|m| m.is_dir())
-                                .unwrap_or(false)
-                            {
-                                let mut module_objects: HashMap<String, Object> = HashMap::new();
-
-                                for file in fs::read_dir(full_path).unwrap() {
-                                    let f = file.unwrap();
-
-                                    let module_name =
-                                        f.file_name().to_string_lossy().replace(".gila", "");
-
-                                    let normalized_path =
-                                        f.path().to_string_lossy().replace("\\", "/");
-                                    let code = fs::read_to_string(normalized_path.to_string())
-                                        .expect("Unable to read file");
-
-                                    let mut compiler = Compiler::new();
-                                    // todo fix this and set our shared_execution_context t- the result
-                                    let compilation_result = compiler.compile_and_exec(
-                                        f.file_name().into_string().unwrap(),
-                                        CompilerFlags {
-                                            init_builtins: true,
-                                            dump_bytecode: false,
-                                        },
-                                        code,
-                                        self.config.clone(),
-                                        None,
-                                        Some(self.environment.clone()),
-                                        Some(self.shared_execution_context.clone()),
-                                    );
-                                    let imported_process_context =
-                                        compilation_result.execution_result.process_context;
-                                    let imported_codegen_context =
-                                        compilation_result.codegen_result.codegen_context;
-                                    let imported_shared_execution_context = compilation_result
-                                        .execution_result
-                                        .shared_execution_context;
-
-                                    // todo we need to append the shared_execution_context to our current context
-                                    self.shared_execution_context =
-                                        imported_shared_execution_context;
-
-                                    // todo go through all exported variables
-
-                                    let mut exported: HashMap<String, Object> = HashMap::new();
-
-                                    for (key, val) in
-                                        imported_codegen_context.chunks[0].variable_map.clone()
-                                    {
-                                        // lets put the variables in this module
-                                        let val = imported_process_context.stack_frames[0].stack
-                                            [val as usize]
-                                            .clone();
-                                        exported.insert(key.to_string(), val.clone());
-                                    }
-                                    let module_dynamic_object = DynamicObject { fields: exported };
-                                    let module = self.shared_execution_context.heap.alloc(
-                                        GCRefData::DYNAMIC_OBJECT(module_dynamic_object),
-                                        &self.config,
-                                    );
-                                    if module.is_err() {
-                                        return Err(module.err().unwrap());
-                                    }
-                                    module_objects
-                                        .insert(module_name, Object::GC_REF(module.unwrap()));
-                                }
+                        let search_path =
+                            module::search_path(self.shared_execution_context.importing.last());
+                        let module = match module::find(&s.s, &search_path) {
+                            Some(source) => self.import_module(source),
+                            None => return Err(RuntimeError::UNKNOWN_MODULE),
+                        };
+                        if module.is_err() {
+                            return Err(module.err().unwrap());
+                        }
 
-                                let module_dynamic_object = DynamicObject {
-                                    fields: module_objects,
-                                };
-                                let module = self.shared_execution_context.heap.alloc(
-                                    GCRefData::DYNAMIC_OBJECT(module_dynamic_object),
-                                    &self.config,
-                                );
+                        stack_set!(self, instr.arg_1, Object::GC_REF(module.unwrap()));
+                        increment_ip!(self);
+                        return Ok(instr.arg_1);
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+    }
 
-                                if module.is_err() {
-                                    return Err(module.err().unwrap());
-                                }
+    // the file of the module the object is, if it is one
+    fn module_path(&self, gc_ref: &GCRef) -> Option<PathBuf> {
+        return self
+            .shared_execution_context
+            .modules
+            .iter()
+            .find(|(_, module)| module.index == gc_ref.index)
+            .map(|(path, _)| path.clone());
+    }
 
-                                stack_set!(self, instr.arg_1, Object::GC_REF(module.unwrap()));
-                                increment_ip!(self);
-
-                                return Ok(instr.arg_1);
-                            } else if fs::metadata(full_path_with_extension.to_string())
-                                .map(|m| m.is_file())
-                                .unwrap_or(false)
-                            {
-                                //todo
-                                let code = fs::read_to_string(full_path_with_extension.to_string())
-                                    .expect("Unable to read file");
-
-                                // todo get the result context and set it to our context
-                                let mut compiler = Compiler::new();
-                                let compilation_result = compiler.compile_and_exec(
-                                    s.s.to_string(),
-                                    CompilerFlags {
-                                        init_builtins: true,
-                                        dump_bytecode: false,
-                                    },
-                                    code,
-                                    self.config.clone(),
-                                    None,
-                                    Some(self.environment.clone()),
-                                    Some(self.shared_execution_context.clone()),
-                                );
-
-                                self.shared_execution_context =
-                                    compilation_result.execution_result.shared_execution_context;
-
-                                let mut module_objects: HashMap<String, Object> = HashMap::new();
-
-                                for (key, val) in
-                                    compilation_result.codegen_result.codegen_context.chunks[0]
-                                        .variable_map
-                                        .clone()
-                                {
-                                    // lets put the variables in this module
-                                    let val = compilation_result
-                                        .execution_result
-                                        .process_context
-                                        .stack_frames[0]
-                                        .stack[val as usize]
-                                        .clone();
-                                    // println!("exported val {:?}={:?}", key, val);
-                                    module_objects.insert(key.to_string(), val);
-                                    continue;
-                                }
+    // a module is only run the first time it's imported, after that everything importing it shares
+    // the same object
+    fn import_module(&mut self, source: ModuleSource) -> Result<GCRef, RuntimeError> {
+        let path = match &source {
+            ModuleSource::FILE(path) | ModuleSource::PACKAGE(path) => path.clone(),
+        };
+        if let Some(module) = self.shared_execution_context.modules.get(&path) {
+            return Ok(module.clone());
+        }
+        let module = match source {
+            ModuleSource::FILE(path) => self.load_module(&path),
+            ModuleSource::PACKAGE(path) => self.load_package(&path),
+        };
+        if module.is_err() {
+            return Err(module.err().unwrap());
+        }
+        let module = module.unwrap();
+        self.shared_execution_context
+            .modules
+            .insert(path, module.clone());
+        return Ok(module);
+    }
 
-                                let module_dynamic_object = DynamicObject {
-                                    fields: module_objects,
-                                };
-                                let module = self.shared_execution_context.heap.alloc(
-                                    GCRefData::DYNAMIC_OBJECT(module_dynamic_object),
-                                    &self.config,
-                                );
+    // an object holding each file in the directory as a submodule
+    fn load_package(&mut self, path: &Path) -> Result<GCRef, RuntimeError> {
+        let mut module_objects: HashMap<String, Object> = HashMap::new();
+        for file in fs::read_dir(path).unwrap() {
+            let f = file.unwrap();
+            let module_name = f.file_name().to_string_lossy().replace(".gila", "");
+            let submodule = self.import_module(ModuleSource::FILE(module::canonical(&f.path())));
+            if submodule.is_err() {
+                return Err(submodule.err().unwrap());
+            }
+            module_objects.insert(module_name, Object::GC_REF(submodule.unwrap()));
+        }
+        let module_dynamic_object = DynamicObject {
+            fields: module_objects,
+            tag: None,
+            annotations: None,
+        };
+        return self.shared_execution_context.heap.alloc(
+            GCRefData::DYNAMIC_OBJECT(module_dynamic_object),
+            &self.config,
+        );
+    }
 
-                                if module.is_err() {
-                                    return Err(module.err().unwrap());
-                                }
+    fn load_module(&mut self, path: &Path) -> Result<GCRef, RuntimeError> {
+        let importing = &self.shared_execution_context.importing;
+        if let Some(start) = importing.iter().position(|p| p == path) {
+            let mut cycle = importing[start..].to_vec();
+            cycle.push(path.to_path_buf());
+            return Err(RuntimeError::CIRCULAR_IMPORT(cycle));
+        }
+        self.shared_execution_context
+            .importing
+            .push(path.to_path_buf());
+        let module = self.run_module(path);
+        self.shared_execution_context.importing.pop();
+        return module;
+    }
 
-                                stack_set!(self, instr.arg_1, Object::GC_REF(module.unwrap()));
-                                increment_ip!(self);
-                                return Ok(instr.arg_1);
-                            }
-                        }
-                        return Err(RuntimeError::UNKNOWN_MODULE);
+    // compiles and runs the file, returning an object holding what it exports
+    fn run_module(&mut self, path: &Path) -> Result<GCRef, RuntimeError> {
+        let code = fs::read_to_string(path).expect("Unable to read file");
+
+        // the module gets a frame of its own above the importer's, which stay where the collector
+        // can still see what they refer to
+        let mut environment = self.environment.clone();
+        let fn_object = environment.stack_frames[environment.stack_frame_pointer]
+            .fn_object
+            .clone();
+        environment.stack_frames.push(StackFrame {
+            stack: vec![Object::I64(0); 255],
+            fn_object,
+            instruction_pointer: 0,
+            return_register: 0,
+        });
+        environment.stack_frame_pointer = environment.stack_frames.len() - 1;
+
+        // todo get the result context and set it to our context
+        let mut compiler = Compiler::new();
+        let compilation_result = compiler.compile_and_exec(
+            path.to_string_lossy().to_string(),
+            CompilerFlags {
+                init_builtins: true,
+                dump_bytecode: false,
+                test_build: false,
+            },
+            code,
+            self.config.clone(),
+            None,
+            Some(environment),
+            Some(self.shared_execution_context.clone()),
+        );
+        if compilation_result.is_err() {
+            return Err(RuntimeError::COMPILATION_ERROR(
+                path.to_path_buf(),
+                Box::new(compilation_result.err().unwrap()),
+            ));
+        }
+        let compilation_result = compilation_result.unwrap();
+
+        self.shared_execution_context =
+            compilation_result.execution_result.shared_execution_context;
+        if let Err(e) = compilation_result.execution_result.result {
+            return Err(e);
+        }
+
+        let main_chunk = &compilation_result.codegen_result.codegen_context.chunks[0];
+        let process_context = &compilation_result.execution_result.process_context;
+        let frame = &process_context.stack_frames[process_context.stack_frame_pointer];
+        let mut module_objects: HashMap<String, Object> = HashMap::new();
+        for name in compilation_result.exports {
+            // interfaces are only checked at compile time so there's nothing to export
+            if let Some(slot) = main_chunk.variable_map.get(&name) {
+                let mut value = frame.stack[*slot as usize].clone();
+                // globals its functions refer to are in cells
+                if main_chunk.cells.contains(&name) {
+                    let cell_value = self.read_cell(&value);
+                    if cell_value.is_err() {
+                        return Err(cell_value.err().unwrap());
                     }
-                    _ => panic!(),
+                    value = cell_value.unwrap();
                 }
+                module_objects.insert(name.to_string(), value);
             }
-            _ => panic!(),
         }
+
+        let module_dynamic_object = DynamicObject {
+            fields: module_objects,
+            tag: None,
+            annotations: None,
+        };
+        return self.shared_execution_context.heap.alloc(
+            GCRefData::DYNAMIC_OBJECT(module_dynamic_object),
+            &self.config,
+        );
     }
 
     fn mark(&mut self) -> Result<(), RuntimeError> {
@@ -2856,6 +4879,26 @@ impl ExecutionEngine {
                     return Err(res.err().unwrap());
                 }
             }
+            for upvalue in &frame.fn_object.upvalues {
+                let res = upvalue.clone().mark(&mut self.shared_execution_context);
+                if res.is_err() {
+                    return Err(res.err().unwrap());
+                }
+            }
+        }
+
+        // imported modules are kept for the next import even when nothing refers to them
+        let modules: Vec<GCRef> = self
+            .shared_execution_context
+            .modules
+            .values()
+            .cloned()
+            .collect();
+        for module in modules {
+            let res = Object::GC_REF(module).mark(&mut self.shared_execution_context);
+            if res.is_err() {
+                return Err(res.err().unwrap());
+            }
         }
 
         Ok(())
@@ -2872,13 +4915,19 @@ impl ExecutionEngine {
                 index: *dead_slot,
                 marked: false,
             });
-            println!("sweeping {} {:?}", dead_slot, val.unwrap());
+            // println!("sweeping {} {:?}", dead_slot, val.unwrap());
             self.shared_execution_context
                 .heap
                 .live_slots
                 .remove(&dead_slot);
         }
-        self.shared_execution_context.heap.dead_objects.clear();
+        // the heap is measured by what its maps have allocated, which doesn't go down as things
+        // are removed, so they're shrunk to what's still alive
+        self.shared_execution_context.heap.dead_objects = HashMap::new();
+        self.shared_execution_context
+            .heap
+            .live_slots
+            .shrink_to_fit();
 
         Ok(())
     }
@@ -2,7 +2,9 @@ use std::rc::Rc;
 
 use deepsize::DeepSizeOf;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use crate::r#type::DataType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, DeepSizeOf)]
 pub struct Position {
     pub index: u32, // 0-based
     pub line: u32,  // 0-based
@@ -32,17 +34,28 @@ pub enum Type {
     AMPERSAND,
     DOT,
     DOT_DOT,
+    ELLIPSIS,
     RETURN,
     LPAREN,
     RPAREN,
     LSQUARE,
     RSQUARE,
+    LCURLY,
+    RCURLY,
     GREATER_THAN,
     LESS_THAN,
     GREATER_EQ,
     LESS_EQ,
     OR,
     AND,
+    NOT,
+    TRY,
+    MOD,
+    POW,
+    BITWISE_AND,
+    BITWISE_XOR,
+    SHIFT_LEFT,
+    SHIFT_RIGHT,
     ANY,
     COMMA,
     ASSIGN,
@@ -53,14 +66,23 @@ pub enum Type {
     FALSE,
     FN,
     FOR,
+    WHILE,
+    LOOP,
+    BREAK,
+    CONTINUE,
     IF,
     IN,
     ELSE,
     DO,
     THEN,
     TYPE,
+    ENUM,
+    INTERFACE,
     LET,
     IMPORT,
+    FROM,
+    AS,
+    EXPORT,
     END,
     ADD,
     SUB,
@@ -79,12 +101,44 @@ pub enum Type {
     ATOM(Rc<String>),
     IDENTIFIER(Rc<String>),
     STRING_LITERAL(Rc<String>),
+    INTERPOLATED_STRING(Vec<StringPart>),
+}
+
+// "x={x}" is lexed into the literal "x=" followed by the tokens of the expression x
+#[derive(Eq, PartialEq, Debug, Clone, Hash, DeepSizeOf)]
+pub enum StringPart {
+    LITERAL(Rc<String>),
+    EXPRESSION(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, DeepSizeOf)]
+pub enum TriviaKind {
+    // a run of spaces, tabs and newlines, up to and including the end of the line
+    WHITESPACE,
+    LINE_COMMENT,
+    BLOCK_COMMENT,
+    // `/// ...`, documents the declaration after it
+    DOC_COMMENT,
+}
+
+// whitespace, comments and other source that doesn't affect parsing, only kept when lexing
+// losslessly apart from doc comments, which are always kept so the parser can attach them to
+// declarations. trivia on the same line as the end of a token trails it, the rest leads the next
+// token
+#[derive(Debug, Clone, PartialEq, Eq, Hash, DeepSizeOf)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Rc<String>,
+    pub pos: Position,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, DeepSizeOf)]
 pub struct Token {
     pub pos: Position,
     pub typ: Type,
+    pub leading_trivia: Vec<Trivia>,
+    // comments on the same line after the token
+    pub trailing_trivia: Vec<Trivia>,
 }
 
 impl Token {
@@ -107,6 +161,41 @@ impl Token {
             _ => panic!(),
         }
     }
+
+    // the lines of the `///` comments before the token without the slashes, if there are any
+    pub fn doc_comment(&self) -> Option<Rc<String>> {
+        let lines: Vec<&str> = self
+            .leading_trivia
+            .iter()
+            .filter(|t| t.kind == TriviaKind::DOC_COMMENT)
+            .map(|t| {
+                let line = &t.text[3..];
+                line.strip_prefix(' ').unwrap_or(line)
+            })
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        return Some(Rc::new(lines.join("\n")));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UNTERMINATED_STRING,
+    UNTERMINATED_COMMENT,
+    UNEXPECTED_CHARACTER(char),
+    MALFORMED_NUMBER(String),
+    NUMBER_OUT_OF_RANGE(String, DataType),
+    INVALID_ESCAPE(char),
+    UNTERMINATED_INTERPOLATION,
+    EMPTY_INTERPOLATION,
+}
+
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub pos: Position,
 }
 
 #[derive(Debug)]
@@ -114,6 +203,10 @@ pub struct Lexer {
     pub counter: u32,
     pub index: u32,
     pub line: u32,
+    pub lossless: bool,
+    // the trivia after the last token that isn't on its line, which is all of it when there are no
+    // tokens
+    pub eof_trivia: Vec<Trivia>,
 }
 
 impl Lexer {
@@ -122,11 +215,27 @@ impl Lexer {
             counter: 0,
             index: 0,
             line: 0,
+            lossless: false,
+            eof_trivia: vec![],
+        };
+    }
+
+    // keeps whitespace and comments as trivia on the tokens, for tools that need to round-trip
+    // source
+    pub fn new_lossless() -> Self {
+        return Lexer {
+            counter: 0,
+            index: 0,
+            line: 0,
+            lossless: true,
+            eof_trivia: vec![],
         };
     }
 
-    pub fn lex(&mut self, source: std::string::String) -> std::vec::Vec<Token> {
+    pub fn lex(&mut self, source: std::string::String) -> Result<Vec<Token>, Vec<LexError>> {
         let mut v = vec![];
+        let mut errors = vec![];
+        let mut trivia = vec![];
         // let mut position: Position = Position { index: 0, line: 0 };
 
         let chars: Vec<char> = source.chars().collect();
@@ -135,11 +244,7 @@ impl Lexer {
             let current = chars[self.counter as usize];
 
             if current.is_whitespace() {
-                if current == '\n' {
-                    self.line += 1;
-                    self.index = 0;
-                }
-                self.counter += 1;
+                self.whitespace(&chars, &mut trivia);
                 continue;
             }
 
@@ -149,6 +254,8 @@ impl Lexer {
                 '$' => {
                     v.push(Token {
                         typ: Type::DOLLAR,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -160,6 +267,8 @@ impl Lexer {
                 '@' => {
                     v.push(Token {
                         typ: Type::AMPERSAND,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -169,13 +278,29 @@ impl Lexer {
                     });
                 }
                 '.' => {
-                    if chars[self.counter as usize + 1] == '.' {
+                    if self.peek(&chars, 1) == Some('.') && self.peek(&chars, 2) == Some('.') {
+                        v.push(Token {
+                            typ: Type::ELLIPSIS,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
+                            pos: Position {
+                                index: self.index,
+                                line: self.line,
+                                index_end: self.index + 3,
+                                line_end: self.line,
+                            },
+                        });
+                        self.counter += 2;
+                        self.index += 2;
+                    } else if self.peek(&chars, 1) == Some('.') {
                         v.push(Token {
                             typ: Type::DOT_DOT,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 1,
+                                index_end: self.index + 2,
                                 line_end: self.line,
                             },
                         });
@@ -184,6 +309,8 @@ impl Lexer {
                     } else {
                         v.push(Token {
                             typ: Type::DOT,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -194,13 +321,29 @@ impl Lexer {
                     }
                 }
                 '>' => {
-                    if chars[self.counter as usize + 1] == '=' {
+                    if self.peek(&chars, 1) == Some('>') {
+                        v.push(Token {
+                            typ: Type::SHIFT_RIGHT,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
+                            pos: Position {
+                                index: self.index,
+                                line: self.line,
+                                index_end: self.index + 2,
+                                line_end: self.line,
+                            },
+                        });
+                        self.counter += 1;
+                        self.index += 1;
+                    } else if self.peek(&chars, 1) == Some('=') {
                         v.push(Token {
                             typ: Type::GREATER_EQ,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 1,
+                                index_end: self.index + 2,
                                 line_end: self.line,
                             },
                         });
@@ -209,6 +352,8 @@ impl Lexer {
                     } else {
                         v.push(Token {
                             typ: Type::GREATER_THAN,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -219,13 +364,29 @@ impl Lexer {
                     }
                 }
                 '<' => {
-                    if chars[self.counter as usize + 1] == '=' {
+                    if self.peek(&chars, 1) == Some('<') {
+                        v.push(Token {
+                            typ: Type::SHIFT_LEFT,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
+                            pos: Position {
+                                index: self.index,
+                                line: self.line,
+                                index_end: self.index + 2,
+                                line_end: self.line,
+                            },
+                        });
+                        self.counter += 1;
+                        self.index += 1;
+                    } else if self.peek(&chars, 1) == Some('=') {
                         v.push(Token {
                             typ: Type::LESS_EQ,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 1,
+                                index_end: self.index + 2,
                                 line_end: self.line,
                             },
                         });
@@ -234,6 +395,8 @@ impl Lexer {
                     } else {
                         v.push(Token {
                             typ: Type::LESS_THAN,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -246,6 +409,8 @@ impl Lexer {
                 '[' => {
                     v.push(Token {
                         typ: Type::LSQUARE,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -258,6 +423,8 @@ impl Lexer {
                 ']' => {
                     v.push(Token {
                         typ: Type::RSQUARE,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -266,9 +433,11 @@ impl Lexer {
                         },
                     });
                 }
-                '+' => {
+                '{' => {
                     v.push(Token {
-                        typ: Type::ADD,
+                        typ: Type::LCURLY,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -277,9 +446,11 @@ impl Lexer {
                         },
                     });
                 }
-                ':' => {
+                '}' => {
                     v.push(Token {
-                        typ: Type::COLON,
+                        typ: Type::RCURLY,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -288,9 +459,11 @@ impl Lexer {
                         },
                     });
                 }
-                '-' => {
+                '+' => {
                     v.push(Token {
-                        typ: Type::SUB,
+                        typ: Type::ADD,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -299,9 +472,11 @@ impl Lexer {
                         },
                     });
                 }
-                '*' => {
+                ':' => {
                     v.push(Token {
-                        typ: Type::MUL,
+                        typ: Type::COLON,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
                         pos: Position {
                             index: self.index,
                             line: self.line,
@@ -310,32 +485,25 @@ impl Lexer {
                         },
                     });
                 }
-                '/' => {
-                    // todo cant have comments without a \n at the end
-                    if chars[self.counter as usize + 1] == '/' {
-                        while chars[self.counter as usize + 1] != '\n' {
-                            self.counter += 1;
-                        }
-                        self.counter += 1;
-                    } else {
-                        v.push(Token {
-                            typ: Type::DIV,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 1,
-                                line_end: self.line,
-                            },
-                        });
-                    }
+                '-' => {
+                    v.push(Token {
+                        typ: Type::SUB,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
+                        pos: Position {
+                            index: self.index,
+                            line: self.line,
+                            index_end: self.index + 1,
+                            line_end: self.line,
+                        },
+                    });
                 }
-                'o' => {
-                    if chars[self.counter as usize + 1] == 'r'
-                    // todo implement this end check
-                        && chars[self.counter as usize + 2].is_whitespace()
-                    {
+                '*' => {
+                    if self.peek(&chars, 1) == Some('*') {
                         v.push(Token {
-                            typ: Type::OR,
+                            typ: Type::POW,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -346,58 +514,129 @@ impl Lexer {
                         self.counter += 1;
                         self.index += 1;
                     } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
-                }
-                'd' => {
-                    if chars[self.counter as usize + 1] == 'o'
-                    // todo implement this end check
-                        && chars[self.counter as usize + 2].is_whitespace()
-                    {
                         v.push(Token {
-                            typ: Type::DO,
+                            typ: Type::MUL,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 2,
+                                index_end: self.index + 1,
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 1;
-                        self.index += 1;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
                     }
                 }
-                'm' => {
-                    if chars[self.counter as usize + 1] == 'a'
-                        && chars[self.counter as usize + 2] == 't'
-                        && chars[self.counter as usize + 3] == 'c'
-                        && chars[self.counter as usize + 4] == 'h'
-                        && chars[self.counter as usize + 5].is_whitespace()
-                    {
+                '%' => {
+                    v.push(Token {
+                        typ: Type::MOD,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
+                        pos: Position {
+                            index: self.index,
+                            line: self.line,
+                            index_end: self.index + 1,
+                            line_end: self.line,
+                        },
+                    });
+                }
+                '&' => {
+                    v.push(Token {
+                        typ: Type::BITWISE_AND,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
+                        pos: Position {
+                            index: self.index,
+                            line: self.line,
+                            index_end: self.index + 1,
+                            line_end: self.line,
+                        },
+                    });
+                }
+                '^' => {
+                    v.push(Token {
+                        typ: Type::BITWISE_XOR,
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
+                        pos: Position {
+                            index: self.index,
+                            line: self.line,
+                            index_end: self.index + 1,
+                            line_end: self.line,
+                        },
+                    });
+                }
+                '/' => {
+                    if self.peek(&chars, 1) == Some('/') {
+                        self.line_comment(&chars, &mut trivia);
+                        continue;
+                    } else if self.peek(&chars, 1) == Some('*') {
+                        self.block_comment(&chars, &mut trivia, &mut errors);
+                        continue;
+                    } else {
                         v.push(Token {
-                            typ: Type::MATCH,
+                            typ: Type::DIV,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 5,
+                                index_end: self.index + 1,
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 4;
-                        self.index += 4;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
                     }
                 }
-                'f' => {
-                    if chars[self.counter as usize + 1] == 'n' {
+                ',' => v.push(Token {
+                    typ: Type::COMMA,
+                    leading_trivia: vec![],
+                    trailing_trivia: vec![],
+                    pos: Position {
+                        index: self.index,
+                        line: self.line,
+                        index_end: self.index + 1,
+                        line_end: self.line,
+                    },
+                }),
+                '(' => v.push(Token {
+                    typ: Type::LPAREN,
+                    leading_trivia: vec![],
+                    trailing_trivia: vec![],
+                    pos: Position {
+                        index: self.index,
+                        line: self.line,
+                        index_end: self.index + 1,
+                        line_end: self.line,
+                    },
+                }),
+                ')' => v.push(Token {
+                    typ: Type::RPAREN,
+                    leading_trivia: vec![],
+                    trailing_trivia: vec![],
+                    pos: Position {
+                        index: self.index,
+                        line: self.line,
+                        index_end: self.index + 1,
+                        line_end: self.line,
+                    },
+                }),
+                '|' => v.push(Token {
+                    typ: Type::BITWISE_OR,
+                    leading_trivia: vec![],
+                    trailing_trivia: vec![],
+                    pos: Position {
+                        index: self.index,
+                        line: self.line,
+                        index_end: self.index + 1,
+                        line_end: self.line,
+                    },
+                }),
+                '=' => {
+                    if self.peek(&chars, 1) == Some('=') {
                         v.push(Token {
-                            typ: Type::FN,
+                            typ: Type::EQUALS,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -405,62 +644,28 @@ impl Lexer {
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 1;
                         self.index += 1;
-                    } else if chars[self.counter as usize + 1] == '6'
-                        && chars[self.counter as usize + 2] == '4'
-                        && chars[self.counter as usize + 3].is_whitespace()
-                    {
-                        v.push(Token {
-                            typ: Type::F64,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 3,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else if chars[self.counter as usize + 1] == 'a'
-                        && chars[self.counter as usize + 2] == 'l'
-                        && chars[self.counter as usize + 3] == 's'
-                        && chars[self.counter as usize + 4] == 'e'
-                    {
-                        v.push(Token {
-                            typ: Type::FALSE,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 5,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 4;
-                        self.index += 4;
-                    } else if chars[self.counter as usize + 1] == 'o'
-                        && chars[self.counter as usize + 2] == 'r'
-                    {
+                        self.counter += 1;
+                    } else {
                         v.push(Token {
-                            typ: Type::FOR,
+                            typ: Type::ASSIGN,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 3,
+                                index_end: self.index + 1,
                                 line_end: self.line,
                             },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
+                        })
                     }
                 }
-                'i' => {
-                    if chars[self.counter as usize + 1] == 'f' {
+                '!' => {
+                    if self.peek(&chars, 1) == Some('=') {
                         v.push(Token {
-                            typ: Type::IF,
+                            typ: Type::NOT_EQUALS,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
@@ -468,509 +673,755 @@ impl Lexer {
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 1;
                         self.index += 1;
-                    } else if chars[self.counter as usize + 1] == 'n'
-                        && chars[self.counter as usize + 2].is_whitespace()
-                    {
+                        self.counter += 1;
+                    } else {
                         v.push(Token {
-                            typ: Type::IN,
+                            typ: Type::EXCLAIM,
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
                                 index: self.index,
                                 line: self.line,
-                                index_end: self.index + 2,
+                                index_end: self.index + 1,
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 1;
-                        self.index += 1;
-                    } else if chars[self.counter as usize + 1] == 'm'
-                        && chars[self.counter as usize + 2] == 'p'
-                        && chars[self.counter as usize + 3] == 'o'
-                        && chars[self.counter as usize + 4] == 'r'
-                        && chars[self.counter as usize + 5] == 't'
-                        && chars[self.counter as usize + 6].is_whitespace()
-                    {
+                    }
+                }
+                '"' => {
+                    self.string(&chars, &mut v, &mut errors, false);
+                    continue;
+                }
+                ':' => {
+                    self.index += 1;
+                    self.counter += 1;
+                    if current.is_alphabetic() {
+                        let tmp_index = self.index;
+                        let mut identifier = "".to_string();
+                        while self.counter < chars.len().try_into().unwrap() {
+                            if !(chars[self.counter as usize].is_alphabetic()
+                                || chars[self.counter as usize] == '_')
+                            {
+                                break;
+                            }
+                            let next = chars[self.counter as usize];
+                            identifier.push(next);
+                            self.index += 1;
+                            self.counter += 1;
+                        }
+                        // identifier
                         v.push(Token {
-                            typ: Type::IMPORT,
+                            typ: Type::ATOM(identifier.into()),
+                            leading_trivia: vec![],
+                            trailing_trivia: vec![],
                             pos: Position {
-                                index: self.index,
+                                index: tmp_index,
                                 line: self.line,
-                                index_end: self.index + 6,
+                                index_end: self.index + 1,
                                 line_end: self.line,
                             },
                         });
-                        self.counter += 5;
-                        self.index += 5;
-                    } else {
-                        self.identifier(&chars, &mut v);
                         continue;
                     }
                 }
-                'e' => {
-                    if chars[self.counter as usize + 1] == 'n'
-                        && chars[self.counter as usize + 2] == 'd'
-                    {
-                        v.push(Token {
-                            typ: Type::END,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 3,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else if chars[self.counter as usize + 1] == 'l'
-                        && chars[self.counter as usize + 2] == 's'
-                        && chars[self.counter as usize + 3] == 'e'
-                    {
-                        v.push(Token {
-                            typ: Type::ELSE,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else {
-                        self.identifier(&chars, &mut v);
+                _ => {
+                    if current == 'r' && self.peek(&chars, 1) == Some('"') {
+                        self.string(&chars, &mut v, &mut errors, true);
                         continue;
                     }
-                }
-                'b' => {
-                    if chars[self.counter as usize + 1] == 'o'
-                        && chars[self.counter as usize + 2] == 'o'
-                        && chars[self.counter as usize + 3] == 'l'
-                    {
-                        v.push(Token {
-                            typ: Type::BOOL,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else {
+                    if current.is_alphabetic() || current == '_' {
                         self.identifier(&chars, &mut v);
                         continue;
                     }
-                }
-                'a' => {
-                    if chars[self.counter as usize + 1] == 's'
-                        && chars[self.counter as usize + 2] == 's'
-                        && chars[self.counter as usize + 3] == 'e'
-                        && chars[self.counter as usize + 4] == 'r'
-                        && chars[self.counter as usize + 5] == 't'
-                    {
-                        v.push(Token {
-                            typ: Type::ASSERT,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 6,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 5;
-                        self.index += 5;
-                    } else if chars[self.counter as usize + 1] == 'n'
-                        && chars[self.counter as usize + 2] == 'y'
-                    {
-                        v.push(Token {
-                            typ: Type::ANY,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 3,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else {
-                        self.identifier(&chars, &mut v);
+                    if current.is_ascii_digit() {
+                        self.number(&chars, &mut v, &mut errors);
                         continue;
                     }
+                    errors.push(LexError {
+                        kind: LexErrorKind::UNEXPECTED_CHARACTER(current),
+                        pos: Position {
+                            index: self.index,
+                            line: self.line,
+                            index_end: self.index + 1,
+                            line_end: self.line,
+                        },
+                    });
                 }
-                's' => {
-                    if chars[self.counter as usize + 1] == 't'
-                        && chars[self.counter as usize + 2] == 'r'
-                        && chars[self.counter as usize + 3] == 'i'
-                        && chars[self.counter as usize + 4] == 'n'
-                        && chars[self.counter as usize + 5] == 'g'
-                    {
-                        v.push(Token {
-                            typ: Type::STRING,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 6,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 5;
-                        self.index += 5;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
+            }
+
+            self.index += 1;
+            self.counter += 1;
+        }
+
+        if errors.len() > 0 {
+            return Err(errors);
+        }
+        self.eof_trivia = attach_trivia(&mut v, trivia);
+        return Ok(v);
+    }
+
+    fn whitespace(&mut self, chars: &Vec<char>, trivia: &mut Vec<Trivia>) {
+        let tmp_index = self.index;
+        let tmp_line = self.line;
+        let mut text = "".to_string();
+        while let Some(next) = self.peek(chars, 0) {
+            if !next.is_whitespace() {
+                break;
+            }
+            text.push(next);
+            self.counter += 1;
+            // the run stops at the end of the line so what's left of it can trail the token before
+            if next == '\n' {
+                self.line += 1;
+                self.index = 0;
+                break;
+            }
+            self.index += 1;
+        }
+        if self.lossless {
+            trivia.push(Trivia {
+                kind: TriviaKind::WHITESPACE,
+                text: text.into(),
+                pos: Position {
+                    index: tmp_index,
+                    line: tmp_line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            });
+        }
+    }
+
+    fn line_comment(&mut self, chars: &Vec<char>, trivia: &mut Vec<Trivia>) {
+        let tmp_index = self.index;
+        let mut text = "".to_string();
+        // stop before the newline so the line counting still happens
+        while let Some(next) = self.peek(chars, 0) {
+            if next == '\n' {
+                break;
+            }
+            text.push(next);
+            self.index += 1;
+            self.counter += 1;
+        }
+        // four or more slashes is just a line of slashes
+        let is_doc = text.starts_with("///") && !text.starts_with("////");
+        if self.lossless || is_doc {
+            trivia.push(Trivia {
+                kind: if is_doc {
+                    TriviaKind::DOC_COMMENT
+                } else {
+                    TriviaKind::LINE_COMMENT
+                },
+                text: text.into(),
+                pos: Position {
+                    index: tmp_index,
+                    line: self.line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            });
+        }
+    }
+
+    fn block_comment(
+        &mut self,
+        chars: &Vec<char>,
+        trivia: &mut Vec<Trivia>,
+        errors: &mut Vec<LexError>,
+    ) {
+        let tmp_index = self.index;
+        let tmp_line = self.line;
+        let mut text = "".to_string();
+        let mut depth = 0;
+        while let Some(next) = self.peek(chars, 0) {
+            if next == '/' && self.peek(chars, 1) == Some('*') {
+                depth += 1;
+                text.push_str("/*");
+                self.index += 2;
+                self.counter += 2;
+                continue;
+            }
+            if next == '*' && self.peek(chars, 1) == Some('/') {
+                depth -= 1;
+                text.push_str("*/");
+                self.index += 2;
+                self.counter += 2;
+                if depth == 0 {
+                    break;
                 }
-                'l' => {
-                    if chars[self.counter as usize + 1] == 'e'
-                        && chars[self.counter as usize + 2] == 't'
-                    {
-                        v.push(Token {
-                            typ: Type::LET,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 3,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
+                continue;
+            }
+            if next == '\n' {
+                self.line += 1;
+                self.index = 0;
+            } else {
+                self.index += 1;
+            }
+            text.push(next);
+            self.counter += 1;
+        }
+        let pos = Position {
+            index: tmp_index,
+            line: tmp_line,
+            index_end: self.index,
+            line_end: self.line,
+        };
+        if depth > 0 {
+            errors.push(LexError {
+                kind: LexErrorKind::UNTERMINATED_COMMENT,
+                pos,
+            });
+            return;
+        }
+        if self.lossless {
+            trivia.push(Trivia {
+                kind: TriviaKind::BLOCK_COMMENT,
+                text: text.into(),
+                pos,
+            });
+        }
+    }
+
+    fn peek(&self, chars: &Vec<char>, offset: usize) -> Option<char> {
+        return chars.get(self.counter as usize + offset).copied();
+    }
+
+    // "..." strings, """...""" multi-line strings and their raw r"..." versions
+    fn string(
+        &mut self,
+        chars: &Vec<char>,
+        v: &mut Vec<Token>,
+        errors: &mut Vec<LexError>,
+        raw: bool,
+    ) {
+        let tmp_index = self.index;
+        let tmp_line = self.line;
+        let mut s = "".to_string();
+        let mut parts: Vec<StringPart> = vec![];
+        let mut interpolated = false;
+        if raw {
+            self.index += 1;
+            self.counter += 1;
+        }
+
+        let triple = self.peek(chars, 0) == Some('"')
+            && self.peek(chars, 1) == Some('"')
+            && self.peek(chars, 2) == Some('"');
+        let mut bounds: Option<(usize, usize)> = None;
+        let mut dedent = 0;
+        if triple {
+            self.index += 3;
+            self.counter += 3;
+            // a newline straight after the opening quotes isn't part of the string
+            if self.peek(chars, 0) == Some('\n') {
+                self.line += 1;
+                self.index = 0;
+                self.counter += 1;
+            }
+            match self.multiline_string_bounds(chars, raw) {
+                Some((content_end, close, indentation)) => {
+                    bounds = Some((content_end, close));
+                    dedent = indentation;
                 }
-                't' => {
-                    if chars[self.counter as usize + 1] == 'h'
-                        && chars[self.counter as usize + 2] == 'e'
-                        && chars[self.counter as usize + 3] == 'n'
-                    {
-                        v.push(Token {
-                            typ: Type::THEN,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else if chars[self.counter as usize + 1] == 'e'
-                        && chars[self.counter as usize + 2] == 's'
-                        && chars[self.counter as usize + 3] == 't'
-                        && chars[self.counter as usize + 4].is_whitespace()
-                    {
-                        v.push(Token {
-                            typ: Type::TEST,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else if chars[self.counter as usize + 1] == 'r'
-                        && chars[self.counter as usize + 2] == 'u'
-                        && chars[self.counter as usize + 3] == 'e'
-                    {
-                        v.push(Token {
-                            typ: Type::TRUE,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else if chars[self.counter as usize + 1] == 'y'
-                        && chars[self.counter as usize + 2] == 'p'
-                        && chars[self.counter as usize + 3] == 'e'
-                        && chars[self.counter as usize + 4].is_whitespace()
-                    {
-                        v.push(Token {
-                            typ: Type::TYPE,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
+                None => {
+                    errors.push(LexError {
+                        kind: LexErrorKind::UNTERMINATED_STRING,
+                        pos: Position {
+                            index: tmp_index,
+                            line: tmp_line,
+                            index_end: tmp_index + 3,
+                            line_end: tmp_line,
+                        },
+                    });
+                    self.counter = chars.len() as u32;
+                    return;
                 }
-                'p' => {
-                    if chars[self.counter as usize + 1] == 'a'
-                        && chars[self.counter as usize + 2] == 's'
-                        && chars[self.counter as usize + 3] == 's'
-                    {
-                        v.push(Token {
-                            typ: Type::PASS,
-                            pos: Position {
-                                index: self.index,
-                                line: self.index,
-                                index_end: self.index + 4,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 3;
-                        self.index += 3;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
+            }
+            self.skip_indentation(chars, dedent);
+        } else {
+            self.index += 1;
+            self.counter += 1;
+        }
+
+        let mut terminated = false;
+        while let Some(next) = self.peek(chars, 0) {
+            match bounds {
+                Some((content_end, _)) => {
+                    if self.counter as usize >= content_end {
+                        terminated = true;
+                        break;
                     }
                 }
-                'r' => {
-                    if chars[self.counter as usize + 1] == 'e'
-                        && chars[self.counter as usize + 2] == 't'
-                        && chars[self.counter as usize + 3] == 'u'
-                        && chars[self.counter as usize + 4] == 'r'
-                        && chars[self.counter as usize + 5] == 'n'
-                    {
-                        v.push(Token {
-                            typ: Type::RETURN,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 6,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 5;
-                        self.index += 5;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
+                None => {
+                    if next == '"' {
+                        terminated = true;
+                        break;
                     }
                 }
-                'u' => {
-                    if chars[self.counter as usize + 1] == '3'
-                        && chars[self.counter as usize + 2] == '2'
-                    {
-                        v.push(Token {
-                            typ: Type::U32,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 3,
-                                line_end: self.line,
-                            },
-                        });
-                        self.counter += 2;
-                        self.index += 2;
-                    } else {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
+            }
+            if next == '\n' {
+                s.push(next);
+                self.line += 1;
+                self.index = 0;
+                self.counter += 1;
+                if triple {
+                    self.skip_indentation(chars, dedent);
                 }
-                ',' => v.push(Token {
-                    typ: Type::COMMA,
-                    pos: Position {
-                        index: self.index,
-                        line: self.line,
-                        index_end: self.index + 1,
-                        line_end: self.line,
-                    },
-                }),
-                '(' => v.push(Token {
-                    typ: Type::LPAREN,
-                    pos: Position {
-                        index: self.index,
-                        line: self.line,
-                        index_end: self.index + 1,
-                        line_end: self.line,
-                    },
-                }),
-                ')' => v.push(Token {
-                    typ: Type::RPAREN,
-                    pos: Position {
-                        index: self.index,
-                        line: self.line,
-                        index_end: self.index + 1,
-                        line_end: self.line,
-                    },
-                }),
-                '|' => v.push(Token {
-                    typ: Type::BITWISE_OR,
-                    pos: Position {
-                        index: self.index,
-                        line: self.line,
-                        index_end: self.index + 1,
-                        line_end: self.line,
-                    },
-                }),
-                '=' => {
-                    if chars[self.counter as usize + 1] == '=' {
-                        v.push(Token {
-                            typ: Type::EQUALS,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 2,
-                                line_end: self.line,
-                            },
-                        });
-                        self.index += 1;
-                        self.counter += 1;
-                    } else {
-                        v.push(Token {
-                            typ: Type::ASSIGN,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 1,
-                                line_end: self.line,
-                            },
-                        })
-                    }
+                continue;
+            }
+            if next == '\\' && !raw {
+                self.escape(chars, &mut s, errors);
+                continue;
+            }
+            if next == '{' && !raw {
+                interpolated = true;
+                if s.len() > 0 {
+                    parts.push(StringPart::LITERAL(s.into()));
+                    s = "".to_string();
                 }
-                '!' => {
-                    if chars[self.counter as usize + 1] == '=' {
-                        v.push(Token {
-                            typ: Type::NOT_EQUALS,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 2,
-                                line_end: self.line,
-                            },
-                        });
-                        self.index += 1;
-                        self.counter += 1;
+                if let Some(tokens) = self.interpolation(chars, errors) {
+                    parts.push(StringPart::EXPRESSION(tokens));
+                }
+                continue;
+            }
+            s.push(next);
+            self.index += 1;
+            self.counter += 1;
+        }
+        if let Some((_, close)) = bounds {
+            if self.counter as usize > close {
+                terminated = false;
+            }
+        }
+        if !terminated {
+            errors.push(LexError {
+                kind: LexErrorKind::UNTERMINATED_STRING,
+                pos: Position {
+                    index: tmp_index,
+                    line: tmp_line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            });
+            return;
+        }
+
+        // the closing quotes, along with the indentation before them
+        match bounds {
+            Some((_, close)) => {
+                while (self.counter as usize) < close {
+                    if chars[self.counter as usize] == '\n' {
+                        self.line += 1;
+                        self.index = 0;
                     } else {
-                        v.push(Token {
-                            typ: Type::EXCLAIM,
-                            pos: Position {
-                                index: self.index,
-                                line: self.line,
-                                index_end: self.index + 1,
-                                line_end: self.line,
-                            },
-                        });
+                        self.index += 1;
                     }
+                    self.counter += 1;
                 }
-                '"' => {
-                    // fixme deal with multiline
-                    let tmp_index = self.index;
-                    let mut s = "".to_string();
+                self.index += 3;
+                self.counter += 3;
+            }
+            None => {
+                self.index += 1;
+                self.counter += 1;
+            }
+        }
+
+        let typ = if interpolated {
+            if s.len() > 0 {
+                parts.push(StringPart::LITERAL(s.into()));
+            }
+            Type::INTERPOLATED_STRING(parts)
+        } else {
+            Type::STRING_LITERAL(s.into())
+        };
+        v.push(Token {
+            typ,
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
+            pos: Position {
+                index: tmp_index,
+                line: tmp_line,
+                index_end: self.index,
+                line_end: self.line,
+            },
+        });
+    }
+
+    // finds where the contents of a """ string end, where its closing quotes are, and the
+    // indentation common to all of its lines which gets stripped.
+    //
+    // a last line that is only whitespace before the closing quotes is dropped, but its
+    // indentation still counts, so the closing quotes can be used to control the indentation.
+    fn multiline_string_bounds(&self, chars: &Vec<char>, raw: bool) -> Option<(usize, usize, usize)> {
+        let start = self.counter as usize;
+        let mut close = start;
+        loop {
+            if close + 2 >= chars.len() {
+                return None;
+            }
+            if chars[close] == '\\' && !raw {
+                close += 2;
+                continue;
+            }
+            if chars[close] == '"' && chars[close + 1] == '"' && chars[close + 2] == '"' {
+                break;
+            }
+            close += 1;
+        }
+
+        let contents: String = chars[start..close].iter().collect();
+        let lines: Vec<&str> = contents.split('\n').collect();
+        let last_line = lines[lines.len() - 1];
+        let closing_line_blank = lines.len() > 1 && last_line.trim().len() == 0;
+
+        let mut content_end = close;
+        if closing_line_blank {
+            // drop the newline before the closing line too
+            content_end = close - last_line.chars().count() - 1;
+        }
+
+        let mut dedent: Option<usize> = None;
+        for i in 0..lines.len() {
+            let is_closing_line = i == lines.len() - 1 && closing_line_blank;
+            if lines[i].trim().len() == 0 && !is_closing_line {
+                continue;
+            }
+            let indentation = lines[i]
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count();
+            dedent = Some(std::cmp::min(dedent.unwrap_or(indentation), indentation));
+        }
+
+        return Some((content_end, close, dedent.unwrap_or(0)));
+    }
+
+    fn skip_indentation(&mut self, chars: &Vec<char>, dedent: usize) {
+        let mut skipped = 0;
+        while skipped < dedent {
+            match self.peek(chars, 0) {
+                Some(' ') | Some('\t') => {
                     self.index += 1;
                     self.counter += 1;
-
-                    while self.counter < chars.len().try_into().unwrap() {
-                        let next = chars[self.counter as usize];
-                        if next == '"' {
-                            break;
-                        };
-                        s.push(next);
-                        self.index += 1;
-                        self.counter += 1;
-                    }
-                    v.push(Token {
-                        typ: Type::STRING_LITERAL(s.into()),
-                        pos: Position {
-                            index: tmp_index,
-                            line: self.line,
-                            index_end: self.index,
-                            line_end: self.line,
-                        },
-                    });
+                    skipped += 1;
                 }
-                ':' => {
+                _ => break,
+            }
+        }
+    }
+
+    fn escape(&mut self, chars: &Vec<char>, s: &mut String, errors: &mut Vec<LexError>) {
+        let tmp_index = self.index;
+        self.index += 1;
+        self.counter += 1;
+        let next = match self.peek(chars, 0) {
+            Some(next) => next,
+            // the string is unterminated, which gets reported by the caller
+            None => return,
+        };
+        self.index += 1;
+        self.counter += 1;
+        let decoded = match next {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            '{' => Some('{'),
+            '}' => Some('}'),
+            'u' => self.unicode_escape(chars),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => s.push(c),
+            None => errors.push(LexError {
+                kind: LexErrorKind::INVALID_ESCAPE(next),
+                pos: Position {
+                    index: tmp_index,
+                    line: self.line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            }),
+        }
+    }
+
+    // \u{1F600}
+    fn unicode_escape(&mut self, chars: &Vec<char>) -> Option<char> {
+        if self.peek(chars, 0) != Some('{') {
+            return None;
+        }
+        self.index += 1;
+        self.counter += 1;
+        let mut hex = "".to_string();
+        while let Some(next) = self.peek(chars, 0) {
+            if !next.is_ascii_hexdigit() {
+                break;
+            }
+            hex.push(next);
+            self.index += 1;
+            self.counter += 1;
+        }
+        if self.peek(chars, 0) != Some('}') || hex.len() == 0 || hex.len() > 6 {
+            return None;
+        }
+        self.index += 1;
+        self.counter += 1;
+        return char::from_u32(u32::from_str_radix(&hex, 16).unwrap());
+    }
+
+    // lexes the expression between {} in a string, the tokens keep their position in the source
+    fn interpolation(
+        &mut self,
+        chars: &Vec<char>,
+        errors: &mut Vec<LexError>,
+    ) -> Option<Vec<Token>> {
+        let tmp_index = self.index;
+        self.index += 1;
+        self.counter += 1;
+        let expression_index = self.index;
+
+        let mut expression = "".to_string();
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut terminated = false;
+        while let Some(next) = self.peek(chars, 0) {
+            if next == '\n' {
+                break;
+            }
+            if in_string {
+                if next == '\\' && self.peek(chars, 1).is_some() {
+                    expression.push(next);
                     self.index += 1;
                     self.counter += 1;
-                    if current.is_alphabetic() {
-                        let tmp_index = self.index;
-                        let mut identifier = "".to_string();
-                        while self.counter < chars.len().try_into().unwrap() {
-                            if !(chars[self.counter as usize].is_alphabetic()
-                                || chars[self.counter as usize] == '_')
-                            {
-                                break;
-                            }
-                            let next = chars[self.counter as usize];
-                            identifier.push(next);
-                            self.index += 1;
-                            self.counter += 1;
-                        }
-                        // identifier
-                        v.push(Token {
-                            typ: Type::ATOM(identifier.into()),
-                            pos: Position {
-                                index: tmp_index,
-                                line: self.line,
-                                index_end: self.index + 1,
-                                line_end: self.line,
-                            },
-                        });
-                        continue;
-                    }
+                    expression.push(chars[self.counter as usize]);
+                    self.index += 1;
+                    self.counter += 1;
+                    continue;
                 }
-                _ => {
-                    if current.is_alphabetic() || current == '_' {
-                        self.identifier(&chars, &mut v);
-                        continue;
-                    }
-                    if current.is_numeric() {
-                        let mut identifier = "".to_string();
-                        let tmp_index = self.index;
+                if next == '"' {
+                    in_string = false;
+                }
+            } else if next == '"' {
+                in_string = true;
+            } else if next == '{' {
+                depth += 1;
+            } else if next == '}' {
+                if depth == 0 {
+                    terminated = true;
+                    break;
+                }
+                depth -= 1;
+            }
+            expression.push(next);
+            self.index += 1;
+            self.counter += 1;
+        }
 
-                        while self.counter < chars.len().try_into().unwrap() {
-                            if chars[self.counter as usize] == '.'
-                                && chars[self.counter as usize + 1] != '.'
-                            {
-                                let next = chars[self.counter as usize];
-                                identifier.push(next);
-                                self.counter += 1;
-                                self.index += 1;
-                                continue;
-                            }
-                            if chars[self.counter as usize] == '_' {
-                                self.counter += 1;
-                                self.index += 1;
-                                continue;
-                            }
-                            if chars[self.counter as usize].is_whitespace()
-                                || !chars[self.counter as usize].is_numeric()
-                            {
-                                break;
-                            }
-                            let next = chars[self.counter as usize];
-                            identifier.push(next);
-                            self.index += 1;
-                            self.counter += 1;
-                        }
-                        // identifier
-                        v.push(Token {
-                            typ: Type::NUMBER(identifier.into()),
-                            pos: Position {
-                                index: tmp_index,
-                                line: self.line,
-                                index_end: self.index + 1,
-                                line_end: self.line,
-                            },
-                        });
-                        continue;
-                    }
+        if !terminated {
+            errors.push(LexError {
+                kind: LexErrorKind::UNTERMINATED_INTERPOLATION,
+                pos: Position {
+                    index: tmp_index,
+                    line: self.line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            });
+            return None;
+        }
+        // the closing brace
+        self.index += 1;
+        self.counter += 1;
+
+        if expression.trim().len() == 0 {
+            errors.push(LexError {
+                kind: LexErrorKind::EMPTY_INTERPOLATION,
+                pos: Position {
+                    index: tmp_index,
+                    line: self.line,
+                    index_end: self.index,
+                    line_end: self.line,
+                },
+            });
+            return None;
+        }
+
+        let mut lexer = Lexer {
+            counter: 0,
+            index: expression_index,
+            line: self.line,
+            lossless: false,
+            eof_trivia: vec![],
+        };
+        match lexer.lex(expression) {
+            Ok(tokens) => return Some(tokens),
+            Err(expression_errors) => {
+                errors.extend(expression_errors);
+                return None;
+            }
+        }
+    }
+
+    // numbers are normalised to decimal with an optional type suffix, so 0xFF_u32 becomes 255u32
+    fn number(&mut self, chars: &Vec<char>, v: &mut Vec<Token>, errors: &mut Vec<LexError>) {
+        let tmp_index = self.index;
+        let mut literal = "".to_string();
+        let mut digits = "".to_string();
+        let mut radix = 10;
+        let mut is_float = false;
+        let mut malformed = false;
+
+        if self.peek(chars, 0) == Some('0') {
+            radix = match self.peek(chars, 1) {
+                Some('x') | Some('X') => 16,
+                Some('b') | Some('B') => 2,
+                Some('o') | Some('O') => 8,
+                _ => 10,
+            };
+            if radix != 10 {
+                literal.push(chars[self.counter as usize]);
+                literal.push(chars[self.counter as usize + 1]);
+                self.index += 2;
+                self.counter += 2;
+            }
+        }
+
+        while let Some(next) = self.peek(chars, 0) {
+            if next == '_' {
+                literal.push(next);
+            } else if next.is_digit(radix) {
+                literal.push(next);
+                digits.push(next);
+            } else if radix == 10
+                && next == '.'
+                && self.peek(chars, 1).map_or(false, |c| c.is_ascii_digit())
+            {
+                // 0..5 is a range rather than a float
+                if is_float {
+                    malformed = true;
+                }
+                is_float = true;
+                literal.push(next);
+                digits.push(next);
+            } else if radix == 10 && (next == 'e' || next == 'E') && self.exponent_follows(chars) {
+                is_float = true;
+                literal.push(next);
+                digits.push(next);
+                if let Some(sign) = self.peek(chars, 1).filter(|c| *c == '+' || *c == '-') {
+                    literal.push(sign);
+                    digits.push(sign);
+                    self.index += 1;
+                    self.counter += 1;
                 }
+            } else {
+                break;
             }
+            self.index += 1;
+            self.counter += 1;
+        }
 
+        // anything alphanumeric straight after the digits has to be a type suffix
+        let mut suffix = "".to_string();
+        while let Some(next) = self.peek(chars, 0) {
+            if !(next.is_alphanumeric() || next == '_') {
+                break;
+            }
+            suffix.push(next);
             self.index += 1;
             self.counter += 1;
         }
+        literal.push_str(&suffix);
+
+        let pos = Position {
+            index: tmp_index,
+            line: self.line,
+            index_end: self.index,
+            line_end: self.line,
+        };
+
+        let typ = if suffix.len() == 0 {
+            if is_float {
+                DataType::F64
+            } else {
+                DataType::I64
+            }
+        } else {
+            match DataType::from_number_suffix(&suffix) {
+                Some(typ) => typ,
+                None => {
+                    errors.push(LexError {
+                        kind: LexErrorKind::MALFORMED_NUMBER(literal),
+                        pos,
+                    });
+                    return;
+                }
+            }
+        };
+        let float_typ = typ == DataType::F32 || typ == DataType::F64;
+        if malformed || digits.len() == 0 || (is_float && !float_typ) || (radix != 10 && float_typ)
+        {
+            errors.push(LexError {
+                kind: LexErrorKind::MALFORMED_NUMBER(literal),
+                pos,
+            });
+            return;
+        }
+
+        let number = if float_typ {
+            let value = digits.parse::<f64>().unwrap();
+            let in_range = if typ == DataType::F32 {
+                (value as f32).is_finite()
+            } else {
+                value.is_finite()
+            };
+            if !in_range {
+                None
+            } else {
+                Some(digits)
+            }
+        } else {
+            // the sign is its own token, so the signed types can go one further to reach their min
+            // and the parser checks the positive ones
+            let max: u128 = match typ {
+                DataType::U32 => u32::MAX as u128,
+                DataType::I32 => i32::MAX as u128 + 1,
+                _ => i64::MAX as u128 + 1,
+            };
+            match u128::from_str_radix(&digits, radix) {
+                Ok(value) if value <= max => Some(value.to_string()),
+                _ => None,
+            }
+        };
+
+        match number {
+            Some(mut number) => {
+                number.push_str(&suffix);
+                v.push(Token {
+                    typ: Type::NUMBER(number.into()),
+                    leading_trivia: vec![],
+                    trailing_trivia: vec![],
+                    pos,
+                });
+            }
+            None => errors.push(LexError {
+                kind: LexErrorKind::NUMBER_OUT_OF_RANGE(literal, typ),
+                pos,
+            }),
+        }
+    }
 
-        return v;
+    // 1e10 and 1.5e-3 are exponents, but 1e on its own is a malformed suffix
+    fn exponent_follows(&self, chars: &Vec<char>) -> bool {
+        return match self.peek(chars, 1) {
+            Some('+') | Some('-') => self.peek(chars, 2).map_or(false, |c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        };
     }
 
     fn identifier(&mut self, chars: &Vec<char>, v: &mut Vec<Token>) {
@@ -988,15 +1439,88 @@ impl Lexer {
             self.index += 1;
             self.counter += 1;
         }
-        // identifier
+        let typ = match keyword(&identifier) {
+            Some(keyword) => keyword,
+            None => Type::IDENTIFIER(identifier.into()),
+        };
         v.push(Token {
-            typ: Type::IDENTIFIER(identifier.into()),
+            typ,
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
             pos: Position {
                 index: tmp_index,
                 line: self.line,
-                index_end: self.index + 1,
+                index_end: self.index,
                 line_end: self.line,
             },
         });
     }
 }
+
+// comments trail the token before them on the same line, otherwise they lead the next token
+// gives back the trivia that's left over at the end of the file
+fn attach_trivia(tokens: &mut Vec<Token>, trivia: Vec<Trivia>) -> Vec<Trivia> {
+    let mut eof_trivia = vec![];
+    let mut next_token = 0;
+    for t in trivia {
+        while next_token < tokens.len()
+            && (tokens[next_token].pos.line, tokens[next_token].pos.index)
+                < (t.pos.line, t.pos.index)
+        {
+            next_token += 1;
+        }
+        if next_token > 0 && tokens[next_token - 1].pos.line_end == t.pos.line {
+            tokens[next_token - 1].trailing_trivia.push(t);
+        } else if next_token < tokens.len() {
+            tokens[next_token].leading_trivia.push(t);
+        } else {
+            eof_trivia.push(t);
+        }
+    }
+    return eof_trivia;
+}
+
+fn keyword(identifier: &str) -> Option<Type> {
+    return match identifier {
+        "and" => Some(Type::AND),
+        "any" => Some(Type::ANY),
+        "as" => Some(Type::AS),
+        "assert" => Some(Type::ASSERT),
+        "bool" => Some(Type::BOOL),
+        "break" => Some(Type::BREAK),
+        "continue" => Some(Type::CONTINUE),
+        "do" => Some(Type::DO),
+        "else" => Some(Type::ELSE),
+        "end" => Some(Type::END),
+        "enum" => Some(Type::ENUM),
+        "export" => Some(Type::EXPORT),
+        "f32" => Some(Type::F32),
+        "f64" => Some(Type::F64),
+        "false" => Some(Type::FALSE),
+        "fn" => Some(Type::FN),
+        "for" => Some(Type::FOR),
+        "from" => Some(Type::FROM),
+        "i32" => Some(Type::I32),
+        "i64" => Some(Type::I64),
+        "if" => Some(Type::IF),
+        "import" => Some(Type::IMPORT),
+        "in" => Some(Type::IN),
+        "interface" => Some(Type::INTERFACE),
+        "let" => Some(Type::LET),
+        "loop" => Some(Type::LOOP),
+        "match" => Some(Type::MATCH),
+        "not" => Some(Type::NOT),
+        "or" => Some(Type::OR),
+        "pass" => Some(Type::PASS),
+        "return" => Some(Type::RETURN),
+        "string" => Some(Type::STRING),
+        "test" => Some(Type::TEST),
+        "then" => Some(Type::THEN),
+        "true" => Some(Type::TRUE),
+        "try" => Some(Type::TRY),
+        "type" => Some(Type::TYPE),
+        "u32" => Some(Type::U32),
+        "while" => Some(Type::WHILE),
+        _ => None,
+    };
+}
@@ -3,7 +3,9 @@ pub mod ast;
 pub mod codegen;
 pub mod compiler;
 pub mod config;
+pub mod doc;
 pub mod execution;
 pub mod lex;
+pub mod module;
 pub mod parse;
 pub mod r#type;
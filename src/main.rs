@@ -3,29 +3,36 @@ mod ast;
 mod codegen;
 mod compiler;
 mod config;
+mod doc;
 mod execution;
 mod lex;
+mod module;
 mod parse;
 mod r#type;
 
 use clap::{arg, command, Parser};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
+use std::path::Path;
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 use std::{env, vec};
 use std::{fs, io::Write};
 
-use analyse::TypeCheckError;
-use codegen::{BytecodeGenerator, Chunk, CodegenContext, CodegenResult, SlotManager};
-use compiler::{CompilationResult, Compiler, CompilerFlags};
+use analyse::{TypeCheckError, TypeCheckWarning};
+use codegen::{BytecodeGenerator, Chunk, CodegenContext, CodegenError, CodegenResult, SlotManager};
+use compiler::{CompilationError, CompilationResult, Compiler, CompilerFlags};
 use config::Config;
 use deepsize::DeepSizeOf;
+use doc::DocFormat;
 use execution::ExecutionResult;
 use execution::Heap;
 use execution::ProcessContext;
+use execution::RuntimeError;
 use execution::{ExecutionEngine, SharedExecutionContext};
+use lex::{LexError, LexErrorKind, Position, Type};
+use parse::{Expected, ParseError};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -116,7 +123,7 @@ fn print_typecheck_error(source: String, typecheck_err: TypeCheckError) {
             println!("{}", split_source[lhs.line as usize]);
             let left_squiggle = "^".repeat((lhs.index_end - lhs.index) as usize);
             let right_squiggle = "^".repeat((rhs.index_end - rhs.index) as usize);
-            let offset = rhs.index - lhs.index + 1;
+            let offset = rhs.index.saturating_sub(lhs.index_end);
             println!(
                 "{}{}{}{}",
                 " ".repeat(lhs.index as usize),
@@ -124,7 +131,11 @@ fn print_typecheck_error(source: String, typecheck_err: TypeCheckError) {
                 " ".repeat(offset as usize),
                 right_squiggle
             );
-            println!("{:?} not assignable to {:?}.\n", rhs_type, lhs_type)
+            println!(
+                "{} not assignable to {}.\n",
+                rhs_type.print(),
+                lhs_type.print()
+            )
         }
         TypeCheckError::UNKNOWN_VARIABLE(t) => {
             println!("{}", split_source[t.pos.line as usize]);
@@ -139,6 +150,439 @@ fn print_typecheck_error(source: String, typecheck_err: TypeCheckError) {
             println!("unknown data type {:?}.\n", data_type);
         }
         TypeCheckError::MISSING_ARGUMENT => println!("missing argument"),
+        TypeCheckError::UNRESOLVED_GENERIC(name, pos) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            println!(
+                "can't work out what ${} is from the arguments, try giving it a type.\n",
+                name
+            );
+        }
+        TypeCheckError::UNKNOWN_MODULE(pos, module) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            println!("can't find module {}.\n", module);
+        }
+        TypeCheckError::UNKNOWN_EXPORT(t, module) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let left_squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), left_squiggle);
+            println!("{} doesn't export {}.\n", module, t.as_identifier());
+        }
+        TypeCheckError::INTERFACE_NOT_SATISFIED(pos, typ, interface, method, wanted, found) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            match found {
+                Some(found) => println!(
+                    "{} doesn't satisfy {}, {} should be {} but is {}.\n",
+                    typ.print(),
+                    interface,
+                    method,
+                    wanted.print(),
+                    found.print()
+                ),
+                None => println!(
+                    "{} doesn't satisfy {}, it has no {} method.\n",
+                    typ.print(),
+                    interface,
+                    method
+                ),
+            }
+        }
+        TypeCheckError::WRONG_ARGUMENT_COUNT(pos, name, expected, found) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            println!(
+                "{} takes {} but was given {}.\n",
+                name,
+                arguments(expected),
+                found
+            );
+        }
+        TypeCheckError::UNKNOWN_FIELD(pos, typ, field) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            println!("{} has no field called {}.\n", typ, field);
+        }
+        TypeCheckError::UNSET_FIELD(pos, typ, field) => {
+            println!("{}", split_source[pos.line as usize]);
+            let left_squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), left_squiggle);
+            println!("{} is missing the field {}.\n", typ, field);
+        }
+    }
+}
+
+fn print_lex_error(source: &String, lex_err: LexError) {
+    println!("Lex Error:\n");
+    let split_source = source.lines().collect::<Vec<&str>>();
+
+    let pos = lex_err.pos;
+    let line = split_source.get(pos.line as usize).unwrap_or(&"");
+    println!("{}", line);
+    // errors spanning lines only get underlined on their first line
+    let width = if pos.line_end == pos.line {
+        pos.index_end.saturating_sub(pos.index)
+    } else {
+        (line.chars().count() as u32).saturating_sub(pos.index)
+    };
+    let squiggle = "^".repeat(std::cmp::max(width, 1) as usize);
+    println!("{}{}", " ".repeat(pos.index as usize), squiggle);
+    match lex_err.kind {
+        LexErrorKind::UNTERMINATED_STRING => println!("unterminated string.\n"),
+        LexErrorKind::UNTERMINATED_COMMENT => println!("unterminated block comment.\n"),
+        LexErrorKind::UNEXPECTED_CHARACTER(c) => println!("unexpected character {:?}.\n", c),
+        LexErrorKind::MALFORMED_NUMBER(n) => println!("malformed number {:?}.\n", n),
+        LexErrorKind::NUMBER_OUT_OF_RANGE(n, typ) => {
+            println!("number {} is out of range for {:?}.\n", n, typ)
+        }
+        LexErrorKind::INVALID_ESCAPE(c) => println!("invalid escape sequence '\\{}'.\n", c),
+        LexErrorKind::UNTERMINATED_INTERPOLATION => {
+            println!("unterminated interpolation, expected '}}'.\n")
+        }
+        LexErrorKind::EMPTY_INTERPOLATION => println!("empty interpolation in string.\n"),
+    }
+}
+
+fn print_parse_error(source: &String, parse_err: ParseError) {
+    println!("Parse Error:\n");
+    let split_source = source.lines().collect::<Vec<&str>>();
+
+    let pos = parse_err.pos;
+    let line = split_source.get(pos.line as usize).unwrap_or(&"");
+    println!("{}", line);
+    let width = if pos.line_end == pos.line {
+        pos.index_end.saturating_sub(pos.index)
+    } else {
+        (line.chars().count() as u32).saturating_sub(pos.index)
+    };
+    let squiggle = "^".repeat(std::cmp::max(width, 1) as usize);
+    println!("{}{}", " ".repeat(pos.index as usize), squiggle);
+    let expected = match parse_err.expected {
+        Expected::TOKEN(typ) => format!("{:?}", typ),
+        Expected::EXPRESSION => "an expression".to_string(),
+        Expected::IDENTIFIER => "an identifier".to_string(),
+        Expected::STRING => "a string".to_string(),
+        Expected::TYPE => "a type".to_string(),
+        Expected::END_OF_INTERPOLATION => "'}' to close the interpolation".to_string(),
+        Expected::ENCLOSING_LOOP => {
+            println!(
+                "{:?} can only be used inside a loop.\n",
+                parse_err.found.unwrap()
+            );
+            return;
+        }
+        Expected::DEFAULT_VALUE => {
+            println!("parameters after one with a default value need a default too.\n");
+            return;
+        }
+        Expected::KEYWORD_ARGUMENT => {
+            println!("positional arguments can't come after keyword arguments.\n");
+            return;
+        }
+        Expected::DECLARATION => "a function, type, enum, interface or variable".to_string(),
+        Expected::TOP_LEVEL => {
+            println!("only the top level of a module can export.\n");
+            return;
+        }
+        Expected::NUMBER_IN_RANGE(typ) => {
+            if let Some(Type::NUMBER(n)) = parse_err.found {
+                println!("number {} is out of range for {:?}.\n", n, typ);
+                return;
+            }
+            format!("a number in range for {:?}", typ)
+        }
+    };
+    match parse_err.found {
+        Some(found) => println!("expected {} but found {:?}.\n", expected, found),
+        None => println!("expected {} but reached the end of the file.\n", expected),
+    }
+}
+
+// i.e. 1 argument or 2 arguments
+fn arguments(n: usize) -> String {
+    if n == 1 {
+        return "1 argument".to_string();
+    }
+    return format!("{} arguments", n);
+}
+
+// the source line at pos with the span underlined
+fn print_caret(source: &String, pos: Option<Position>) {
+    if let Some(pos) = pos {
+        let split_source = source.lines().collect::<Vec<&str>>();
+        println!("{}", split_source.get(pos.line as usize).unwrap_or(&""));
+        let width = if pos.line_end == pos.line {
+            pos.index_end.saturating_sub(pos.index)
+        } else {
+            1
+        };
+        println!(
+            "{}{}",
+            " ".repeat(pos.index as usize),
+            "^".repeat(std::cmp::max(width, 1) as usize)
+        );
+    }
+}
+
+// paths under the current directory are shown relative to it
+fn relative_path(path: &Path) -> String {
+    let current_dir = env::current_dir().unwrap_or_default();
+    return path
+        .strip_prefix(&current_dir)
+        .unwrap_or(path)
+        .display()
+        .to_string();
+}
+
+fn print_runtime_error(source: &String, runtime_err: RuntimeError) {
+    match runtime_err {
+        RuntimeError::DESTRUCTURE_MISMATCH(line, expected, found) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!(
+                "expected {} values to destructure but found {}.\n",
+                expected, found
+            );
+        }
+        RuntimeError::CANNOT_DESTRUCTURE(line, column, expected, value) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{}^", " ".repeat(column));
+            println!("cannot destructure {}, expected {}.\n", value, expected);
+        }
+        RuntimeError::MISSING_FIELD(line, type_name, field) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} has no field {} to destructure.\n", type_name, field);
+        }
+        RuntimeError::NO_MATCH(line, column, value) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{}{}", " ".repeat(column), "^".repeat("match".len()));
+            println!("no case matched {}.\n", value);
+        }
+        RuntimeError::MISSING_ARGUMENT(line, name, param) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} is missing the argument {}.\n", name, param);
+        }
+        RuntimeError::UNKNOWN_ARGUMENT(line, name, arg) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} has no parameter called {}.\n", name, arg);
+        }
+        RuntimeError::DUPLICATE_ARGUMENT(line, name, param) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} was given more than one value for {}.\n", name, param);
+        }
+        RuntimeError::TOO_MANY_ARGUMENTS(line, name, expected, found) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!(
+                "{} takes at most {} but was given {}.\n",
+                name,
+                arguments(expected),
+                found
+            );
+        }
+        RuntimeError::WRONG_ARGUMENT_COUNT(line, name, expected, found) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!(
+                "{} takes {} but was given {}.\n",
+                name,
+                arguments(expected),
+                found
+            );
+        }
+        RuntimeError::INVALID_ARGUMENT(line, name, value) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} can't be passed to {}.\n", value, name);
+        }
+        RuntimeError::UNKNOWN_FIELD(line, name, field) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} has no field called {}.\n", name, field);
+        }
+        RuntimeError::UNSET_FIELD(line, name, field) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} is missing the field {}.\n", name, field);
+        }
+        RuntimeError::POSITIONAL_CONSTRUCTOR(line, name) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!(
+                "{} can only be constructed with keyword arguments i.e. {}(field=value).\n",
+                name, name
+            );
+        }
+        RuntimeError::NOT_CALLABLE(line, value) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} can't be called.\n", value);
+        }
+        RuntimeError::NOT_ITERABLE(line, value) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} can't be looped over.\n", value);
+        }
+        RuntimeError::OUT_OF_BOUNDS(pos, index, len) => {
+            println!("Runtime Error:\n");
+            print_caret(source, pos);
+            println!("index {} is out of bounds for length {}.\n", index, len);
+        }
+        RuntimeError::INVALID_INDEX(pos, index) => {
+            println!("Runtime Error:\n");
+            print_caret(source, pos);
+            println!("{} can't be used as an index.\n", index);
+        }
+        RuntimeError::REVERSED_RANGE(pos, start, end) => {
+            println!("Runtime Error:\n");
+            print_caret(source, pos);
+            println!("range {}..{} ends before it starts.\n", start, end);
+        }
+        RuntimeError::UNKNOWN_EXPORT(line, path, name) => {
+            println!("Runtime Error:\n");
+            let split_source = source.lines().collect::<Vec<&str>>();
+            println!("{}", split_source.get(line).unwrap_or(&""));
+            println!("{} doesn't export {}.\n", relative_path(&path), name);
+        }
+        RuntimeError::CIRCULAR_IMPORT(cycle) => {
+            println!("Runtime Error:\n");
+            let files = cycle
+                .iter()
+                .map(|path| relative_path(path))
+                .collect::<Vec<String>>();
+            println!("circular import {}.\n", files.join(" -> "));
+        }
+        RuntimeError::COMPILATION_ERROR(path, err) => {
+            println!("in {}:\n", relative_path(&path));
+            let module_source = fs::read_to_string(&path).unwrap_or_default();
+            print_compilation_error(&module_source, *err);
+        }
+        e => println!("encountered runtime exception {:?}", e),
+    }
+}
+
+fn print_compilation_error(source: &String, compilation_err: CompilationError) {
+    match compilation_err {
+        CompilationError::LEX_ERRORS(errors) => {
+            for err in errors {
+                print_lex_error(source, err);
+            }
+        }
+        CompilationError::PARSE_ERRORS(errors) => {
+            for err in errors {
+                print_parse_error(source, err);
+            }
+        }
+        CompilationError::TYPECHECK_ERROR(err) => print_typecheck_error(source.clone(), err),
+        CompilationError::CODEGEN_ERRORS(errors) => {
+            for err in errors {
+                print_codegen_error(source, err);
+            }
+        }
+    }
+}
+
+fn print_codegen_error(source: &String, codegen_err: CodegenError) {
+    println!("Compile Error:\n");
+    let split_source = source.lines().collect::<Vec<&str>>();
+
+    match codegen_err {
+        CodegenError::UNKNOWN_VARIABLE(t) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), squiggle);
+            println!("unknown variable {}.\n", t.as_identifier());
+        }
+        CodegenError::UNDECLARED_VARIABLE(t) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), squiggle);
+            println!(
+                "{} hasn't been declared, use let {} = ... to declare it.\n",
+                t.as_identifier(),
+                t.as_identifier()
+            );
+        }
+        CodegenError::INVALID_ANNOTATION(t, reason) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), squiggle);
+            println!("can't use @{} here, {}.\n", t.as_identifier(), reason);
+        }
+        CodegenError::NON_CONSTANT_ANNOTATION_ARGUMENT(pos) => {
+            println!("{}", split_source[pos.line as usize]);
+            let squiggle = "^".repeat((pos.index_end - pos.index) as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), squiggle);
+            println!("annotation arguments must be constants.\n");
+        }
+        CodegenError::TEST_ONLY(t) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), squiggle);
+            println!(
+                "{} is marked @test_only so it can only be used in tests.\n",
+                t.as_identifier()
+            );
+        }
+        CodegenError::JUMP_TOO_FAR(pos) => {
+            let line = split_source[pos.line as usize];
+            println!("{}", line);
+            // a block's position runs to its end, so only the line it starts on is underlined
+            let width = if pos.line_end == pos.line {
+                pos.index_end - pos.index
+            } else {
+                line.len() as u32 - pos.index
+            };
+            let squiggle = "^".repeat(width as usize);
+            println!("{}{}", " ".repeat(pos.index as usize), squiggle);
+            println!(
+                "jumps can only reach the first 256 instructions of a function, try splitting it up.\n"
+            );
+        }
+    }
+}
+
+fn print_typecheck_warning(source: &String, warning: TypeCheckWarning) {
+    println!("Warning:\n");
+    let split_source = source.lines().collect::<Vec<&str>>();
+
+    match warning {
+        TypeCheckWarning::DEPRECATED(t, message) => {
+            println!("{}", split_source[t.pos.line as usize]);
+            let squiggle = "^".repeat((t.pos.index_end - t.pos.index) as usize);
+            println!("{}{}", " ".repeat(t.pos.index as usize), squiggle);
+            match message {
+                Some(message) => println!("{} is deprecated: {}.\n", t.as_identifier(), message),
+                None => println!("{} is deprecated.\n", t.as_identifier()),
+            }
+        }
     }
 }
 
@@ -148,15 +592,18 @@ fn exec(args: Args) {
     fs::create_dir_all("./gila-build");
 
     let config = Config {
-        max_memory: 100_000,
+        max_memory: 1_000_000,
         gc_threshold: 0.9,
     };
     let mut shared_execution_context = SharedExecutionContext {
         heap: Heap {
             live_slots: HashMap::new(),
             dead_objects: HashMap::new(),
+            next_index: 0,
         },
         gila_abis_dlls: vec![],
+        modules: HashMap::new(),
+        importing: vec![],
     };
 
     let mut codegen_context = CodegenContext {
@@ -164,12 +611,16 @@ fn exec(args: Args) {
         chunks: vec![Chunk {
             slot_manager: SlotManager::new(),
             debug_line_info: vec![],
+            debug_position_info: HashMap::new(),
             constant_pool: vec![],
             gc_ref_data: vec![],
             instructions: vec![],
             variable_map: HashMap::new(),
             string_interns: HashMap::new(),
+            cells: HashSet::new(),
+            upvalues: vec![],
         }],
+        prelude_result: HashMap::new(),
     };
     let mut environment = ProcessContext {
         stack_frame_pointer: 0,
@@ -177,7 +628,7 @@ fn exec(args: Args) {
         native_fns: HashMap::new(),
     };
 
-    let prelude_source = fs::read_to_string("./prelude/prelude.gila").expect("Unable to read file");
+    let prelude_source = fs::read_to_string(module::prelude_path()).expect("Unable to read file");
 
     // println!("doing prelude...");
     // let prelude_compile_result = compiler.compile_and_exec(
@@ -201,28 +652,46 @@ fn exec(args: Args) {
 
     let file_to_exec = args.file;
     let source = fs::read_to_string(file_to_exec.to_string()).expect("Unable to read file");
+    // imports are found relative to the file being run
+    shared_execution_context
+        .importing
+        .push(module::canonical(Path::new(&file_to_exec)));
 
     let mut full_source = prelude_source.clone();
+    full_source.push('\n');
     full_source.push_str(&source);
 
+    let warning_source = full_source.clone();
+    compiler.on_warnings = Some(Box::new(move |warnings| {
+        for warning in warnings {
+            print_typecheck_warning(&warning_source, warning.clone());
+        }
+    }));
     let result = compiler.compile_and_exec(
         file_to_exec.to_string(),
         CompilerFlags {
             init_builtins: true,
             dump_bytecode: args.dump_bytecode,
+            test_build: false,
         },
-        full_source,
+        full_source.clone(),
         config,
         Some(codegen_context),
         Some(environment),
         Some(shared_execution_context),
     );
+    if result.is_err() {
+        print_compilation_error(&full_source, result.err().unwrap());
+        std::process::exit(1);
+    }
+    let result = result.unwrap();
 
     match result.execution_result.result {
         Ok(o) => {}
         Err(e) => {
-            println!("encountered runtime exception {:?}", e);
+            print_runtime_error(&full_source, e);
             // execution_engine.print_stacktrace();
+            std::process::exit(1);
         }
     }
     let denominator = 1000_000;
@@ -250,15 +719,18 @@ fn do_test(args: Args) {
     let mut compiler = Compiler::new();
 
     let config = Config {
-        max_memory: 100_000,
+        max_memory: 1_000_000,
         gc_threshold: 0.9,
     };
     let mut shared_execution_context = SharedExecutionContext {
         heap: Heap {
             live_slots: HashMap::new(),
             dead_objects: HashMap::new(),
+            next_index: 0,
         },
         gila_abis_dlls: vec![],
+        modules: HashMap::new(),
+        importing: vec![],
     };
 
     let mut codegen_context = CodegenContext {
@@ -266,12 +738,16 @@ fn do_test(args: Args) {
         chunks: vec![Chunk {
             slot_manager: SlotManager::new(),
             debug_line_info: vec![],
+            debug_position_info: HashMap::new(),
             constant_pool: vec![],
             gc_ref_data: vec![],
             instructions: vec![],
             variable_map: HashMap::new(),
             string_interns: HashMap::new(),
+            cells: HashSet::new(),
+            upvalues: vec![],
         }],
+        prelude_result: HashMap::new(),
     };
     let mut environment = ProcessContext {
         stack_frame_pointer: 0,
@@ -279,19 +755,29 @@ fn do_test(args: Args) {
         native_fns: HashMap::new(),
     };
 
-    let prelude_source = fs::read_to_string("./prelude/prelude.gila").expect("Unable to read file");
+    let prelude_source = fs::read_to_string(module::prelude_path()).expect("Unable to read file");
     let prelude_compile_result = compiler.compile_and_exec(
         "prelude".to_string(),
         CompilerFlags {
             init_builtins: true,
             dump_bytecode: false,
+            test_build: true,
         },
-        prelude_source,
+        prelude_source.clone(),
         config.clone(),
         Some(codegen_context.clone()),
         Some(environment.clone()),
         Some(shared_execution_context.clone()),
     );
+    if prelude_compile_result.is_err() {
+        print_compilation_error(&prelude_source, prelude_compile_result.err().unwrap());
+        std::process::exit(1);
+    }
+    let prelude_compile_result = prelude_compile_result.unwrap();
+    if let Err(e) = prelude_compile_result.execution_result.result.clone() {
+        print_runtime_error(&prelude_source, e);
+        std::process::exit(1);
+    }
 
     codegen_context = prelude_compile_result.codegen_result.codegen_context;
     environment = prelude_compile_result.execution_result.process_context;
@@ -302,19 +788,40 @@ fn do_test(args: Args) {
     let file_to_test = args.file;
 
     let source = fs::read_to_string(file_to_test.to_string()).expect("Unable to read file");
-
+    shared_execution_context
+        .importing
+        .push(module::canonical(Path::new(&file_to_test)));
+
+    let warning_source = source.clone();
+    compiler.on_warnings = Some(Box::new(move |warnings| {
+        for warning in warnings {
+            print_typecheck_warning(&warning_source, warning.clone());
+        }
+    }));
     let result = compiler.compile_and_exec(
         file_to_test.to_string(),
         CompilerFlags {
             init_builtins: false,
             dump_bytecode: args.dump_bytecode,
+            test_build: true,
         },
-        source,
+        source.clone(),
         config.clone(),
         Some(codegen_context.clone()),
         Some(environment),
         Some(shared_execution_context),
     );
+    if result.is_err() {
+        print_compilation_error(&source, result.err().unwrap());
+        std::process::exit(1);
+    }
+    let result = result.unwrap();
+    // the tests are compiled from their names, which warnings can't point into
+    compiler.on_warnings = None;
+    if let Err(e) = result.execution_result.result.clone() {
+        print_runtime_error(&source, e);
+        std::process::exit(1);
+    }
 
     codegen_context = result.clone().codegen_result.codegen_context;
     environment = result.clone().execution_result.process_context;
@@ -335,6 +842,7 @@ fn do_test(args: Args) {
             CompilerFlags {
                 init_builtins: false,
                 dump_bytecode: false,
+                test_build: true,
             },
             format!("{}()", test.to_string()),
             config.clone(),
@@ -342,6 +850,11 @@ fn do_test(args: Args) {
             Some(environment.clone()),
             Some(shared_execution_context.clone()),
         );
+        if res.is_err() {
+            println!("doing {:<25}... {}.", test, "❌");
+            continue;
+        }
+        let res = res.unwrap();
 
         // let res = exec_shared_ctx(
         //     format!("{}()", test.to_string()),
@@ -357,7 +870,7 @@ fn do_test(args: Args) {
                 let dynamic = obj.as_dynamic_object(&shared_execution_context);
                 match dynamic {
                     Ok(dynamic_obj) => {
-                        if dynamic_obj.fields.contains_key("Data") {
+                        if dynamic_obj.is_variant("Data") {
                             println!("doing {:<25}... {}.", test, "✅");
                         } else {
                             println!("doing {:<25}... {}.", test, "❌");
@@ -385,6 +898,65 @@ fn do_test(args: Args) {
     );
 }
 
+// documents every .gila file under the directory given as the file, in markdown unless html is
+// passed as the extra argument
+fn do_doc(args: Args) {
+    let format = match args.extra.first() {
+        Some(name) => match DocFormat::from_name(name) {
+            Some(format) => format,
+            None => {
+                println!("unknown doc format {}, expected md or html.", name);
+                std::process::exit(1);
+            }
+        },
+        None => DocFormat::MARKDOWN,
+    };
+    let root = Path::new(&args.file);
+    let files = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else {
+        doc::gila_files(root)
+    };
+
+    let mut modules = vec![];
+    let mut failed = false;
+    for file in files {
+        let source = fs::read_to_string(&file).expect("Unable to read file");
+        let tokens = lex::Lexer::new().lex(source.clone());
+        if tokens.is_err() {
+            print_compilation_error(&source, CompilationError::LEX_ERRORS(tokens.err().unwrap()));
+            failed = true;
+            continue;
+        }
+        let tokens = tokens.unwrap();
+        let parse_result = parse::Parser::new(&tokens).parse();
+        if parse_result.errors.len() > 0 {
+            print_compilation_error(&source, CompilationError::PARSE_ERRORS(parse_result.errors));
+            failed = true;
+            continue;
+        }
+        let name = match root.is_file() {
+            true => doc::module_name(root.parent().unwrap_or(Path::new("")), &file),
+            false => doc::module_name(root, &file),
+        };
+        modules.push((name, parse_result.ast));
+    }
+
+    let out_dir = Path::new("./gila-build/doc");
+    fs::create_dir_all(out_dir).expect("Unable to create doc directory");
+    for (name, contents) in doc::render(&modules, format) {
+        fs::write(out_dir.join(name), contents).expect("Unable to write doc");
+    }
+    println!(
+        "documented {} modules in {}",
+        modules.len(),
+        out_dir.display()
+    );
+    if failed {
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     // let args: Vec<String> = std::env::args().collect();
     // let file_to_exec: String = args[3].to_string();
@@ -408,6 +980,7 @@ fn main() {
         "run" => exec(args),
         "repl" => repl(args),
         "test" => do_test(args),
+        "doc" => do_doc(args),
         _ => panic!(),
     }
 }
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    ast::{ASTNode, Statement},
+    lex, parse,
+};
+
+// where the code for an imported module lives
+pub enum ModuleSource {
+    // a .gila file
+    FILE(PathBuf),
+    // a directory, where each file in it is a submodule
+    PACKAGE(PathBuf),
+}
+
+// where imports are looked for, in order: the directory of the file doing the importing (or the
+// current directory when there isn't one), each directory in GILA_PATH and then the directory gila
+// is installed in so std can always be imported
+pub fn search_path(importer: Option<&PathBuf>) -> Vec<PathBuf> {
+    let importer_dir = importer
+        .and_then(|importer| importer.parent())
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or(PathBuf::from("."));
+    let mut paths = vec![importer_dir];
+    if let Some(gila_path) = env::var_os("GILA_PATH") {
+        paths.extend(env::split_paths(&gila_path));
+    }
+    if let Some(home) = home() {
+        paths.push(home);
+    }
+    return paths;
+}
+
+// the directory gila is installed in, which is GILA_HOME when it's set and otherwise the closest
+// directory above the executable with std in it, so both an installed gila and one built under
+// target/ find it
+pub fn home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("GILA_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    let exe = canonical(&env::current_exe().ok()?);
+    return exe
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("std").is_dir())
+        .map(|dir| dir.to_path_buf());
+}
+
+// the prelude is installed alongside std
+pub fn prelude_path() -> PathBuf {
+    return home()
+        .unwrap_or(PathBuf::from("."))
+        .join("prelude")
+        .join("prelude.gila");
+}
+
+// a.b.c is either the directory a/b/c or the file a/b/c.gila in the first search path which has
+// it. the path is canonical so the same module is always found at the same path
+pub fn find(module: &str, search_path: &Vec<PathBuf>) -> Option<ModuleSource> {
+    let relative_path = module.replace(".", "/");
+    for path in search_path {
+        let full_path = path.join(&relative_path);
+        if full_path.is_dir() {
+            return Some(ModuleSource::PACKAGE(canonical(&full_path)));
+        }
+        let full_path_with_extension = full_path.with_extension("gila");
+        if full_path_with_extension.is_file() {
+            return Some(ModuleSource::FILE(canonical(&full_path_with_extension)));
+        }
+    }
+    return None;
+}
+
+pub fn canonical(path: &Path) -> PathBuf {
+    return fs::canonicalize(path).unwrap_or(path.to_path_buf());
+}
+
+// the names declared with export at the top level of a module
+pub fn exported_names(ast: &ASTNode) -> Vec<Rc<String>> {
+    let Statement::PROGRAM(items) = &ast.statement else {
+        return vec![];
+    };
+    return items.iter().filter_map(exported_name).collect();
+}
+
+// the export can be under doc comments and annotations, as well as over them
+fn exported_name(node: &ASTNode) -> Option<Rc<String>> {
+    match &node.statement {
+        Statement::EXPORT(declaration) => declaration.declared_name(),
+        Statement::DOC(_, e) | Statement::ANNOTATION(_, _, e) => exported_name(e),
+        _ => None,
+    }
+}
+
+// none if the file can't be read or lexed
+pub fn parse(path: &Path) -> Option<ASTNode> {
+    let code = fs::read_to_string(path).ok()?;
+    let tokens = lex::Lexer::new().lex(code).ok()?;
+    return Some(parse::Parser::new(&tokens).parse().ast);
+}
+
+// what can be imported from a module without running it, none if it can't be read or lexed, in
+// which case importing it will report the problem
+pub fn exports(source: &ModuleSource) -> Option<HashSet<Rc<String>>> {
+    match source {
+        ModuleSource::FILE(path) => {
+            let ast = parse(path)?;
+            return Some(exported_names(&ast).into_iter().collect());
+        }
+        ModuleSource::PACKAGE(path) => {
+            let mut submodules: HashSet<Rc<String>> = HashSet::new();
+            for file in fs::read_dir(path).ok()? {
+                let name = file
+                    .ok()?
+                    .file_name()
+                    .to_string_lossy()
+                    .replace(".gila", "");
+                submodules.insert(Rc::new(name));
+            }
+            return Some(submodules);
+        }
+    }
+}
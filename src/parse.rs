@@ -1,18 +1,15 @@
-use std::vec;
+use std::{rc::Rc, vec};
 
 use crate::{
-    ast::{ASTNode, Op, Statement},
-    lex::{Position, Token, Type},
+    ast::{ASTNode, Op, Pattern, Statement, UnaryOp},
+    lex::{Position, StringPart, Token, Type},
     r#type::DataType,
 };
 
 macro_rules! consume_token {
     ($self:expr, $expected:expr) => {
-        if $self.tokens[$self.counter].typ != $expected {
-            panic!(
-                "Unexpected token: expected {:?}, found {:?}",
-                $expected, $self.tokens[$self.counter].typ
-            );
+        if !$self.check($expected) {
+            return Err($self.error(Expected::TOKEN($expected)));
         }
         $self.counter += 1;
     };
@@ -20,12 +17,15 @@ macro_rules! consume_token {
 
 macro_rules! get_position {
     ($self:expr) => {
-        $self.tokens[$self.counter].pos.clone()
+        $self.current_position()
     };
 }
 
 macro_rules! get_next {
-    ($self:expr) => {{
+    ($self:expr, $expected:expr) => {{
+        if $self.end() {
+            return Err($self.error($expected));
+        }
         let t = &$self.tokens[$self.counter];
         $self.counter += 1;
         t
@@ -34,8 +34,97 @@ macro_rules! get_next {
 
 macro_rules! get_current {
     ($self:expr) => {
-        &$self.tokens[$self.counter];
+        &$self.tokens[$self.counter]
+    };
+}
+
+const COMPARISON_PRECEDENCE: u8 = 3;
+const POW_PRECEDENCE: u8 = 10;
+
+// the binding power of each infix operator, the higher it is the tighter it binds
+fn binary_operator(typ: &Type) -> Option<(Op, u8)> {
+    match typ {
+        Type::OR => Some((Op::LOGICAL_OR, 1)),
+        Type::AND => Some((Op::LOGICAL_AND, 2)),
+        Type::EQUALS => Some((Op::EQ, COMPARISON_PRECEDENCE)),
+        Type::NOT_EQUALS => Some((Op::NEQ, COMPARISON_PRECEDENCE)),
+        Type::GREATER_THAN => Some((Op::GT, COMPARISON_PRECEDENCE)),
+        Type::GREATER_EQ => Some((Op::GE, COMPARISON_PRECEDENCE)),
+        Type::LESS_THAN => Some((Op::LT, COMPARISON_PRECEDENCE)),
+        Type::LESS_EQ => Some((Op::LE, COMPARISON_PRECEDENCE)),
+        Type::IN => Some((Op::IN, COMPARISON_PRECEDENCE)),
+        Type::BITWISE_OR => Some((Op::BITWISE_OR, 4)),
+        Type::BITWISE_XOR => Some((Op::BITWISE_XOR, 5)),
+        Type::BITWISE_AND => Some((Op::BITWISE_AND, 6)),
+        Type::SHIFT_LEFT => Some((Op::SHIFT_LEFT, 7)),
+        Type::SHIFT_RIGHT => Some((Op::SHIFT_RIGHT, 7)),
+        Type::ADD => Some((Op::ADD, 8)),
+        Type::SUB => Some((Op::SUB, 8)),
+        Type::MUL => Some((Op::MUL, 9)),
+        Type::DIV => Some((Op::DIV, 9)),
+        Type::MOD => Some((Op::MOD, 9)),
+        Type::POW => Some((Op::POW, POW_PRECEDENCE)),
+        _ => None,
+    }
+}
+
+// doc comments are only kept on declarations. an export is documented inside so it still directly
+// wraps the declaration, and the comment closest to the declaration wins if there are two
+fn documented(doc: Option<Rc<String>>, node: ASTNode) -> ASTNode {
+    let Some(doc) = doc else {
+        return node;
     };
+    match node.statement {
+        Statement::EXPORT(declaration) => ASTNode {
+            statement: Statement::EXPORT(Box::new(documented(Some(doc), *declaration))),
+            position: node.position,
+        },
+        Statement::DOC(_, _) => node,
+        _ if node.declared_name().is_none() => node,
+        _ => {
+            let position = node.position.clone();
+            ASTNode {
+                statement: Statement::DOC(doc, Box::new(node)),
+                position,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    TOKEN(Type),
+    EXPRESSION,
+    IDENTIFIER,
+    STRING,
+    TYPE,
+    // a token was left over inside the {} of an interpolated string
+    END_OF_INTERPOLATION,
+    // break or continue was used outside of a loop
+    ENCLOSING_LOOP,
+    // a parameter without a default came after one with a default
+    DEFAULT_VALUE,
+    // a positional argument came after a keyword argument
+    KEYWORD_ARGUMENT,
+    // something other than a function, type, enum, interface or variable was exported
+    DECLARATION,
+    // export was used inside a block
+    TOP_LEVEL,
+    // a number literal too big for its type
+    NUMBER_IN_RANGE(DataType),
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub expected: Expected,
+    // none if we ran out of tokens
+    pub found: Option<Type>,
+    pub pos: Position,
+}
+
+pub struct ParseResult {
+    pub ast: ASTNode,
+    pub errors: Vec<ParseError>,
 }
 
 // this is used so we can disambiguate certain contexts
@@ -46,6 +135,10 @@ macro_rules! get_current {
 pub struct ParseContext {
     pub in_function_call: bool,
     pub in_group: bool,
+    // break and continue are only allowed in here, this is reset by function bodies
+    pub in_loop: bool,
+    // only the top level of a module can export
+    pub in_block: bool,
 }
 
 impl ParseContext {
@@ -53,6 +146,8 @@ impl ParseContext {
         return ParseContext {
             in_function_call: false,
             in_group: false,
+            in_loop: false,
+            in_block: false,
         };
     }
 }
@@ -60,120 +155,312 @@ impl ParseContext {
 pub struct Parser<'a> {
     pub tokens: &'a std::vec::Vec<Token>,
     pub counter: usize,
+    pub errors: Vec<ParseError>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn parse(&mut self) -> ASTNode {
+    pub fn new(tokens: &'a std::vec::Vec<Token>) -> Self {
+        return Parser {
+            tokens,
+            counter: 0,
+            errors: vec![],
+        };
+    }
+
+    pub fn parse(&mut self) -> ParseResult {
         let mut program: Vec<ASTNode> = vec![];
 
         let parse_context = ParseContext::new();
         while !self.end() {
-            program.push(self.statement(parse_context));
+            if let Some(statement) = self.recovering_statement(parse_context) {
+                program.push(statement);
+            }
         }
         // Statement::PROGRAM(vec![Statement::EXPRESSION(Expression::BIN_OP(
         //     Box::new(Expression::LITERAL_NUM(1.0)),
         //     Box::new(Expression::LITERAL_NUM(1.0)),
         //     Op::ADD,
         // ))])
-        ASTNode {
-            statement: Statement::PROGRAM(program),
-            position: Position {
-                index: 0,
-                line: 0,
-                index_end: 0,
-                line_end: 0,
+        ParseResult {
+            ast: ASTNode {
+                statement: Statement::PROGRAM(program),
+                position: Position {
+                    index: 0,
+                    line: 0,
+                    index_end: 0,
+                    line_end: 0,
+                },
             },
+            errors: self.errors.clone(),
         }
     }
 
-    fn statement(&mut self, parse_context: ParseContext) -> ASTNode {
+    // parses a statement, and if it fails records the error and skips ahead so we can carry on
+    // parsing and report any other errors in one go.
+    fn recovering_statement(&mut self, parse_context: ParseContext) -> Option<ASTNode> {
+        let start = self.counter;
+        match self.statement(parse_context) {
+            Ok(statement) => Some(statement),
+            Err(e) => {
+                let line = e.pos.line;
+                self.errors.push(e);
+                self.synchronise(self.tokens[start].pos.line, line);
+                // always make progress, otherwise a stray token would loop forever
+                if self.counter == start && !self.end() {
+                    self.counter += 1;
+                }
+                None
+            }
+        }
+    }
+
+    // there are no statement terminators, so a new line (or an `end` closing the block) is where
+    // the next statement starts. a statement cut short by one on a later line (i.e. a missing
+    // bracket) carries on from that one rather than skipping it too
+    fn synchronise(&mut self, start_line: u32, error_line: u32) {
+        if let Some(current) = self.tokens.get(self.counter) {
+            let starts_line =
+                self.counter > 0 && self.tokens[self.counter - 1].pos.line_end < current.pos.line;
+            if starts_line && current.pos.line > start_line {
+                return;
+            }
+        }
+        while !self.end() {
+            let current = get_current!(self);
+            if current.typ == Type::END || current.pos.line > error_line {
+                return;
+            }
+            self.counter += 1;
+        }
+    }
+
+    fn statement(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let current: &Token = &self.tokens[self.counter];
+        let doc = current.doc_comment();
 
-        match current.typ {
+        let statement = match current.typ {
             Type::MATCH => self.matchh(parse_context),
             Type::ASSERT => self.assert(parse_context),
             Type::DO => self.block(parse_context),
             Type::TEST => self.test(parse_context),
             Type::IF => self.iff(parse_context),
             Type::FOR => self.forr(parse_context),
+            Type::WHILE => self.whilee(parse_context),
+            Type::LOOP => self.loopp(parse_context),
+            Type::BREAK | Type::CONTINUE => self.loop_control(parse_context),
             Type::RETURN => self.ret(parse_context),
+            Type::EXPORT => self.export(parse_context),
+            Type::LET => self.lett(parse_context),
+            Type::FROM => self.selective_import(parse_context),
+            Type::AMPERSAND => self.annotation(parse_context, true),
             Type::IDENTIFIER(_) => self.identifier(parse_context),
+            Type::LPAREN => {
+                if let Some(destructure) = self.try_destructure(parse_context)? {
+                    return Ok(destructure);
+                }
+                self.expression(parse_context)
+            }
             _ => self.expression(parse_context),
-        }
+        }?;
+        return Ok(documented(doc, statement));
     }
 
-    fn expression(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.import(parse_context);
+    fn expression(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let higher_precedence = self.import(parse_context)?;
         let lhs_pos = higher_precedence.position.clone();
 
-        if !self.end() && self.tokens[self.counter].typ == Type::ASSIGN {
+        // `=>` separates a match case's guard from its body, it isn't an assignment
+        if self.check(Type::ASSIGN) && self.peek(1) != Some(&Type::GREATER_THAN) {
             consume_token!(self, Type::ASSIGN);
-            let rhs = self.expression(parse_context);
+            let rhs = self.expression(parse_context)?;
             let rhs_pos = rhs.position.clone();
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::ASSIGN(Box::new(higher_precedence), Box::new(rhs)),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
 
-        higher_precedence
+        Ok(higher_precedence)
     }
 
-    fn import(&mut self, parse_context: ParseContext) -> ASTNode {
-        if self.tokens[self.counter].typ == Type::IMPORT {
+    fn import(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.check(Type::IMPORT) {
             let lhs_pos = get_position!(self);
             consume_token!(self, Type::IMPORT);
-            // todo parse module path properly
-            let mut tokens: Vec<Token> = vec![];
-            loop {
-                let t = get_next!(self);
-                tokens.push(t.clone());
-                if !self.end() && self.tokens[self.counter].typ == Type::DOT {
-                    consume_token!(self, Type::DOT);
-                } else {
+            let tokens = self.module_path()?;
+            let alias = self.alias()?;
+            let last = alias.as_ref().unwrap_or(&tokens[tokens.len() - 1]);
+            let rhs_pos = last.pos.clone();
+            return Ok(ASTNode {
+                statement: Statement::IMPORT(tokens, alias),
+                position: lhs_pos.join(rhs_pos),
+            });
+        }
+
+        return self.parse_range(parse_context);
+    }
+
+    fn selective_import(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let lhs_pos = get_position!(self);
+        consume_token!(self, Type::FROM);
+        let path = self.module_path()?;
+        consume_token!(self, Type::IMPORT);
+        let mut names: Vec<(Token, Option<Token>)> = vec![];
+        loop {
+            let name = self.expect_identifier()?;
+            let alias = self.alias()?;
+            names.push((name.clone(), alias));
+            if !self.check(Type::COMMA) {
+                break;
+            }
+            consume_token!(self, Type::COMMA);
+        }
+        let rhs_pos = self.tokens[self.counter - 1].pos.clone();
+        return Ok(ASTNode {
+            statement: Statement::FROM_IMPORT(path, names),
+            position: lhs_pos.join(rhs_pos),
+        });
+    }
+
+    // a.b.c
+    fn module_path(&mut self) -> Result<Vec<Token>, ParseError> {
+        let mut tokens: Vec<Token> = vec![];
+        loop {
+            let t = self.expect_identifier()?;
+            tokens.push(t.clone());
+            if self.check(Type::DOT) {
+                consume_token!(self, Type::DOT);
+            } else {
+                break;
+            }
+        }
+        return Ok(tokens);
+    }
+
+    // the name after `as`, if there is one
+    fn alias(&mut self) -> Result<Option<Token>, ParseError> {
+        if !self.check(Type::AS) {
+            return Ok(None);
+        }
+        consume_token!(self, Type::AS);
+        let alias = self.expect_identifier()?;
+        return Ok(Some(alias.clone()));
+    }
+
+    fn export(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if parse_context.in_block {
+            return Err(self.error(Expected::TOP_LEVEL));
+        }
+        let lhs_pos = get_position!(self);
+        consume_token!(self, Type::EXPORT);
+        let start = self.counter;
+        let declaration = self.statement(parse_context)?;
+        if declaration.declared_name().is_none() {
+            return Err(ParseError {
+                expected: Expected::DECLARATION,
+                found: Some(self.tokens[start].typ.clone()),
+                pos: declaration.position,
+            });
+        }
+        let rhs_pos = declaration.position.clone();
+        return Ok(ASTNode {
+            statement: Statement::EXPORT(Box::new(declaration)),
+            position: lhs_pos.join(rhs_pos),
+        });
+    }
+
+    // `@name` or `@name(args)` before a declaration, or before an expression when it's used inside
+    // one. whether the arguments are constants is checked when generating code
+    fn annotation(
+        &mut self,
+        parse_context: ParseContext,
+        statement: bool,
+    ) -> Result<ASTNode, ParseError> {
+        let lhs_pos = get_position!(self);
+        consume_token!(self, Type::AMPERSAND);
+        let annotation = self.expect_identifier()?.clone();
+        let mut args: Vec<ASTNode> = vec![];
+        if self.check(Type::LPAREN) {
+            consume_token!(self, Type::LPAREN);
+            // the arguments are separate, like a call's, rather than one tuple
+            let mut args_parse_context = parse_context.clone();
+            args_parse_context.in_function_call = true;
+            while !self.check(Type::RPAREN) {
+                args.push(self.expression(args_parse_context)?);
+                if !self.check(Type::COMMA) {
                     break;
                 }
+                consume_token!(self, Type::COMMA);
             }
-            let first = &tokens.clone()[0];
-            return ASTNode {
-                statement: Statement::IMPORT(tokens),
-                position: lhs_pos.join(first.pos.clone()),
-            };
+            consume_token!(self, Type::RPAREN);
         }
 
-        return self.parse_range(parse_context);
+        let annotated = if statement {
+            self.statement(parse_context)?
+        } else {
+            self.expression(parse_context)?
+        };
+        let rhs_pos = annotated.position.clone();
+        return Ok(ASTNode {
+            statement: Statement::ANNOTATION(annotation, args, Box::new(annotated)),
+            position: lhs_pos.join(rhs_pos),
+        });
     }
 
-    fn tryy(&mut self, parse_context: ParseContext) -> ASTNode {
-        if self.tokens[self.counter].typ == Type::EXCLAIM {
+    fn tryy(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.check(Type::TRY) {
             let lhs_pos = get_position!(self);
-            consume_token!(self, Type::EXCLAIM);
+            consume_token!(self, Type::TRY);
             // fixme should this be self.expression()
-            let rhs = self.call(parse_context);
+            let rhs = self.call(parse_context)?;
             let rhs_pos = rhs.position.clone();
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::TRY(Box::new(rhs)),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
 
         self.call(parse_context)
     }
 
-    fn call(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn call(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let lhs_pos = get_position!(self);
-        let higher_precedence = self.index(parse_context);
+        let mut higher_precedence = self.index(parse_context)?;
 
-        if !self.end() && self.tokens[self.counter].typ == Type::LPAREN {
+        // keep going so functions returned from calls can be called i.e. f()(), but only on the
+        // same line, otherwise a bracketed statement or match case after an expression would
+        // become an argument list
+        while self.check(Type::LPAREN)
+            && get_current!(self).pos.line == self.tokens[self.counter - 1].pos.line_end
+        {
             consume_token!(self, Type::LPAREN);
             let mut args: Vec<ASTNode> = vec![];
             let mut rhs_pos: Position;
-            if self.tokens[self.counter].typ != Type::RPAREN {
+            if !self.check(Type::RPAREN) {
                 let mut new_parse_context = parse_context.clone();
                 new_parse_context.in_function_call = true;
                 loop {
-                    args.push(self.expression(new_parse_context));
-                    if self.tokens[self.counter].typ == Type::RPAREN {
+                    let is_positional = self.peek(1) != Some(&Type::ASSIGN);
+                    if is_positional
+                        && args
+                            .iter()
+                            .any(|arg| matches!(arg.statement, Statement::ASSIGN(_, _)))
+                    {
+                        return Err(self.error(Expected::KEYWORD_ARGUMENT));
+                    }
+                    if self.check(Type::ELLIPSIS) {
+                        let spread_pos = get_position!(self);
+                        consume_token!(self, Type::ELLIPSIS);
+                        let spread = self.expression(new_parse_context)?;
+                        let pos = spread_pos.join(spread.position.clone());
+                        args.push(ASTNode {
+                            statement: Statement::SPREAD(Box::new(spread)),
+                            position: pos,
+                        });
+                    } else {
+                        args.push(self.expression(new_parse_context)?);
+                    }
+                    if self.check(Type::RPAREN) {
                         rhs_pos = get_position!(self);
                         consume_token!(self, Type::RPAREN);
                         break;
@@ -184,55 +471,93 @@ impl<'a> Parser<'a> {
                 }
             } else {
                 rhs_pos = get_position!(self);
-                get_next!(self);
+                consume_token!(self, Type::RPAREN);
             }
-            return ASTNode {
+            higher_precedence = ASTNode {
                 statement: Statement::CALL(Box::new(higher_precedence), args),
                 position: lhs_pos.join(rhs_pos),
             };
         }
 
-        higher_precedence
+        Ok(higher_precedence)
     }
 
-    fn index(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn index(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let lhs_pos = get_position!(self);
-        let higher_precedence = self.struct_access(parse_context);
-        if !self.end() && self.tokens[self.counter].typ == Type::LSQUARE {
+        let higher_precedence = self.struct_access(parse_context)?;
+        if self.check(Type::LSQUARE) {
             consume_token!(self, Type::LSQUARE);
-            let the_index = self.struct_access(parse_context);
-            let rhs_pos = self.tokens[self.counter].pos.clone();
+            let the_index = self.index_or_range(parse_context)?;
+            let rhs_pos = get_position!(self);
             consume_token!(self, Type::RSQUARE);
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::INDEX(Box::new(higher_precedence), Box::new(the_index)),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
-        higher_precedence
+        Ok(higher_precedence)
+    }
+
+    // what goes between the brackets, either an index or a range such as 1..3, ..n or n..
+    fn index_or_range(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let pos = get_position!(self);
+        let start = if self.check(Type::DOT_DOT) {
+            None
+        } else {
+            Some(self.binary(parse_context, 0)?)
+        };
+        if !self.check(Type::DOT_DOT) {
+            return Ok(start.unwrap());
+        }
+        consume_token!(self, Type::DOT_DOT);
+        let end = if self.check(Type::RSQUARE) {
+            None
+        } else {
+            Some(self.binary(parse_context, 0)?)
+        };
+
+        // a missing start is the beginning, and a missing end is i64::MAX which slicing takes to
+        // mean the end
+        let number = |n: String, pos: Position| ASTNode {
+            statement: Statement::LITERAL_NUM(Token {
+                pos: pos.clone(),
+                typ: Type::NUMBER(n.into()),
+                leading_trivia: vec![],
+                trailing_trivia: vec![],
+            }),
+            position: pos,
+        };
+        let end_pos = get_position!(self);
+        let start = start.unwrap_or_else(|| number("0".to_string(), pos.clone()));
+        let end = end.unwrap_or_else(|| number(i64::MAX.to_string(), end_pos));
+        let position = start.position.clone().join(end.position.clone());
+        Ok(ASTNode {
+            statement: Statement::RANGE(Box::new(start), Box::new(end)),
+            position,
+        })
     }
 
-    fn struct_access(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn struct_access(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         // Parse the leftmost expression first (e.g., x in x.y.z)
         let lhs_pos = get_position!(self);
-        let mut lhs = self.tuple(parse_context);
+        let mut lhs = self.tuple(parse_context)?;
         // Keep parsing as long as there's a DOT token followed by an identifier
-        while !self.end() && self.tokens[self.counter].typ == Type::DOT {
+        while self.check(Type::DOT) {
             consume_token!(self, Type::DOT);
             let rhs_pos = get_position!(self);
-            let field = get_next!(self); // Field being accessed
-                                         // Create a new STRUCT_ACCESS node for this level
+            let field = self.expect_identifier()?; // Field being accessed
+                                                   // Create a new STRUCT_ACCESS node for this level
             lhs = ASTNode {
                 statement: Statement::STRUCT_ACCESS(Box::new(lhs), field.clone()),
                 position: lhs_pos.join(rhs_pos),
             };
         }
-        lhs
+        Ok(lhs)
     }
 
-    fn tuple(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_expression = self.single(parse_context);
-        if !self.end()
-            && self.tokens[self.counter].typ == Type::COMMA
+    fn tuple(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let higher_expression = self.single(parse_context)?;
+        if self.check(Type::COMMA)
             && ((parse_context.in_function_call && parse_context.in_group)
                 || (!parse_context.in_function_call))
         {
@@ -241,71 +566,84 @@ impl<'a> Parser<'a> {
 
             consume_token!(self, Type::COMMA);
             while !self.end() {
-                exprs.push(self.single(parse_context));
-                if self.end() || self.tokens[self.counter].typ != Type::COMMA {
+                exprs.push(self.single(parse_context)?);
+                if !self.check(Type::COMMA) {
                     break;
                 }
                 consume_token!(self, Type::COMMA);
             }
 
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::TUPLE(exprs),
                 position: lhs_pos,
-            };
+            });
         }
-        higher_expression
+        Ok(higher_expression)
     }
 
-    fn single(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn single(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::EXPRESSION));
+        }
         let next: &Token = &self.tokens[self.counter];
         match next.typ {
             Type::TRUE => {
                 consume_token!(self, Type::TRUE);
-                return ASTNode {
+                return Ok(ASTNode {
                     statement: Statement::LITERAL_BOOL(true),
                     position: next.pos.clone(),
-                };
+                });
             }
             Type::FALSE => {
                 consume_token!(self, Type::FALSE);
-                return ASTNode {
+                return Ok(ASTNode {
                     statement: Statement::LITERAL_BOOL(false),
                     position: next.pos.clone(),
-                };
+                });
             }
             Type::STRING_LITERAL(_) => self.string(parse_context),
+            Type::INTERPOLATED_STRING(_) => self.interpolated_string(parse_context),
             Type::ATOM(_) => self.atom(parse_context),
+            Type::FN => self.lambda(parse_context),
+            Type::MATCH => self.matchh(parse_context),
             Type::IDENTIFIER(_) => {
-                get_next!(self);
-                return ASTNode {
+                self.counter += 1;
+                return Ok(ASTNode {
                     statement: Statement::VARIABLE(next.clone()),
                     position: next.pos.clone(),
-                };
+                });
             }
-            Type::NUMBER(_) => {
-                get_next!(self);
-                return ASTNode {
+            Type::NUMBER(ref n) => {
+                if !DataType::number_literal_in_range(n, false) {
+                    let (_, typ) = DataType::from_number_literal(n);
+                    return Err(self.error(Expected::NUMBER_IN_RANGE(typ)));
+                }
+                self.counter += 1;
+                return Ok(ASTNode {
                     statement: Statement::LITERAL_NUM(next.clone()),
                     position: next.pos.clone(),
-                };
+                });
             }
             Type::LPAREN => {
                 let lhs_pos = get_position!(self);
                 consume_token!(self, Type::LPAREN);
-                let mut expr = self.expression(parse_context);
+                // brackets make a tuple possible again inside call args i.e. f((1, 2))
+                let mut group_parse_context = parse_context;
+                group_parse_context.in_group = true;
+                let mut expr = self.expression(group_parse_context)?;
                 let rhs_pos = get_position!(self);
                 consume_token!(self, Type::RPAREN);
                 expr.position = lhs_pos.join(rhs_pos);
-                return expr;
+                return Ok(expr);
             }
             Type::LSQUARE => {
                 let lhs_pos = get_position!(self);
                 consume_token!(self, Type::LSQUARE);
                 let mut items: Vec<ASTNode> = vec![];
-                if self.tokens[self.counter].typ != Type::RSQUARE {
+                if !self.check(Type::RSQUARE) {
                     loop {
-                        items.push(self.single(parse_context));
-                        if self.tokens[self.counter].typ == Type::RSQUARE {
+                        items.push(self.single(parse_context)?);
+                        if self.check(Type::RSQUARE) {
                             break;
                         }
                         consume_token!(self, Type::COMMA);
@@ -313,283 +651,222 @@ impl<'a> Parser<'a> {
                 }
                 let rhs_pos = get_position!(self);
                 consume_token!(self, Type::RSQUARE);
-                return ASTNode {
+                return Ok(ASTNode {
                     statement: Statement::SLICE(items),
                     position: lhs_pos.join(rhs_pos),
-                };
+                });
             }
-            Type::COLON => {
+            Type::LCURLY => {
                 let lhs_pos = get_position!(self);
-                consume_token!(self, Type::COLON);
+                consume_token!(self, Type::LCURLY);
+                // commas separate the entries so they can't make tuples, a tuple key needs brackets
+                let mut entry_parse_context = parse_context;
+                entry_parse_context.in_function_call = true;
+                entry_parse_context.in_group = false;
+                let mut entries: Vec<(ASTNode, ASTNode)> = vec![];
+                if !self.check(Type::RCURLY) {
+                    loop {
+                        let key = self.binary(entry_parse_context, 0)?;
+                        consume_token!(self, Type::COLON);
+                        let value = self.binary(entry_parse_context, 0)?;
+                        entries.push((key, value));
+                        if self.check(Type::RCURLY) {
+                            break;
+                        }
+                        consume_token!(self, Type::COMMA);
+                    }
+                }
                 let rhs_pos = get_position!(self);
-                let atom = get_next!(self);
-                return ASTNode {
-                    statement: Statement::ATOM(atom.clone()),
+                consume_token!(self, Type::RCURLY);
+                return Ok(ASTNode {
+                    statement: Statement::DICT(entries),
                     position: lhs_pos.join(rhs_pos),
-                };
+                });
             }
-            Type::AMPERSAND => {
-                // doing annotation
+            Type::COLON => {
                 let lhs_pos = get_position!(self);
-                consume_token!(self, Type::AMPERSAND);
-                let annotation = get_next!(self);
-                let mut args: Vec<Token> = vec![];
-                if self.tokens[self.counter].typ == Type::LPAREN {
-                    consume_token!(self, Type::LPAREN);
-                    if self.tokens[self.counter].typ != Type::RPAREN {
-                        loop {
-                            let next = get_next!(self);
-                            args.push(next.clone());
-                            if self.tokens[self.counter].typ == Type::RPAREN {
-                                consume_token!(self, Type::RPAREN);
-                                break;
-                            }
-                            consume_token!(self, Type::COMMA);
-                        }
-                    } else {
-                        get_next!(self);
-                    }
-                }
-
-                // FIXME should probably do this with statements...
-                let expr = self.expression(parse_context);
-                let rhs_pos = expr.position.clone();
-
-                return ASTNode {
-                    statement: Statement::ANNOTATION(annotation.clone(), args, Box::new(expr)),
+                consume_token!(self, Type::COLON);
+                let rhs_pos = get_position!(self);
+                let atom = self.expect_identifier()?;
+                return Ok(ASTNode {
+                    statement: Statement::ATOM(atom.clone()),
                     position: lhs_pos.join(rhs_pos),
-                };
+                });
             }
+            Type::AMPERSAND => self.annotation(parse_context, false),
             // _ => higher_precedence,
-            _ => panic!("{:?}", next),
+            _ => Err(self.error(Expected::EXPRESSION)),
         }
     }
 
-    fn logical_operators(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.equality(parse_context);
-
-        if !self.end() && self.tokens[self.counter].typ == Type::OR {
-            consume_token!(self, Type::OR);
-            let rhs = self.logical_operators(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(
-                    Box::new(higher_precedence),
-                    Box::new(rhs),
-                    Op::LOGICAL_OR,
-                ),
+    fn binary(
+        &mut self,
+        parse_context: ParseContext,
+        min_precedence: u8,
+    ) -> Result<ASTNode, ParseError> {
+        let mut lhs = self.unary(parse_context)?;
+        while !self.end() {
+            let operator = binary_operator(&get_current!(self).typ);
+            if operator.is_none() {
+                break;
+            }
+            let (op, precedence) = operator.unwrap();
+            if precedence < min_precedence {
+                break;
+            }
+            // a `-` starting a new line is a negative number beginning the next statement or match
+            // case, not a subtraction
+            if op == Op::SUB
+                && get_current!(self).pos.line != self.tokens[self.counter - 1].pos.line_end
+            {
+                break;
+            }
+            self.counter += 1;
+            // ** is right associative so the rhs may contain another ** at the same precedence
+            let rhs_precedence = if op == Op::POW {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.binary(parse_context, rhs_precedence)?;
+            let pos = lhs.position.clone().join(rhs.position.clone());
+            lhs = ASTNode {
+                statement: Statement::BIN_OP(Box::new(lhs), Box::new(rhs), op),
                 position: pos,
             };
         }
-
-        higher_precedence
+        Ok(lhs)
     }
 
-    fn equality(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.add_sub(parse_context);
-        if !self.end() && self.tokens[self.counter].typ == Type::EQUALS {
-            consume_token!(self, Type::EQUALS);
-            let rhs = self.equality(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::EQ),
-                position: pos,
-            };
-        } else if !self.end() && self.tokens[self.counter].typ == Type::NOT_EQUALS {
-            consume_token!(self, Type::NOT_EQUALS);
-            let rhs = self.equality(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::NEQ),
-                position: pos,
-            };
-        } else if !self.end() && self.tokens[self.counter].typ == Type::GREATER_THAN {
-            consume_token!(self, Type::GREATER_THAN);
-            let rhs = self.equality(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::GT),
-                position: pos,
-            };
-        }
-        if !self.end() && self.tokens[self.counter].typ == Type::GREATER_EQ {
-            consume_token!(self, Type::GREATER_EQ);
-            let rhs = self.equality(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::GE),
-                position: pos,
-            };
-        }
-        if !self.end() && self.tokens[self.counter].typ == Type::LESS_THAN {
-            consume_token!(self, Type::LESS_THAN);
-            let rhs = self.equality(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::LT),
-                position: pos,
-            };
+    fn unary(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::EXPRESSION));
         }
-        if !self.end() && self.tokens[self.counter].typ == Type::LESS_EQ {
-            consume_token!(self, Type::LESS_EQ);
-            let rhs = self.expression(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::LE),
-                position: pos,
-            };
+        // only ** binds tighter than - and !, so -2**2 is -(2**2), whereas `not` takes a whole
+        // comparison like python, so `not a == b` is not (a == b)
+        let (op, operand_precedence) = match get_current!(self).typ {
+            Type::SUB => (UnaryOp::NEG, POW_PRECEDENCE),
+            Type::EXCLAIM => (UnaryOp::NOT, POW_PRECEDENCE),
+            Type::NOT => (UnaryOp::NOT, COMPARISON_PRECEDENCE),
+            _ => return self.tryy(parse_context),
+        };
+        let lhs_pos = get_position!(self);
+        self.counter += 1;
+        if op == UnaryOp::NEG {
+            if let Some(literal) = self.negative_literal(lhs_pos.clone())? {
+                return Ok(literal);
+            }
         }
-
-        higher_precedence
+        let rhs = self.binary(parse_context, operand_precedence)?;
+        let rhs_pos = rhs.position.clone();
+        Ok(ASTNode {
+            statement: Statement::UNARY_OP(op, Box::new(rhs)),
+            position: lhs_pos.join(rhs_pos),
+        })
     }
 
-    fn add_sub(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.mul_div(parse_context);
-        if !self.end() && self.tokens[self.counter].typ == Type::ADD {
-            consume_token!(self, Type::ADD);
-            let rhs: ASTNode = self.add_sub(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::ADD),
-                position: pos,
-            };
-        } else if !self.end() && self.tokens[self.counter].typ == Type::SUB {
-            consume_token!(self, Type::SUB);
-            let rhs = self.add_sub(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::SUB),
-                position: pos,
-            };
+    // -<number> is a literal of its own so the signed integers can reach their min, unless something
+    // binding tighter than the - follows the number i.e. -2**2 or -5.abs()
+    fn negative_literal(&mut self, sub_pos: Position) -> Result<Option<ASTNode>, ParseError> {
+        let number = match self.tokens.get(self.counter) {
+            Some(token) => token.clone(),
+            None => return Ok(None),
+        };
+        let n = match &number.typ {
+            Type::NUMBER(n) => n.clone(),
+            _ => return Ok(None),
+        };
+        let (_, typ) = DataType::from_number_literal(&n);
+        if typ == DataType::U32 {
+            return Ok(None);
         }
-        return higher_precedence;
-    }
-
-    fn mul_div(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.bitwise(parse_context);
-        if !self.end() && self.tokens[self.counter].typ == Type::MUL {
-            consume_token!(self, Type::MUL);
-            let rhs = self.mul_div(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::MUL),
-                position: pos,
-            };
-        } else if !self.end() && self.tokens[self.counter].typ == Type::DIV {
-            consume_token!(self, Type::DIV);
-            let rhs = self.mul_div(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(Box::new(higher_precedence), Box::new(rhs), Op::DIV),
-                position: pos,
-            };
+        if let Some(next) = self.tokens.get(self.counter + 1) {
+            if matches!(
+                next.typ,
+                Type::POW | Type::DOT | Type::LPAREN | Type::LSQUARE
+            ) {
+                return Ok(None);
+            }
         }
-        return higher_precedence;
-    }
-
-    fn bitwise(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.tryy(parse_context);
-        if !self.end() && self.tokens[self.counter].typ == Type::BITWISE_OR {
-            consume_token!(self, Type::BITWISE_OR);
-            let rhs = self.bitwise(parse_context);
-            let pos = higher_precedence
-                .position
-                .clone()
-                .join(rhs.position.clone());
-            return ASTNode {
-                statement: Statement::BIN_OP(
-                    Box::new(higher_precedence),
-                    Box::new(rhs),
-                    Op::BITWISE_OR,
-                ),
-                position: pos,
-            };
+        if !DataType::number_literal_in_range(&n, true) {
+            return Err(self.error(Expected::NUMBER_IN_RANGE(typ)));
         }
-        return higher_precedence;
+        self.counter += 1;
+        let position = sub_pos.join(number.pos.clone());
+        return Ok(Some(ASTNode {
+            statement: Statement::LITERAL_NUM(Token {
+                typ: Type::NUMBER(format!("-{}", n).into()),
+                pos: position.clone(),
+                leading_trivia: vec![],
+                trailing_trivia: vec![],
+            }),
+            position,
+        }));
     }
 
-    fn matchh(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn matchh(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let matchh_pos = get_position!(self);
         consume_token!(self, Type::MATCH);
-        let match_value = self.expression(parse_context);
+        let match_value = self.expression(parse_context)?;
         consume_token!(self, Type::DO);
 
-        // the cases here
-        let t = get_next!(self);
-        consume_token!(self, Type::ASSIGN);
-        consume_token!(self, Type::GREATER_THAN);
-        let expr = self.statement(parse_context);
-        let pattern = Statement::MATCH_CASE(t.clone(), Box::new(expr));
+        // the cases here, each is `<pattern> [if <guard>] => <statement>`
+        let mut cases: Vec<ASTNode> = vec![];
+        while !self.check(Type::END) {
+            let case_pos = get_position!(self);
+            let pattern = self.pattern_list(true)?;
+            let mut guard: Option<Box<ASTNode>> = None;
+            if self.check(Type::IF) {
+                consume_token!(self, Type::IF);
+                guard = Some(Box::new(self.expression(parse_context)?));
+            }
+            consume_token!(self, Type::ASSIGN);
+            consume_token!(self, Type::GREATER_THAN);
+            let body = self.statement(parse_context)?;
+            let body_pos = body.position.clone();
+            cases.push(ASTNode {
+                statement: Statement::MATCH_CASE(pattern, guard, Box::new(body)),
+                position: case_pos.join(body_pos),
+            });
+        }
         consume_token!(self, Type::END);
-        ASTNode {
-            statement: Statement::MATCH(
-                Box::new(match_value),
-                vec![ASTNode {
-                    statement: pattern,
-                    position: matchh_pos.clone(),
-                }],
-            ),
+        Ok(ASTNode {
+            statement: Statement::MATCH(Box::new(match_value), cases),
             position: matchh_pos,
-        }
+        })
     }
 
-    fn assert(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn assert(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let assert_pos = get_position!(self);
         consume_token!(self, Type::ASSERT);
-        let expr = self.expression(parse_context);
+        let expr = self.expression(parse_context)?;
         let mut name: Option<Token> = None;
         let mut rhs_pos = expr.position.clone();
-        if !self.end() && self.tokens[self.counter].typ == Type::COMMA {
+        if self.check(Type::COMMA) {
             consume_token!(self, Type::COMMA);
-            let next = get_next!(self);
+            let next = get_next!(self, Expected::STRING);
             name = Some(next.clone());
             rhs_pos = name.as_ref().unwrap().pos.clone();
         }
-        return ASTNode {
+        return Ok(ASTNode {
             statement: Statement::ASSERT(Box::new(expr), name),
             position: assert_pos.join(rhs_pos),
-        };
+        });
     }
 
-    fn block(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn block(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let do_pos = get_position!(self);
         consume_token!(self, Type::DO);
+        let mut block_parse_context = parse_context.clone();
+        block_parse_context.in_block = true;
+        // the statements are their own, even when the block is in a call's arguments
+        block_parse_context.in_function_call = false;
+        block_parse_context.in_group = false;
         let mut stms = vec![];
-        while !self.end() && self.tokens[self.counter].typ != Type::END {
-            stms.push(self.statement(parse_context));
+        while !self.end() && !self.check(Type::END) {
+            if let Some(statement) = self.recovering_statement(block_parse_context) {
+                stms.push(statement);
+            }
         }
         let end_pos: Position;
         if stms.len() > 0 {
@@ -598,160 +875,229 @@ impl<'a> Parser<'a> {
             end_pos = self.tokens[self.counter - 1].pos.clone();
         }
         consume_token!(self, Type::END);
-        return ASTNode {
+        return Ok(ASTNode {
             statement: Statement::BLOCK(stms),
             position: do_pos.join(end_pos),
-        };
+        });
     }
 
-    fn test(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn test(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let test_pos = get_position!(self);
         consume_token!(self, Type::TEST);
-        let test_name = self.string(parse_context);
-        let body = self.statement(parse_context);
+        let test_name = self.string(parse_context)?;
+        let body = self.statement(parse_context)?;
         let body_pos = body.position.clone();
-        ASTNode {
+        Ok(ASTNode {
             statement: Statement::TEST(Box::new(test_name), Box::new(body)),
             position: test_pos.join(body_pos),
-        }
+        })
     }
 
-    fn iff(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn iff(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let if_pos = get_position!(self);
         consume_token!(self, Type::IF);
-        let condition = self.expression(parse_context);
-        let body = self.statement(parse_context);
+        let condition = self.expression(parse_context)?;
+        let body = self.body(parse_context)?;
         let body_pos = body.position.clone();
         let mut else_body: Option<Box<ASTNode>> = None;
-        if !self.end() && self.tokens[self.counter].typ == Type::ELSE {
+        if self.check(Type::ELSE) {
             consume_token!(self, Type::ELSE);
-            else_body = Some(Box::new(self.statement(parse_context)));
+            else_body = Some(Box::new(self.body(parse_context)?));
         }
-        ASTNode {
+        Ok(ASTNode {
             statement: Statement::IF(Box::new(condition), Box::new(body), else_body),
             position: if_pos.join(body_pos),
-        }
+        })
     }
 
-    fn forr(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn forr(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let for_pos = get_position!(self);
         consume_token!(self, Type::FOR);
-        let var = get_next!(self);
+        let var = self.pattern_list(false)?;
         consume_token!(self, Type::IN);
-        let iter_obj = self.parse_range(parse_context);
-        let body = self.statement(parse_context);
+        let iter_obj = self.parse_range(parse_context)?;
+        let body = self.loop_body(parse_context)?;
         let body_pos = body.position.clone();
-        return ASTNode {
-            statement: Statement::FOR(var.clone(), Box::new(iter_obj), Box::new(body)),
+        return Ok(ASTNode {
+            statement: Statement::FOR(var, Box::new(iter_obj), Box::new(body)),
             position: for_pos.join(body_pos.clone()),
+        });
+    }
+
+    fn whilee(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let while_pos = get_position!(self);
+        consume_token!(self, Type::WHILE);
+        let condition = self.expression(parse_context)?;
+        let body = self.loop_body(parse_context)?;
+        let body_pos = body.position.clone();
+        return Ok(ASTNode {
+            statement: Statement::WHILE(Box::new(condition), Box::new(body)),
+            position: while_pos.join(body_pos),
+        });
+    }
+
+    fn loopp(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let loop_pos = get_position!(self);
+        consume_token!(self, Type::LOOP);
+        let body = self.loop_body(parse_context)?;
+        let body_pos = body.position.clone();
+        return Ok(ASTNode {
+            statement: Statement::LOOP(Box::new(body)),
+            position: loop_pos.join(body_pos),
+        });
+    }
+
+    fn loop_body(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let mut loop_parse_context = parse_context.clone();
+        loop_parse_context.in_loop = true;
+        self.body(loop_parse_context)
+    }
+
+    fn loop_control(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if !parse_context.in_loop {
+            return Err(self.error(Expected::ENCLOSING_LOOP));
+        }
+        let t = get_current!(self);
+        self.counter += 1;
+        let statement = match t.typ {
+            Type::BREAK => Statement::BREAK,
+            _ => Statement::CONTINUE,
         };
+        return Ok(ASTNode {
+            statement,
+            position: t.pos.clone(),
+        });
     }
 
-    fn parse_range(&mut self, parse_context: ParseContext) -> ASTNode {
-        let higher_precedence = self.logical_operators(parse_context);
+    fn parse_range(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let higher_precedence = self.binary(parse_context, 0)?;
         let first_pos = higher_precedence.position;
-        if !self.end() && self.tokens[self.counter].typ == Type::DOT_DOT {
+        if self.check(Type::DOT_DOT) {
             consume_token!(self, Type::DOT_DOT);
-            let second = self.expression(parse_context);
+            let second = self.expression(parse_context)?;
             let second_pos = second.position;
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::RANGE(Box::new(higher_precedence), Box::new(second)),
                 position: first_pos.join(second_pos),
-            };
+            });
         }
-        higher_precedence
+        Ok(higher_precedence)
     }
 
-    fn ret(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn ret(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let pos = get_position!(self);
         consume_token!(self, Type::RETURN);
-        let val = self.expression(parse_context);
+        let val = self.expression(parse_context)?;
         let rhs_pos = val.position.clone();
-        return ASTNode {
+        return Ok(ASTNode {
             statement: Statement::RETURN(Some(Box::new(val))),
             position: pos.clone().join(rhs_pos),
-        };
+        });
+    }
+
+    // the body of an if, for or fn, which is a statement that must be there
+    fn body(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::EXPRESSION));
+        }
+        self.statement(parse_context)
     }
 
-    fn string(&mut self, parse_context: ParseContext) -> ASTNode {
-        let s = get_next!(self);
-        ASTNode {
+    fn string(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::STRING));
+        }
+        let s = get_current!(self);
+        match s.typ {
+            Type::STRING_LITERAL(_) => {}
+            _ => return Err(self.error(Expected::STRING)),
+        }
+        self.counter += 1;
+        Ok(ASTNode {
             statement: Statement::STRING(s.clone()),
             position: s.pos.clone(),
+        })
+    }
+
+    fn interpolated_string(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let s = get_next!(self, Expected::STRING);
+        let parts = match &s.typ {
+            Type::INTERPOLATED_STRING(parts) => parts,
+            _ => panic!(),
+        };
+
+        let mut nodes: Vec<ASTNode> = vec![];
+        for part in parts {
+            match part {
+                StringPart::LITERAL(literal) => nodes.push(ASTNode {
+                    statement: Statement::STRING(Token {
+                        typ: Type::STRING_LITERAL(literal.clone()),
+                        pos: s.pos.clone(),
+                        leading_trivia: vec![],
+                        trailing_trivia: vec![],
+                    }),
+                    position: s.pos.clone(),
+                }),
+                StringPart::EXPRESSION(tokens) => {
+                    let mut parser = Parser::new(tokens);
+                    let expr = parser.expression(ParseContext::new())?;
+                    if !parser.end() {
+                        return Err(parser.error(Expected::END_OF_INTERPOLATION));
+                    }
+                    nodes.push(expr);
+                }
+            }
         }
+
+        Ok(ASTNode {
+            statement: Statement::INTERPOLATED_STRING(nodes),
+            position: s.pos.clone(),
+        })
     }
 
-    fn atom(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn atom(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let pos = get_position!(self);
-        let tok = get_next!(self);
-        return ASTNode {
+        let tok = get_next!(self, Expected::EXPRESSION);
+        return Ok(ASTNode {
             statement: Statement::ATOM(tok.clone()),
             position: pos,
-        };
+        });
     }
 
-    fn identifier(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn identifier(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let identifier = get_current!(self);
         if self.end_away(1) {
-            get_next!(self);
-            return ASTNode {
+            self.counter += 1;
+            return Ok(ASTNode {
                 statement: Statement::VARIABLE(identifier.clone()),
                 position: identifier.pos.clone(),
-            };
+            });
         }
 
-        if self.tokens[self.counter + 1].typ == Type::COLON {
-            get_next!(self);
+        if self.peek(1) == Some(&Type::COLON) {
+            self.counter += 1;
             let lhs_pos = identifier.pos.clone();
             // move over the :
             consume_token!(self, Type::COLON);
-            let typ = self.parse_type(parse_context);
+            let typ = self.parse_type(parse_context)?;
             // move over the =
             consume_token!(self, Type::ASSIGN);
-            let rhs = self.expression(parse_context);
+            let rhs = self.expression(parse_context)?;
             let rhs_pos = rhs.position.clone();
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::DEFINE(identifier.clone(), Some(typ), Some(Box::new(rhs))),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
 
         // function
-        if self.tokens[self.counter + 1].typ == Type::FN {
-            get_next!(self);
+        if self.peek(1) == Some(&Type::FN) {
+            self.counter += 1;
             let lhs_pos = identifier.pos.clone();
             consume_token!(self, Type::FN);
-
-            let mut params: Vec<ASTNode> = vec![];
-
-            if self.tokens[self.counter].typ == Type::LPAREN {
-                consume_token!(self, Type::LPAREN);
-                if self.tokens[self.counter].typ != Type::RPAREN {
-                    loop {
-                        params.push(self.parse_decl(parse_context));
-                        if self.tokens[self.counter].typ == Type::RPAREN {
-                            consume_token!(self, Type::RPAREN);
-                            break;
-                        }
-                        consume_token!(self, Type::COMMA);
-                    }
-                } else {
-                    get_next!(self);
-                }
-            }
-
-            let mut return_type: Option<DataType> = None;
-            if self.tokens[self.counter].typ == Type::SUB {
-                consume_token!(self, Type::SUB);
-                if self.tokens[self.counter].typ == Type::GREATER_THAN {
-                    consume_token!(self, Type::GREATER_THAN);
-                    return_type = Some(self.parse_type(parse_context));
-                }
-            }
-
-            let rhs = self.statement(parse_context);
+            let (params, return_type, rhs) = self.function(parse_context)?;
             let rhs_pos = rhs.position.clone();
-            return ASTNode {
+            return Ok(ASTNode {
                 statement: Statement::NAMED_FUNCTION(
                     identifier.clone(),
                     params,
@@ -759,63 +1105,339 @@ impl<'a> Parser<'a> {
                     Box::new(rhs),
                 ),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
 
         // type
         // todo deal with blocks?
-        if self.tokens[self.counter + 1].typ == Type::TYPE {
-            get_next!(self);
+        if self.peek(1) == Some(&Type::TYPE) {
+            self.counter += 1;
             let lhs_pos = identifier.pos.clone();
             consume_token!(self, Type::TYPE);
             let mut decls: Vec<ASTNode> = vec![];
-            let mut rhs_pos: Position;
-            if self.tokens[self.counter].typ != Type::END {
-                loop {
-                    decls.push(self.parse_decl(parse_context));
-                    if self.tokens[self.counter].typ == Type::END {
-                        rhs_pos = get_position!(self);
-                        consume_token!(self, Type::END);
-                        break;
+            while !self.check(Type::END) {
+                decls.push(self.parse_decl(parse_context)?);
+            }
+            let rhs_pos = get_position!(self);
+            consume_token!(self, Type::END);
+
+            return Ok(ASTNode {
+                statement: Statement::NAMED_TYPE_DECL(identifier.clone(), decls),
+                position: lhs_pos.join(rhs_pos),
+            });
+        }
+
+        // enum, each variant is a name with its fields in brackets, if it has any
+        if self.peek(1) == Some(&Type::ENUM) {
+            self.counter += 1;
+            let lhs_pos = identifier.pos.clone();
+            consume_token!(self, Type::ENUM);
+            let mut variants: Vec<(Token, Vec<ASTNode>)> = vec![];
+            while !self.check(Type::END) {
+                let variant = self.expect_identifier()?;
+                let mut decls: Vec<ASTNode> = vec![];
+                if self.check(Type::LPAREN) {
+                    consume_token!(self, Type::LPAREN);
+                    while !self.check(Type::RPAREN) {
+                        decls.push(self.parse_decl(parse_context)?);
+                        if !self.check(Type::COMMA) {
+                            break;
+                        }
+                        consume_token!(self, Type::COMMA);
                     }
+                    consume_token!(self, Type::RPAREN);
                 }
-            } else {
-                rhs_pos = get_position!(self);
-                get_next!(self);
+                variants.push((variant.clone(), decls));
             }
+            let rhs_pos = get_position!(self);
+            consume_token!(self, Type::END);
 
-            return ASTNode {
-                statement: Statement::NAMED_TYPE_DECL(identifier.clone(), decls),
+            return Ok(ASTNode {
+                statement: Statement::NAMED_ENUM_DECL(identifier.clone(), variants),
                 position: lhs_pos.join(rhs_pos),
-            };
+            });
         }
 
-        // fixme this should be lower precedence
-        if self.tokens[self.counter + 1].typ == Type::ASSIGN {
-            get_next!(self);
+        // interface, each method is a function signature without a body
+        if self.peek(1) == Some(&Type::INTERFACE) {
+            self.counter += 1;
             let lhs_pos = identifier.pos.clone();
+            consume_token!(self, Type::INTERFACE);
+            let mut methods: Vec<(Token, Vec<ASTNode>, Option<DataType>)> = vec![];
+            while !self.check(Type::END) {
+                let method = self.expect_identifier()?;
+                consume_token!(self, Type::FN);
+                let (params, return_type) = self.function_signature(parse_context)?;
+                methods.push((method.clone(), params, return_type));
+            }
+            let rhs_pos = get_position!(self);
+            consume_token!(self, Type::END);
+
+            return Ok(ASTNode {
+                statement: Statement::NAMED_INTERFACE_DECL(identifier.clone(), methods),
+                position: lhs_pos.join(rhs_pos),
+            });
+        }
+
+        let next = self.peek(1);
+        if next == Some(&Type::COMMA) || next == Some(&Type::LPAREN) {
+            if let Some(destructure) = self.try_destructure(parse_context)? {
+                return Ok(destructure);
+            }
+        }
+
+        self.expression(parse_context)
+    }
+
+    // `a, b = ...`, `(a, b) = ...` and `Vec(x=px) = ...` look just like a tuple or a call until we
+    // reach the `=`, so if it doesn't turn out to be a destructure we go back and parse an expression
+    fn try_destructure(
+        &mut self,
+        parse_context: ParseContext,
+    ) -> Result<Option<ASTNode>, ParseError> {
+        let start = self.counter;
+        let lhs_pos = get_position!(self);
+        let pattern = self.pattern_list(false);
+        let pattern = match pattern {
+            Ok(Pattern::BIND(_)) | Err(_) => None,
+            Ok(pattern) => Some(pattern),
+        };
+        if pattern.is_none() || !self.check(Type::ASSIGN) {
+            self.counter = start;
+            return Ok(None);
+        }
+        consume_token!(self, Type::ASSIGN);
+        let rhs = self.expression(parse_context)?;
+        let rhs_pos = rhs.position.clone();
+        Ok(Some(ASTNode {
+            statement: Statement::DESTRUCTURE_ASSIGN(pattern.unwrap(), Box::new(rhs)),
+            position: lhs_pos.join(rhs_pos),
+        }))
+    }
+
+    // `let x = ...`, `let x: t = ...` or `let a, b = ...` declare new variables, where a plain `=`
+    // gives one that already exists a new value
+    fn lett(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let lhs_pos = get_position!(self);
+        consume_token!(self, Type::LET);
+        let pattern = self.pattern_list(false)?;
+        if let Pattern::BIND(identifier) = pattern {
+            let mut typ: Option<DataType> = None;
+            if self.check(Type::COLON) {
+                consume_token!(self, Type::COLON);
+                typ = Some(self.parse_type(parse_context)?);
+            }
             consume_token!(self, Type::ASSIGN);
-            let rhs = self.expression(parse_context);
+            let rhs = self.expression(parse_context)?;
             let rhs_pos = rhs.position.clone();
-            return ASTNode {
-                statement: Statement::DEFINE(identifier.clone(), None, Some(Box::new(rhs))),
+            return Ok(ASTNode {
+                statement: Statement::DEFINE(identifier, typ, Some(Box::new(rhs))),
                 position: lhs_pos.join(rhs_pos),
+            });
+        }
+        consume_token!(self, Type::ASSIGN);
+        let rhs = self.expression(parse_context)?;
+        let rhs_pos = rhs.position.clone();
+        Ok(ASTNode {
+            statement: Statement::DESTRUCTURE(pattern, Some(Box::new(rhs))),
+            position: lhs_pos.join(rhs_pos),
+        })
+    }
+
+    // patterns separated by commas, more than one is a tuple. refutable patterns (literals and
+    // ranges) are only allowed where the value can fail to match, i.e. a match case
+    fn pattern_list(&mut self, refutable: bool) -> Result<Pattern, ParseError> {
+        let lhs_pos = get_position!(self);
+        let first = self.pattern(refutable)?;
+        if !self.check(Type::COMMA) {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.check(Type::COMMA) {
+            consume_token!(self, Type::COMMA);
+            items.push(self.pattern(refutable)?);
+        }
+        let rhs_pos = self.tokens[self.counter - 1].pos.clone();
+        Ok(Pattern::TUPLE(items, lhs_pos.join(rhs_pos)))
+    }
+
+    fn pattern(&mut self, refutable: bool) -> Result<Pattern, ParseError> {
+        let lhs_pos = get_position!(self);
+        if self.check(Type::LPAREN) {
+            consume_token!(self, Type::LPAREN);
+            let pattern = self.pattern_list(refutable)?;
+            consume_token!(self, Type::RPAREN);
+            return Ok(pattern);
+        }
+
+        if refutable && !self.end() {
+            let literal = match get_current!(self).typ {
+                Type::NUMBER(_)
+                | Type::STRING_LITERAL(_)
+                | Type::TRUE
+                | Type::FALSE
+                | Type::ATOM(_)
+                | Type::COLON => Some(self.single(ParseContext::new())?),
+                Type::SUB => Some(self.unary(ParseContext::new())?),
+                _ => None,
             };
+            if let Some(literal) = literal {
+                if !self.check(Type::DOT_DOT) {
+                    return Ok(Pattern::LITERAL(Box::new(literal)));
+                }
+                consume_token!(self, Type::DOT_DOT);
+                let end = self.unary(ParseContext::new())?;
+                return Ok(Pattern::RANGE(Box::new(literal), Box::new(end)));
+            }
         }
 
-        self.expression(parse_context)
+        let identifier = self.expect_identifier()?;
+        let mut typ = ASTNode {
+            statement: Statement::VARIABLE(identifier.clone()),
+            position: identifier.pos.clone(),
+        };
+        let mut qualified = false;
+        while self.check(Type::DOT) {
+            consume_token!(self, Type::DOT);
+            let member = self.expect_identifier()?;
+            let pos = typ.position.clone().join(member.pos.clone());
+            typ = ASTNode {
+                statement: Statement::STRUCT_ACCESS(Box::new(typ), member.clone()),
+                position: pos,
+            };
+            qualified = true;
+        }
+        if !self.check(Type::LPAREN) {
+            // a path on its own is a variant without fields i.e. `Colour.Red`, which has nothing
+            // to bind so is only useful in a match
+            if qualified {
+                if !refutable {
+                    return Err(self.error(Expected::TOKEN(Type::LPAREN)));
+                }
+                let pos = typ.position.clone();
+                return Ok(Pattern::STRUCT(Box::new(typ), vec![], pos));
+            }
+            if *identifier.as_identifier() == "_" {
+                return Ok(Pattern::WILDCARD(identifier.pos.clone()));
+            }
+            return Ok(Pattern::BIND(identifier.clone()));
+        }
+
+        consume_token!(self, Type::LPAREN);
+        let mut fields: Vec<(Token, Pattern)> = vec![];
+        while !self.check(Type::RPAREN) {
+            let field = self.expect_identifier()?;
+            consume_token!(self, Type::ASSIGN);
+            fields.push((field.clone(), self.pattern(refutable)?));
+            if !self.check(Type::COMMA) {
+                break;
+            }
+            consume_token!(self, Type::COMMA);
+        }
+        let rhs_pos = get_position!(self);
+        consume_token!(self, Type::RPAREN);
+        Ok(Pattern::STRUCT(
+            Box::new(typ),
+            fields,
+            lhs_pos.join(rhs_pos),
+        ))
     }
 
-    fn parse_type(&mut self, parse_context: ParseContext) -> DataType {
-        let current = get_next!(self);
+    // everything after the `fn`, shared by named functions and lambdas
+    fn function(
+        &mut self,
+        parse_context: ParseContext,
+    ) -> Result<(Vec<ASTNode>, Option<DataType>, ASTNode), ParseError> {
+        let (params, return_type) = self.function_signature(parse_context)?;
+
+        let mut body_parse_context = parse_context.clone();
+        body_parse_context.in_loop = false;
+        let body = self.body(body_parse_context)?;
+        Ok((params, return_type, body))
+    }
+
+    // the params and return type, which is all an interface method has
+    fn function_signature(
+        &mut self,
+        parse_context: ParseContext,
+    ) -> Result<(Vec<ASTNode>, Option<DataType>), ParseError> {
+        let mut params: Vec<ASTNode> = vec![];
+
+        if self.check(Type::LPAREN) {
+            consume_token!(self, Type::LPAREN);
+            if !self.check(Type::RPAREN) {
+                loop {
+                    let param = self.parse_param(parse_context)?;
+                    let has_default = matches!(param.statement, Statement::DEFINE(_, _, Some(_)));
+                    let is_rest = matches!(param.statement, Statement::SPREAD(_));
+                    // only trailing parameters can be left out of a positional call
+                    if !has_default
+                        && !is_rest
+                        && params
+                            .iter()
+                            .any(|p| matches!(p.statement, Statement::DEFINE(_, _, Some(_))))
+                    {
+                        return Err(ParseError {
+                            expected: Expected::DEFAULT_VALUE,
+                            found: None,
+                            pos: param.position,
+                        });
+                    }
+                    params.push(param);
+                    // the rest parameter takes everything left over so it has to be the last
+                    if self.check(Type::RPAREN) || is_rest {
+                        consume_token!(self, Type::RPAREN);
+                        break;
+                    }
+                    consume_token!(self, Type::COMMA);
+                }
+            } else {
+                consume_token!(self, Type::RPAREN);
+            }
+        }
+
+        let mut return_type: Option<DataType> = None;
+        if self.check(Type::SUB) {
+            consume_token!(self, Type::SUB);
+            consume_token!(self, Type::GREATER_THAN);
+            return_type = Some(self.parse_type(parse_context)?);
+        }
+        Ok((params, return_type))
+    }
+
+    fn lambda(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        let lhs_pos = get_position!(self);
+        consume_token!(self, Type::FN);
+        // a body which isn't a block is a single expression, so a comma after it belongs to
+        // whatever the lambda is in i.e. [fn() 1, fn() 2]
+        let mut body_parse_context = parse_context;
+        body_parse_context.in_function_call = true;
+        body_parse_context.in_group = false;
+        let (params, return_type, body) = self.function(body_parse_context)?;
+        let rhs_pos = body.position.clone();
+        Ok(ASTNode {
+            statement: Statement::LAMBDA(params, return_type, Box::new(body)),
+            position: lhs_pos.join(rhs_pos),
+        })
+    }
+
+    fn parse_type(&mut self, parse_context: ParseContext) -> Result<DataType, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::TYPE));
+        }
+        let current = get_current!(self);
         let mut t: DataType;
         match &current.typ {
             Type::DOLLAR => {
-                let next = get_next!(self);
-                return match &next.typ {
-                    Type::IDENTIFIER(i) => DataType::GENERIC(i.clone()),
-                    _ => panic!(),
-                };
+                self.counter += 1;
+                let next = self.expect_identifier()?;
+                let t = DataType::GENERIC(next.as_identifier());
+                if self.check(Type::LSQUARE) {
+                    consume_token!(self, Type::LSQUARE);
+                    consume_token!(self, Type::RSQUARE);
+                    return Ok(DataType::SLICE(Box::new(t)));
+                }
+                return Ok(t);
             }
             Type::ANY => t = DataType::ANY,
             Type::STRING => t = DataType::STRING,
@@ -826,26 +1448,167 @@ impl<'a> Parser<'a> {
             Type::F32 => t = DataType::F32,
             Type::F64 => t = DataType::F64,
             Type::IDENTIFIER(i) => t = DataType::NAMED_REFERENCE(i.clone()),
-            _ => panic!(),
+            _ => return Err(self.error(Expected::TYPE)),
+        }
+        self.counter += 1;
+        // the type parameters of a generic type i.e. Box[i64]
+        if let DataType::NAMED_REFERENCE(name) = &t {
+            if self.check(Type::LSQUARE) && !matches!(self.peek(1), None | Some(Type::RSQUARE)) {
+                t = DataType::INSTANCE(name.clone(), self.parse_type_params(parse_context)?);
+            }
         }
-        if self.tokens[self.counter].typ == Type::LSQUARE {
+        if self.check(Type::LSQUARE) {
             consume_token!(self, Type::LSQUARE);
             consume_token!(self, Type::RSQUARE);
-            return DataType::SLICE(Box::new(t));
+            return Ok(DataType::SLICE(Box::new(t)));
         }
-        t
+        Ok(t)
+    }
+
+    // the [i64, string] after a generic type
+    fn parse_type_params(
+        &mut self,
+        parse_context: ParseContext,
+    ) -> Result<Vec<DataType>, ParseError> {
+        consume_token!(self, Type::LSQUARE);
+        let mut params: Vec<DataType> = vec![self.parse_type(parse_context)?];
+        while self.check(Type::COMMA) {
+            consume_token!(self, Type::COMMA);
+            params.push(self.parse_type(parse_context)?);
+        }
+        consume_token!(self, Type::RSQUARE);
+        Ok(params)
     }
 
-    fn parse_decl(&mut self, parse_context: ParseContext) -> ASTNode {
+    fn parse_decl(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
         let lhs_pos = get_position!(self);
-        let identifier = get_next!(self);
+        let identifier = self.expect_identifier()?;
         consume_token!(self, Type::COLON);
-        let rhs_pos = self.tokens[self.counter].pos.clone();
-        let typ = self.parse_type(parse_context);
-        return ASTNode {
+        let rhs_pos = get_position!(self);
+        let typ = self.parse_type(parse_context)?;
+        return Ok(ASTNode {
             statement: Statement::DEFINE(identifier.clone(), Some(typ), None),
             position: lhs_pos.join(rhs_pos),
-        };
+        });
+    }
+
+    // like a decl but the type is optional, or a pattern to destructure the argument with. a
+    // named parameter can have a default after an `=`, or be a `...rest` parameter which
+    // collects the extra positional arguments into a slice
+    fn parse_param(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.check(Type::ELLIPSIS) {
+            let lhs_pos = get_position!(self);
+            consume_token!(self, Type::ELLIPSIS);
+            let rest = if self.peek(1) == Some(&Type::COLON) {
+                self.parse_decl(parse_context)?
+            } else {
+                let identifier = self.expect_identifier()?;
+                ASTNode {
+                    statement: Statement::DEFINE(identifier.clone(), None, None),
+                    position: identifier.pos.clone(),
+                }
+            };
+            let pos = lhs_pos.join(rest.position.clone());
+            return Ok(ASTNode {
+                statement: Statement::SPREAD(Box::new(rest)),
+                position: pos,
+            });
+        }
+        let param = self.parse_required_param(parse_context)?;
+        if !self.check(Type::ASSIGN) {
+            return Ok(param);
+        }
+        match param.statement {
+            Statement::DEFINE(identifier, typ, None) => {
+                consume_token!(self, Type::ASSIGN);
+                // the commas between parameters can't make a tuple
+                let mut default_parse_context = parse_context;
+                default_parse_context.in_function_call = true;
+                default_parse_context.in_group = false;
+                let default = self.binary(default_parse_context, 0)?;
+                let pos = param.position.join(default.position.clone());
+                Ok(ASTNode {
+                    statement: Statement::DEFINE(identifier, typ, Some(Box::new(default))),
+                    position: pos,
+                })
+            }
+            _ => Err(self.error(Expected::TOKEN(Type::COMMA))),
+        }
+    }
+
+    fn parse_required_param(&mut self, parse_context: ParseContext) -> Result<ASTNode, ParseError> {
+        if self.check(Type::LPAREN) || self.peek(1) == Some(&Type::LPAREN) {
+            let lhs_pos = get_position!(self);
+            let pattern = self.pattern(false)?;
+            let rhs_pos = self.tokens[self.counter - 1].pos.clone();
+            return Ok(ASTNode {
+                statement: Statement::DESTRUCTURE(pattern, None),
+                position: lhs_pos.join(rhs_pos),
+            });
+        }
+        if self.peek(1) != Some(&Type::COLON) {
+            let identifier = self.expect_identifier()?;
+            return Ok(ASTNode {
+                statement: Statement::DEFINE(identifier.clone(), None, None),
+                position: identifier.pos.clone(),
+            });
+        }
+        self.parse_decl(parse_context)
+    }
+
+    fn expect_identifier(&mut self) -> Result<&'a Token, ParseError> {
+        if self.end() {
+            return Err(self.error(Expected::IDENTIFIER));
+        }
+        let t = &self.tokens[self.counter];
+        match t.typ {
+            Type::IDENTIFIER(_) => {
+                self.counter += 1;
+                Ok(t)
+            }
+            _ => Err(self.error(Expected::IDENTIFIER)),
+        }
+    }
+
+    fn check(&self, typ: Type) -> bool {
+        !self.end() && self.tokens[self.counter].typ == typ
+    }
+
+    // an error at the current token, or just after the last token if we ran out
+    fn error(&self, expected: Expected) -> ParseError {
+        if self.end() {
+            return ParseError {
+                expected,
+                found: None,
+                pos: self.current_position(),
+            };
+        }
+        let found = get_current!(self);
+        ParseError {
+            expected,
+            found: Some(found.typ.clone()),
+            pos: found.pos.clone(),
+        }
+    }
+
+    fn current_position(&self) -> Position {
+        if !self.end() {
+            return self.tokens[self.counter].pos.clone();
+        }
+        match self.tokens.last() {
+            Some(last) => Position {
+                index: last.pos.index_end,
+                line: last.pos.line_end,
+                index_end: last.pos.index_end + 1,
+                line_end: last.pos.line_end,
+            },
+            None => Position {
+                index: 0,
+                line: 0,
+                index_end: 0,
+                line_end: 0,
+            },
+        }
     }
 
     fn end(&self) -> bool {
@@ -855,4 +1618,9 @@ impl<'a> Parser<'a> {
     fn end_away(&self, offset: usize) -> bool {
         self.counter + offset == self.tokens.len()
     }
+
+    // the type of the token offset past the current one, none past the end of the file
+    fn peek(&self, offset: usize) -> Option<&Type> {
+        self.tokens.get(self.counter + offset).map(|t| &t.typ)
+    }
 }
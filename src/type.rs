@@ -4,7 +4,7 @@
 //     SLICE(Box<DataTypeVariant>),
 // }
 
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
@@ -20,12 +20,173 @@ pub enum DataType {
     FN(Vec<DataType>, Box<DataType>),
     SLICE(Box<DataType>),
     NAMED_REFERENCE(Rc<String>),
+    // a generic type with its type parameters filled in i.e. Box[i64]
+    INSTANCE(Rc<String>, Vec<DataType>),
     DYNAMIC_OBJECT(Vec<DataType>),
     GENERIC(Rc<String>),
 }
 
 impl DataType {
     pub fn assignable_from(self, other: Self) -> bool {
-        return self == other;
+        if self == other || self == DataType::ANY || other == DataType::ANY {
+            return true;
+        }
+        return match (self, other) {
+            // integers are all i64 at runtime so they're interchangeable for now, same for floats
+            (a, b) if a.is_integer() && b.is_integer() => true,
+            (a, b) if a.is_float() && b.is_float() => true,
+            (DataType::SLICE(a), DataType::SLICE(b)) => a.assignable_from(*b),
+            (DataType::INSTANCE(a, a_params), DataType::INSTANCE(b, b_params)) => {
+                a == b
+                    && a_params.len() == b_params.len()
+                    && a_params
+                        .into_iter()
+                        .zip(b_params)
+                        .all(|(a, b)| a.assignable_from(b))
+            }
+            // leaving out the type parameters accepts any of them
+            (DataType::NAMED_REFERENCE(a), DataType::INSTANCE(b, _))
+            | (DataType::INSTANCE(a, _), DataType::NAMED_REFERENCE(b)) => a == b,
+            (DataType::FN(a_params, a_return), DataType::FN(b_params, b_return)) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .into_iter()
+                        .zip(b_params)
+                        .all(|(a, b)| a.assignable_from(b))
+                    && a_return.assignable_from(*b_return)
+            }
+            _ => false,
+        };
+    }
+
+    fn is_integer(&self) -> bool {
+        return matches!(self, DataType::U32 | DataType::I32 | DataType::I64);
+    }
+
+    fn is_float(&self) -> bool {
+        return matches!(self, DataType::F32 | DataType::F64);
+    }
+
+    // the names of the generics used in this type, in the order they first appear
+    pub fn generics(&self, names: &mut Vec<Rc<String>>) {
+        match self {
+            DataType::GENERIC(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            DataType::SLICE(t) => t.generics(names),
+            DataType::INSTANCE(_, params) | DataType::DYNAMIC_OBJECT(params) => {
+                for param in params {
+                    param.generics(names);
+                }
+            }
+            DataType::FN(params, return_type) => {
+                for param in params {
+                    param.generics(names);
+                }
+                return_type.generics(names);
+            }
+            _ => {}
+        }
+    }
+
+    // replace the generics which have been worked out, the rest are left as they are
+    pub fn substitute(&self, bindings: &HashMap<Rc<String>, DataType>) -> DataType {
+        return match self {
+            DataType::GENERIC(name) => bindings.get(name).cloned().unwrap_or(self.clone()),
+            DataType::SLICE(t) => DataType::SLICE(Box::new(t.substitute(bindings))),
+            DataType::INSTANCE(name, params) => DataType::INSTANCE(
+                name.clone(),
+                params.iter().map(|p| p.substitute(bindings)).collect(),
+            ),
+            DataType::DYNAMIC_OBJECT(params) => {
+                DataType::DYNAMIC_OBJECT(params.iter().map(|p| p.substitute(bindings)).collect())
+            }
+            DataType::FN(params, return_type) => DataType::FN(
+                params.iter().map(|p| p.substitute(bindings)).collect(),
+                Box::new(return_type.substitute(bindings)),
+            ),
+            _ => self.clone(),
+        };
+    }
+
+    // how the type is written in gila, for error messages
+    pub fn print(&self) -> String {
+        return self.print_with(&|name| name.to_string());
+    }
+
+    // like print, but with each named type rendered by name, i.e. as a link in the docs
+    pub fn print_with(&self, name: &dyn Fn(&Rc<String>) -> String) -> String {
+        return match self {
+            DataType::VOID => "void".to_string(),
+            DataType::ANY => "any".to_string(),
+            DataType::U32 => "u32".to_string(),
+            DataType::I32 => "i32".to_string(),
+            DataType::I64 => "i64".to_string(),
+            DataType::F32 => "f32".to_string(),
+            DataType::F64 => "f64".to_string(),
+            DataType::BOOL => "bool".to_string(),
+            DataType::STRING => "string".to_string(),
+            DataType::FN(params, return_type) => format!(
+                "fn({}) -> {}",
+                params
+                    .iter()
+                    .map(|p| p.print_with(name))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                return_type.print_with(name)
+            ),
+            DataType::SLICE(t) => format!("{}[]", t.print_with(name)),
+            DataType::NAMED_REFERENCE(reference) => name(reference),
+            DataType::INSTANCE(reference, params) => format!(
+                "{}[{}]",
+                name(reference),
+                params
+                    .iter()
+                    .map(|p| p.print_with(name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            DataType::DYNAMIC_OBJECT(_) => "type".to_string(),
+            DataType::GENERIC(generic) => format!("${}", generic),
+        };
+    }
+
+    // the type suffix of a number literal, i.e. 3u32 or 2.0f32
+    pub fn from_number_suffix(suffix: &str) -> Option<DataType> {
+        return match suffix {
+            "u32" => Some(DataType::U32),
+            "i32" => Some(DataType::I32),
+            "i64" => Some(DataType::I64),
+            "f32" => Some(DataType::F32),
+            "f64" => Some(DataType::F64),
+            _ => None,
+        };
+    }
+
+    // number tokens are already normalised by the lexer to decimal digits and an optional suffix
+    pub fn from_number_literal(num: &str) -> (&str, DataType) {
+        let suffix_start = num.find(|c| c == 'u' || c == 'i' || c == 'f');
+        if let Some(i) = suffix_start {
+            return (&num[..i], DataType::from_number_suffix(&num[i..]).unwrap());
+        }
+        if num.contains(".") || num.contains("e") || num.contains("E") {
+            return (num, DataType::F64);
+        }
+        return (num, DataType::I64);
+    }
+
+    // the lexer lets signed integers go one past their max so they can be negated into their min,
+    // this checks the literal fits once we know if it's negative
+    pub fn number_literal_in_range(num: &str, negative: bool) -> bool {
+        let (digits, typ) = DataType::from_number_literal(num);
+        let max: u128 = match typ {
+            DataType::I32 => i32::MAX as u128,
+            DataType::I64 => i64::MAX as u128,
+            _ => return true,
+        };
+        let max = if negative { max + 1 } else { max };
+        return digits.parse::<u128>().map_or(false, |value| value <= max);
     }
 }
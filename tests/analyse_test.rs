@@ -0,0 +1,51 @@
+use gila::{
+    analyse::{Analyser, TypeCheckError},
+    lex::Lexer,
+    parse::Parser,
+};
+
+fn type_error(source: &str) -> Option<TypeCheckError> {
+    let mut lexer = Lexer::new();
+    let tokens = lexer.lex(source.to_string()).expect("Unable to lex");
+    let mut parser = Parser::new(&tokens);
+    let parse_result = parser.parse();
+    assert!(parse_result.errors.is_empty(), "{}", source);
+    let mut analyser = Analyser::new(vec![]);
+    return analyser.analyse(&parse_result.ast).err();
+}
+
+#[test]
+fn builtins_take_exactly_their_arguments() {
+    for (source, expected, found) in [("print()", 1, 0), ("len()", 1, 0), ("len(1, 2)", 1, 2)] {
+        match type_error(source) {
+            Some(TypeCheckError::WRONG_ARGUMENT_COUNT(_, _, e, f)) => {
+                assert_eq!((e, f), (expected, found), "{}", source)
+            }
+            other => panic!("{} gave {:?}", source, other),
+        }
+    }
+    assert!(type_error("print(1)").is_none());
+}
+
+#[test]
+fn shadowed_builtins_are_not_checked() {
+    assert!(type_error("f fn() do\n    let print = fn(a, b) a\n    print(1, 2)\nend").is_none());
+}
+
+#[test]
+fn constructors_set_exactly_the_fields() {
+    let decl = "Vec type\n    x: i64\nend\n";
+    match type_error(&format!("{}Vec(x=1, y=2)", decl)) {
+        Some(TypeCheckError::UNKNOWN_FIELD(_, typ, field)) => {
+            assert_eq!((typ.as_str(), field.as_str()), ("Vec", "y"))
+        }
+        other => panic!("gave {:?}", other),
+    }
+    match type_error(&format!("{}Vec()", decl)) {
+        Some(TypeCheckError::UNSET_FIELD(_, typ, field)) => {
+            assert_eq!((typ.as_str(), field.as_str()), ("Vec", "x"))
+        }
+        other => panic!("gave {:?}", other),
+    }
+    assert!(type_error(&format!("{}Vec(x=1)", decl)).is_none());
+}
@@ -0,0 +1,47 @@
+use std::{fs, path::Path};
+
+use gila::{
+    doc::{self, DocFormat},
+    lex::Lexer,
+    parse::Parser,
+};
+
+// renders the modules under tests/doc/src and checks each page against the one in
+// tests/doc/<format>
+fn check_pages(format: &str) {
+    let root = Path::new("./tests/doc/src");
+    let mut modules = vec![];
+    for file in doc::gila_files(root) {
+        let source = fs::read_to_string(&file).expect("Unable to read file");
+        let tokens = Lexer::new().lex(source).expect("Unable to lex");
+        let parse_result = Parser::new(&tokens).parse();
+        assert!(parse_result.errors.is_empty(), "{}", file.display());
+        modules.push((doc::module_name(root, &file), parse_result.ast));
+    }
+
+    let pages = doc::render(&modules, DocFormat::from_name(format).unwrap());
+    let mut names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+    names.sort();
+    let expected_dir = Path::new("./tests/doc").join(format);
+    let mut expected_names: Vec<String> = fs::read_dir(&expected_dir)
+        .expect("Unable to read directory")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    expected_names.sort();
+    assert_eq!(names, expected_names);
+
+    for (name, contents) in &pages {
+        let expected = fs::read_to_string(expected_dir.join(name)).expect("Unable to read file");
+        assert_eq!(contents.trim_end(), expected.trim_end(), "{}", name);
+    }
+}
+
+#[test]
+fn markdown_pages_link_types_across_modules() {
+    check_pages("md");
+}
+
+#[test]
+fn html_pages_link_types_across_modules() {
+    check_pages("html");
+}
@@ -48,18 +48,82 @@
 //     exec_engine.exec(bytecode, false)
 // }
 
-// macro_rules! dynamic_test {
-//     ($test_name:ident, $file_path:expr) => {
-//         #[test]
-//         fn $test_name() {
-//             use std::fs;
-//             let source = fs::read_to_string($file_path).expect("Unable to read file");
-//             let result = compile_and_execute(source);
-//             assert_eq!(result.is_ok(), true);
-//         }
-//     };
-// }
+use std::{fs, path::Path, process::Command};
+
+// runs a fixture through the binary and returns what it printed, without the timing line
+fn run_fixture(file_path: &str, mode: &str) -> (String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_gila"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--file", file_path, "--mode", mode])
+        .output()
+        .expect("Unable to run gila");
+    let stdout = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.starts_with("compiled in"))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    return (stdout, output.status.code());
+}
+
+// each fixture has what it should print next to it in a .out file
+macro_rules! dynamic_test {
+    ($test_name:ident, $file_path:expr) => {
+        #[test]
+        fn $test_name() {
+            let expected = fs::read_to_string(Path::new($file_path).with_extension("out"))
+                .expect("Unable to read file");
+            let (stdout, _) = run_fixture($file_path, "run");
+            assert_eq!(stdout.trim_end(), expected.trim_end());
+
+            let (_, status) = run_fixture($file_path, "test");
+            assert_ne!(status, Some(101), "{} panicked in test mode", $file_path);
+        }
+    };
+}
 
-// dynamic_test!(test_constructor, "./tests/gila/constructor.gila");
-// dynamic_test!(addition, "./tests/gila/addition.gila");
-// dynamic_test!(logical_operators, "./tests/gila/logical_operators.gila");
+dynamic_test!(addition, "./tests/gila/addition.gila");
+dynamic_test!(annotations, "./tests/gila/annotations.gila");
+dynamic_test!(arguments, "./tests/gila/arguments.gila");
+dynamic_test!(circular_import, "./tests/gila/circular_import.gila");
+dynamic_test!(closures, "./tests/gila/closures.gila");
+dynamic_test!(constructor, "./tests/gila/constructor.gila");
+dynamic_test!(destructuring, "./tests/gila/destructuring.gila");
+dynamic_test!(destructuring_mismatch, "./tests/gila/destructuring_mismatch.gila");
+dynamic_test!(dicts, "./tests/gila/dicts.gila");
+dynamic_test!(enums, "./tests/gila/enums.gila");
+dynamic_test!(for_over_number, "./tests/gila/for_over_number.gila");
+dynamic_test!(for_without_iter, "./tests/gila/for_without_iter.gila");
+dynamic_test!(for_wrong_iter, "./tests/gila/for_wrong_iter.gila");
+dynamic_test!(generic_argument_mismatch, "./tests/gila/generic_argument_mismatch.gila");
+dynamic_test!(generic_field_mismatch, "./tests/gila/generic_field_mismatch.gila");
+dynamic_test!(generic_return_mismatch, "./tests/gila/generic_return_mismatch.gila");
+dynamic_test!(generics, "./tests/gila/generics.gila");
+dynamic_test!(indexing, "./tests/gila/indexing.gila");
+dynamic_test!(interfaces, "./tests/gila/interfaces.gila");
+dynamic_test!(interpolation, "./tests/gila/interpolation.gila");
+dynamic_test!(invalid_argument, "./tests/gila/invalid_argument.gila");
+dynamic_test!(invalid_index, "./tests/gila/invalid_index.gila");
+dynamic_test!(jump_too_far, "./tests/gila/jump_too_far.gila");
+dynamic_test!(logical_operators, "./tests/gila/logical_operators.gila");
+dynamic_test!(loops, "./tests/gila/loops.gila");
+dynamic_test!(match_, "./tests/gila/match.gila");
+dynamic_test!(missing_export, "./tests/gila/missing_export.gila");
+dynamic_test!(module_globals, "./tests/gila/module_globals.gila");
+dynamic_test!(modules, "./tests/gila/modules.gila");
+dynamic_test!(multiline_strings, "./tests/gila/multiline_strings.gila");
+dynamic_test!(native_arity, "./tests/gila/native_arity.gila");
+dynamic_test!(not_iterable, "./tests/gila/not_iterable.gila");
+dynamic_test!(numbers, "./tests/gila/numbers.gila");
+dynamic_test!(operators, "./tests/gila/operators.gila");
+dynamic_test!(out_of_bounds, "./tests/gila/out_of_bounds.gila");
+dynamic_test!(out_of_scope, "./tests/gila/out_of_scope.gila");
+dynamic_test!(overloading, "./tests/gila/overloading.gila");
+dynamic_test!(printing, "./tests/gila/printing.gila");
+dynamic_test!(range_out_of_bounds, "./tests/gila/range_out_of_bounds.gila");
+dynamic_test!(reversed_range, "./tests/gila/reversed_range.gila");
+dynamic_test!(scoping, "./tests/gila/scoping.gila");
+dynamic_test!(shadowing, "./tests/gila/shadowing.gila");
+dynamic_test!(test_only, "./tests/gila/test_only.gila");
+dynamic_test!(unknown_export, "./tests/gila/unknown_export.gila");
+dynamic_test!(unresolved_generic, "./tests/gila/unresolved_generic.gila");
+dynamic_test!(variadic, "./tests/gila/variadic.gila");
@@ -0,0 +1,168 @@
+use gila::{
+    lex::{LexError, LexErrorKind, Lexer, Token, Trivia, TriviaKind},
+    r#type::DataType,
+};
+
+fn lex_lossless(source: &str) -> (Vec<Token>, Vec<Trivia>) {
+    let mut lexer = Lexer::new_lossless();
+    let tokens = lexer.lex(source.to_string()).expect("Unable to lex");
+    return (tokens, lexer.eof_trivia);
+}
+
+fn comments(trivia: &Vec<Trivia>) -> Vec<String> {
+    return trivia
+        .iter()
+        .filter(|t| t.kind != TriviaKind::WHITESPACE)
+        .map(|t| t.text.to_string())
+        .collect();
+}
+
+// the char offset of a line and index
+fn offset(source: &str, line: u32, index: u32) -> usize {
+    let before: usize = source
+        .split_inclusive('\n')
+        .take(line as usize)
+        .map(|l| l.chars().count())
+        .sum();
+    return before + index as usize;
+}
+
+#[test]
+fn comments_trail_their_line_and_lead_the_next() {
+    let (tokens, eof_trivia) = lex_lossless("let x = 1 // one\n// two\nprint(x)\n");
+    let one = tokens.iter().find(|t| t.pos.line == 0 && t.pos.index == 8);
+    assert_eq!(comments(&one.unwrap().trailing_trivia), vec!["// one"]);
+    let print = tokens.iter().find(|t| t.pos.line == 2 && t.pos.index == 0);
+    assert_eq!(comments(&print.unwrap().leading_trivia), vec!["// two"]);
+    assert!(comments(&eof_trivia).is_empty());
+}
+
+#[test]
+fn block_comments_nest() {
+    let (tokens, _) = lex_lossless("/* a /* b */ c */ x");
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        comments(&tokens[0].leading_trivia),
+        vec!["/* a /* b */ c */"]
+    );
+}
+
+#[test]
+fn comments_at_the_end_of_the_file_are_kept_separately() {
+    let (tokens, eof_trivia) = lex_lossless("x // same line\n// own line\n");
+    assert_eq!(comments(&tokens[0].trailing_trivia), vec!["// same line"]);
+    assert_eq!(comments(&eof_trivia), vec!["// own line"]);
+}
+
+#[test]
+fn comment_only_input_keeps_the_comment() {
+    let (tokens, eof_trivia) = lex_lossless("// nothing else\n");
+    assert!(tokens.is_empty());
+    assert_eq!(comments(&eof_trivia), vec!["// nothing else"]);
+}
+
+#[test]
+fn lossless_lexing_round_trips() {
+    let source = "/// docs\nadd fn(a, b) do  \n\treturn a + b /* sum */\nend\n\n// done\n";
+    let (tokens, eof_trivia) = lex_lossless(source);
+    let chars: Vec<char> = source.chars().collect();
+    let mut rebuilt = "".to_string();
+    for token in &tokens {
+        for t in &token.leading_trivia {
+            rebuilt.push_str(&t.text);
+        }
+        let start = offset(source, token.pos.line, token.pos.index);
+        let end = offset(source, token.pos.line_end, token.pos.index_end);
+        rebuilt.extend(&chars[start..end]);
+        for t in &token.trailing_trivia {
+            rebuilt.push_str(&t.text);
+        }
+    }
+    for t in &eof_trivia {
+        rebuilt.push_str(&t.text);
+    }
+    assert_eq!(rebuilt, source);
+}
+
+#[test]
+fn only_doc_comments_are_kept_normally() {
+    let mut lexer = Lexer::new();
+    let tokens = lexer
+        .lex("// plain\n/// doc\nx  /* block */\n".to_string())
+        .expect("Unable to lex");
+    assert_eq!(tokens[0].leading_trivia.len(), 1);
+    assert_eq!(tokens[0].leading_trivia[0].kind, TriviaKind::DOC_COMMENT);
+    assert!(tokens[0].trailing_trivia.is_empty());
+}
+
+fn lex_errors(source: &str) -> Vec<LexError> {
+    let mut lexer = Lexer::new();
+    return lexer.lex(source.to_string()).err().unwrap_or_default();
+}
+
+// line, index, line_end, index_end
+fn span(error: &LexError) -> (u32, u32, u32, u32) {
+    return (
+        error.pos.line,
+        error.pos.index,
+        error.pos.line_end,
+        error.pos.index_end,
+    );
+}
+
+#[test]
+fn unterminated_strings_point_at_the_string() {
+    let errors = lex_errors("let s = \"abc");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, LexErrorKind::UNTERMINATED_STRING);
+    assert_eq!(span(&errors[0]), (0, 8, 0, 12));
+
+    // a string can run over lines, so it goes on to the end of the file
+    let errors = lex_errors("let x = 1\n  \"abc\nx = 2\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, LexErrorKind::UNTERMINATED_STRING);
+    assert_eq!((errors[0].pos.line, errors[0].pos.index), (1, 2));
+}
+
+#[test]
+fn invalid_escapes_point_at_the_escape() {
+    let errors = lex_errors("print(\"a\\qb\")");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, LexErrorKind::INVALID_ESCAPE('q'));
+    assert_eq!(span(&errors[0]), (0, 8, 0, 10));
+}
+
+#[test]
+fn unexpected_characters_are_all_reported() {
+    let errors = lex_errors("let a = 1 ` 2\nlet b = `\n");
+    let kinds: Vec<LexErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            LexErrorKind::UNEXPECTED_CHARACTER('`'),
+            LexErrorKind::UNEXPECTED_CHARACTER('`')
+        ]
+    );
+    assert_eq!(span(&errors[0]), (0, 10, 0, 11));
+    assert_eq!(span(&errors[1]), (1, 8, 1, 9));
+}
+
+#[test]
+fn integer_literals_past_any_type_are_an_error() {
+    for (source, typ) in [
+        ("9223372036854775809", DataType::I64),
+        ("0x8000000000000001", DataType::I64),
+        ("4294967296u32", DataType::U32),
+        ("2147483649i32", DataType::I32),
+    ] {
+        let errors = lex_errors(source);
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert_eq!(
+            errors[0].kind,
+            LexErrorKind::NUMBER_OUT_OF_RANGE(source.to_string(), typ),
+            "{}",
+            source
+        );
+        assert_eq!(span(&errors[0]), (0, 0, 0, source.len() as u32));
+    }
+}
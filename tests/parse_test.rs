@@ -0,0 +1,84 @@
+use gila::{
+    ast::Statement,
+    lex::Lexer,
+    parse::{Expected, ParseError, ParseResult, Parser},
+    r#type::DataType,
+};
+
+fn parse(source: &str) -> ParseResult {
+    let mut lexer = Lexer::new();
+    let tokens = lexer.lex(source.to_string()).expect("Unable to lex");
+    let mut parser = Parser::new(&tokens);
+    return parser.parse();
+}
+
+fn parse_errors(source: &str) -> Vec<ParseError> {
+    return parse(source).errors;
+}
+
+#[test]
+fn unfinished_call_arguments_are_an_error() {
+    for source in ["f(", "f(1,", "f(a="] {
+        let errors = parse_errors(source);
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert!(errors[0].found.is_none(), "{}", source);
+    }
+}
+
+#[test]
+fn unfinished_parameters_are_an_error() {
+    for source in ["x = fn(", "f fn(", "I interface\n    m fn("] {
+        let errors = parse_errors(source);
+        assert!(!errors.is_empty(), "{}", source);
+        assert!(errors.iter().all(|e| e.found.is_none()), "{}", source);
+    }
+}
+
+#[test]
+fn every_syntax_error_is_reported() {
+    let source =
+        "let a = (1 +\nlet b = 2\nprint(b\nlet c = [1, 2\nprint(c)\nlet d = * 2\nlet e = 3\n";
+    let result = parse(source);
+    let lines: Vec<u32> = result.errors.iter().map(|e| e.pos.line).collect();
+    assert_eq!(lines, vec![1, 3, 4, 5]);
+
+    // the statements between the errors are still parsed
+    let Statement::PROGRAM(statements) = &result.ast.statement else {
+        panic!("expected a program");
+    };
+    let defined: Vec<String> = statements
+        .iter()
+        .filter_map(|s| match &s.statement {
+            Statement::DEFINE(t, _, _) => Some(t.as_identifier().to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(defined, vec!["b", "e"]);
+    assert_eq!(statements.len(), 3);
+}
+
+#[test]
+fn integer_literals_must_fit_their_type() {
+    for (source, typ) in [
+        ("let x = 2147483648i32", DataType::I32),
+        ("let x = 9223372036854775808", DataType::I64),
+        ("let x = 9223372036854775808i64", DataType::I64),
+    ] {
+        let errors = parse_errors(source);
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert_eq!(
+            errors[0].expected,
+            Expected::NUMBER_IN_RANGE(typ),
+            "{}",
+            source
+        );
+    }
+    // the signed types reach one further when they're negative
+    for source in [
+        "let x = -2147483648i32",
+        "let x = -9223372036854775808",
+        "let x = 4294967295u32",
+    ] {
+        assert!(parse_errors(source).is_empty(), "{}", source);
+    }
+}